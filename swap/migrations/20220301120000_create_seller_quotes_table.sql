@@ -0,0 +1,12 @@
+ALTER TABLE peer_addresses ADD COLUMN last_seen INTEGER;
+
+CREATE TABLE if NOT EXISTS seller_quotes
+(
+    id            INTEGER PRIMARY KEY autoincrement NOT NULL,
+    peer_id       TEXT                NOT NULL,
+    price         INTEGER             NOT NULL,
+    min_quantity  INTEGER             NOT NULL,
+    max_quantity  INTEGER             NOT NULL,
+    latency_ms    INTEGER,
+    received_at   INTEGER             NOT NULL
+);
@@ -0,0 +1,7 @@
+CREATE TABLE if NOT EXISTS pending_messages
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    swap_id     TEXT                UNIQUE NOT NULL,
+    peer_id     TEXT                NOT NULL,
+    message     TEXT                NOT NULL
+);
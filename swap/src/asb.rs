@@ -1,13 +1,16 @@
 pub mod command;
 pub mod config;
+pub mod inventory;
 mod event_loop;
 mod network;
 mod rate;
 mod recovery;
+pub mod sweep;
 pub mod tracing;
 
 pub use event_loop::{EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate};
 pub use network::behaviour::{Behaviour, OutEvent};
+pub use network::rendezous;
 pub use network::transport;
 pub use rate::Rate;
 pub use recovery::cancel::cancel;
@@ -16,6 +19,3 @@ pub use recovery::redeem::{redeem, Finality};
 pub use recovery::refund::refund;
 pub use recovery::safely_abort::safely_abort;
 pub use recovery::{cancel, refund};
-
-#[cfg(test)]
-pub use network::rendezous;
@@ -1,5 +1,5 @@
 use crate::asb::config::GetDefaults;
-use crate::bitcoin::Amount;
+use crate::bitcoin::{Amount, DEFAULT_RESCAN_GAP_LIMIT};
 use crate::env;
 use crate::env::GetConfig;
 use anyhow::{bail, Result};
@@ -60,6 +60,50 @@ where
             env_config: env_config(testnet),
             cmd: Command::Balance,
         },
+        RawCommand::BtcHistory => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::BtcHistory,
+        },
+        RawCommand::Seed(SeedCommand::Export) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::ExportSeed,
+        },
+        RawCommand::Seed(SeedCommand::Restore {
+            gap_limit,
+            birthday_height,
+        }) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::RestoreSeed {
+                gap_limit,
+                birthday_height,
+            },
+        },
+        RawCommand::Rescan {
+            gap_limit,
+            birthday_height,
+        } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Rescan {
+                gap_limit,
+                birthday_height,
+            },
+        },
         RawCommand::Config => Arguments {
             testnet,
             json,
@@ -202,6 +246,16 @@ pub enum Command {
         address: Address,
     },
     Balance,
+    BtcHistory,
+    ExportSeed,
+    RestoreSeed {
+        gap_limit: u32,
+        birthday_height: Option<u32>,
+    },
+    Rescan {
+        gap_limit: u32,
+        birthday_height: Option<u32>,
+    },
     Redeem {
         swap_id: Uuid,
         do_not_await_finality: bool,
@@ -286,12 +340,58 @@ pub enum RawCommand {
         about = "Prints the Bitcoin and judecoin balance. Requires the judecoin-wallet-rpc to be running."
     )]
     Balance,
+    #[structopt(
+        about = "Prints the transactions of the internal Bitcoin wallet, labelled by what they were made for."
+    )]
+    BtcHistory,
     #[structopt(about = "Print the internal bitcoin wallet descriptor.")]
     ExportBitcoinWallet,
+    #[structopt(about = "Back up or restore the seed as a BIP39 mnemonic.")]
+    Seed(SeedCommand),
+    #[structopt(
+        about = "Discards the cached state of the internal Bitcoin wallet, rescans it and prints the discovered balance."
+    )]
+    Rescan {
+        #[structopt(
+            long = "gap-limit",
+            help = "Stop looking for funds after this many consecutive unused addresses.",
+            default_value = DEFAULT_RESCAN_GAP_LIMIT
+        )]
+        gap_limit: u32,
+
+        #[structopt(
+            long = "birthday-height",
+            help = "The block height at which the seed was created. Warns if funds are found before it."
+        )]
+        birthday_height: Option<u32>,
+    },
     #[structopt(about = "Contains sub-commands for recovering a swap manually.")]
     ManualRecovery(ManualRecovery),
 }
 
+#[derive(structopt::StructOpt, Debug)]
+pub enum SeedCommand {
+    #[structopt(about = "Prints the seed as a 24 word BIP39 mnemonic.")]
+    Export,
+    #[structopt(
+        about = "Restores the seed from a BIP39 mnemonic read from stdin and rescans the internal Bitcoin wallet. Fails if a seed already exists."
+    )]
+    Restore {
+        #[structopt(
+            long = "gap-limit",
+            help = "Stop looking for funds after this many consecutive unused addresses.",
+            default_value = DEFAULT_RESCAN_GAP_LIMIT
+        )]
+        gap_limit: u32,
+
+        #[structopt(
+            long = "birthday-height",
+            help = "The block height at which the seed was created. Warns if funds are found before it."
+        )]
+        birthday_height: Option<u32>,
+    },
+}
+
 #[derive(structopt::StructOpt, Debug)]
 pub enum ManualRecovery {
     #[structopt(
@@ -411,6 +511,72 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_btc_history_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "btc-history"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::BtcHistory,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_seed_restore_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "seed",
+            "restore",
+            "--birthday-height",
+            "700000",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::RestoreSeed {
+                gap_limit: 100,
+                birthday_height: Some(700_000),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_rescan_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "rescan"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Rescan {
+                gap_limit: 100,
+                birthday_height: None,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
     #[test]
     fn ensure_withdraw_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -1,3 +1,5 @@
+use crate::asb::inventory;
+use crate::bitcoin::{FeeEstimator, FeeStrategy, TargetBlocks};
 use crate::env::{Mainnet, Testnet};
 use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
@@ -9,6 +11,7 @@ use libp2p::core::Multiaddr;
 use rust_decimal::prelude::FromPrimitive;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr, OneOrMany};
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -83,6 +86,8 @@ fn default_asb_data_dir() -> Result<PathBuf> {
 const DEFAULT_MIN_BUY_AMOUNT: f64 = 0.002f64;
 const DEFAULT_MAX_BUY_AMOUNT: f64 = 0.02f64;
 const DEFAULT_SPREAD: f64 = 0.02f64;
+const DEFAULT_SWEEP_INTERVAL_SECS: u64 = 600;
+const DEFAULT_INVENTORY_CHECK_INTERVAL_SECS: u64 = 600;
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
@@ -114,12 +119,15 @@ pub struct Data {
     pub dir: PathBuf,
 }
 
+#[serde_as]
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Network {
     pub listen: Vec<Multiaddr>,
+    /// Accepts a single address as well as a list of addresses.
+    #[serde_as(as = "OneOrMany<_>")]
     #[serde(default)]
-    pub rendezvous_point: Option<Multiaddr>,
+    pub rendezvous_point: Vec<Multiaddr>,
     #[serde(default)]
     pub external_addresses: Vec<Multiaddr>,
 }
@@ -132,6 +140,66 @@ pub struct Bitcoin {
     pub finality_confirmations: Option<u32>,
     #[serde(with = "crate::bitcoin::network")]
     pub network: bitcoin::Network,
+    #[serde(default)]
+    pub min_sat_per_vb: Option<f32>,
+    #[serde(default)]
+    pub max_sat_per_vb: Option<f32>,
+    /// Extended public key or output descriptor of the wallet redeemed and
+    /// punished Bitcoin is sent to. If not set, the internal wallet is used.
+    #[serde(default)]
+    pub payout_descriptor: Option<String>,
+    #[serde(default)]
+    pub fee_estimator: FeeEstimator,
+    #[serde(default)]
+    pub target_blocks: TargetBlocks,
+    #[serde(default)]
+    pub sweep: Option<SweepPolicy>,
+}
+
+/// Moves Bitcoin above `threshold_btc` out of the internal wallet.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SweepPolicy {
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    pub threshold_btc: bitcoin::Amount,
+    /// Cold storage address. If not set, addresses are derived from
+    /// `payout_descriptor`.
+    #[serde(default)]
+    pub address: Option<bitcoin::Address>,
+    #[serde(default = "default_sweep_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_sweep_interval_secs() -> u64 {
+    DEFAULT_SWEEP_INTERVAL_SECS
+}
+
+impl SweepPolicy {
+    pub fn validate(&self, network: bitcoin::Network) -> Result<()> {
+        if let Some(address) = &self.address {
+            if address.network != network {
+                bail!(
+                    "Expected sweep address in config file to be on {:?} but was on {:?}",
+                    network,
+                    address.network
+                )
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Bitcoin {
+    pub fn fee_strategy(&self) -> FeeStrategy {
+        FeeStrategy {
+            estimator: self.fee_estimator.clone(),
+            target_block: self.target_block,
+            target_blocks: self.target_blocks,
+            min_sat_per_vb: self.min_sat_per_vb,
+            max_sat_per_vb: self.max_sat_per_vb,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -141,6 +209,66 @@ pub struct judecoin {
     pub finality_confirmations: Option<u64>,
     #[serde(with = "crate::judecoin::network")]
     pub network: judecoin::Network,
+    #[serde(default)]
+    pub inventory: Inventory,
+}
+
+/// Bounds for the JUDE balance of the ASB's wallet.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Inventory {
+    /// Below this balance the ASB alerts and stops quoting.
+    #[serde(default)]
+    pub floor_jude: Option<Decimal>,
+    /// JUDE above this balance is sent to `cold_address`.
+    #[serde(default)]
+    pub ceiling_jude: Option<Decimal>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub cold_address: Option<judecoin::Address>,
+    #[serde(default = "default_inventory_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+fn default_inventory_check_interval_secs() -> u64 {
+    DEFAULT_INVENTORY_CHECK_INTERVAL_SECS
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            floor_jude: None,
+            ceiling_jude: None,
+            cold_address: None,
+            check_interval_secs: DEFAULT_INVENTORY_CHECK_INTERVAL_SECS,
+        }
+    }
+}
+
+impl Inventory {
+    pub fn policy(&self) -> Result<inventory::Policy> {
+        let floor = self
+            .floor_jude
+            .map(crate::judecoin::Amount::from_decimal)
+            .transpose()?;
+
+        let ceiling = match (self.ceiling_jude, self.cold_address) {
+            (Some(ceiling), Some(address)) => {
+                Some((crate::judecoin::Amount::from_decimal(ceiling)?, address))
+            }
+            (None, None) => None,
+            _ => bail!("JUDE inventory ceiling and cold address have to be configured together"),
+        };
+
+        if let (Some(floor), Some((ceiling, _))) = (floor, ceiling) {
+            if floor >= ceiling {
+                bail!("JUDE inventory floor has to be below the ceiling")
+            }
+        }
+
+        Ok(inventory::Policy { floor, ceiling })
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -301,9 +429,9 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         network: Network {
             listen: listen_addresses,
             rendezvous_point: if rendezvous_point.is_empty() {
-                None
+                vec![]
             } else {
-                Some(rendezvous_point)
+                vec![rendezvous_point]
             },
             external_addresses: vec![],
         },
@@ -312,11 +440,18 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             target_block,
             finality_confirmations: None,
             network: bitcoin_network,
+            min_sat_per_vb: None,
+            max_sat_per_vb: None,
+            payout_descriptor: None,
+            fee_estimator: FeeEstimator::Electrum,
+            target_blocks: TargetBlocks::default(),
+            sweep: None,
         },
         judecoin: judecoin {
             wallet_rpc_url: judecoin_wallet_rpc_url,
             finality_confirmations: None,
             network: judecoin_network,
+            inventory: Inventory::default(),
         },
         tor: TorConf {
             control_port: tor_control_port,
@@ -352,10 +487,16 @@ mod tests {
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Testnet,
+                min_sat_per_vb: None,
+                max_sat_per_vb: None,
+                payout_descriptor: None,
+                fee_estimator: FeeEstimator::Electrum,
+                target_blocks: TargetBlocks::default(),
+                sweep: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
-                rendezvous_point: None,
+                rendezvous_point: vec![],
                 external_addresses: vec![],
             },
 
@@ -363,6 +504,7 @@ mod tests {
                 wallet_rpc_url: defaults.judecoin_wallet_rpc_url,
                 finality_confirmations: None,
                 network: judecoin::Network::Stagenet,
+                inventory: Inventory::default(),
             },
             tor: Default::default(),
             maker: Maker {
@@ -395,10 +537,16 @@ mod tests {
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Bitcoin,
+                min_sat_per_vb: None,
+                max_sat_per_vb: None,
+                payout_descriptor: None,
+                fee_estimator: FeeEstimator::Electrum,
+                target_blocks: TargetBlocks::default(),
+                sweep: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
-                rendezvous_point: None,
+                rendezvous_point: vec![],
                 external_addresses: vec![],
             },
 
@@ -406,6 +554,7 @@ mod tests {
                 wallet_rpc_url: defaults.judecoin_wallet_rpc_url,
                 finality_confirmations: None,
                 network: judecoin::Network::Mainnet,
+                inventory: Inventory::default(),
             },
             tor: Default::default(),
             maker: Maker {
@@ -421,4 +570,139 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn config_roundtrip_with_fee_strategy() {
+        let temp_dir = tempdir().unwrap().path().to_path_buf();
+        let config_path = Path::join(&temp_dir, "config.toml");
+
+        let defaults = Mainnet::getConfigFileDefaults().unwrap();
+
+        let expected = Config {
+            data: Data {
+                dir: Default::default(),
+            },
+            bitcoin: Bitcoin {
+                electrum_rpc_url: defaults.electrum_rpc_url,
+                target_block: defaults.bitcoin_confirmation_target,
+                finality_confirmations: None,
+                network: bitcoin::Network::Bitcoin,
+                min_sat_per_vb: Some(2.0),
+                max_sat_per_vb: Some(200.0),
+                payout_descriptor: None,
+                fee_estimator: FeeEstimator::Mempool {
+                    api_url: Url::parse("https://mempool.space/api/").unwrap(),
+                },
+                target_blocks: TargetBlocks {
+                    lock: Some(1),
+                    punish: Some(6),
+                    ..TargetBlocks::default()
+                },
+                sweep: None,
+            },
+            network: Network {
+                listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                rendezvous_point: vec![],
+                external_addresses: vec![],
+            },
+
+            judecoin: judecoin {
+                wallet_rpc_url: defaults.judecoin_wallet_rpc_url,
+                finality_confirmations: None,
+                network: judecoin::Network::Mainnet,
+                inventory: Inventory::default(),
+            },
+            tor: Default::default(),
+            maker: Maker {
+                min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
+                max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
+                ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
+                price_ticker_ws_url: defaults.price_ticker_ws_url,
+            },
+        };
+
+        initial_setup(config_path.clone(), expected.clone()).unwrap();
+        let actual = read_config(config_path).unwrap().unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn sweep_interval_defaults_when_not_set() {
+        let policy: SweepPolicy = toml::from_str(
+            r#"
+            threshold_btc = 0.5
+            address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(policy, SweepPolicy {
+            threshold_btc: bitcoin::Amount::from_btc(0.5).unwrap(),
+            address: Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".parse().unwrap()),
+            interval_secs: DEFAULT_SWEEP_INTERVAL_SECS,
+        });
+    }
+
+    #[test]
+    fn given_sweep_address_on_other_network_then_validation_fails() {
+        let policy: SweepPolicy = toml::from_str(
+            r#"
+            threshold_btc = 0.5
+            address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+            "#,
+        )
+        .unwrap();
+
+        assert!(policy.validate(bitcoin::Network::Bitcoin).is_ok());
+        assert!(policy.validate(bitcoin::Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn given_ceiling_without_cold_address_then_inventory_policy_fails() {
+        let inventory = Inventory {
+            ceiling_jude: Some(Decimal::from(10)),
+            ..Inventory::default()
+        };
+
+        assert!(inventory.policy().is_err());
+    }
+
+    #[test]
+    fn given_floor_above_ceiling_then_inventory_policy_fails() {
+        let inventory = Inventory {
+            floor_jude: Some(Decimal::from(20)),
+            ceiling_jude: Some(Decimal::from(10)),
+            cold_address: Some("53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse().unwrap()),
+            ..Inventory::default()
+        };
+
+        assert!(inventory.policy().is_err());
+    }
+
+    #[test]
+    fn rendezvous_point_accepts_single_address_or_list() {
+        let single: Network = toml::from_str(
+            r#"
+            listen = []
+            rendezvous_point = "/dns4/rendezvous.example.com/tcp/8888"
+            "#,
+        )
+        .unwrap();
+        let list: Network = toml::from_str(
+            r#"
+            listen = []
+            rendezvous_point = [
+                "/dns4/rendezvous.example.com/tcp/8888",
+                "/dns4/rendezvous.example.org/tcp/8888",
+            ]
+            "#,
+        )
+        .unwrap();
+        let none: Network = toml::from_str("listen = []").unwrap();
+
+        assert_eq!(single.rendezvous_point.len(), 1);
+        assert_eq!(list.rendezvous_point.len(), 2);
+        assert!(none.rendezvous_point.is_empty());
+    }
 }
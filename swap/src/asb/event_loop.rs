@@ -1,23 +1,27 @@
-use crate::asb::{Behaviour, OutEvent, Rate};
+use crate::asb::{inventory, Behaviour, OutEvent, Rate};
+use crate::bitcoin::wallet::ScriptStatus;
 use crate::network::quote::BidQuote;
+use crate::network::redial;
 use crate::network::swap_setup::alice::WalletSnapshot;
 use crate::network::transfer_proof;
 use crate::protocol::alice::{AliceState, State3, Swap};
-use crate::protocol::{Database, State};
+use crate::protocol::{Database, PendingMessage, State};
 use crate::{bitcoin, env, kraken, judecoin};
 use anyhow::{Context, Result};
 use futures::future;
 use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
+use libp2p::multiaddr::Protocol;
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::{Multiaddr, PeerId, Swarm};
 use rust_decimal::Decimal;
 use std::collections::HashMap;
 use std::convert::{Infallible, TryInto};
 use std::fmt::Debug;
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 use uuid::Uuid;
 
 /// A future that resolves to a tuple of `PeerId`, `transfer_proof::Request` and
@@ -30,6 +34,9 @@ use uuid::Uuid;
 type OutgoingTransferProof =
     BoxFuture<'static, Result<(PeerId, transfer_proof::Request, bmrng::Responder<()>)>>;
 
+/// The maximum number of onion addresses we keep per peer.
+const MAX_ADVERTISED_ADDRESSES: usize = 4;
+
 #[allow(missing_debug_implementations)]
 pub struct EventLoop<LR>
 where
@@ -38,11 +45,14 @@ where
     swarm: libp2p::Swarm<Behaviour<LR>>,
     env_config: env::Config,
     bitcoin_wallet: Arc<bitcoin::Wallet>,
+    payout_wallet: Option<Arc<bitcoin::PayoutWallet>>,
     judecoin_wallet: Arc<judecoin::Wallet>,
     db: Arc<dyn Database + Send + Sync>,
     latest_rate: LR,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    /// Stop accepting swaps while the unlocked JUDE balance is below the floor.
+    jude_floor: Option<inventory::Floor>,
 
     swap_sender: mpsc::Sender<Swap>,
 
@@ -52,13 +62,25 @@ where
 
     send_transfer_proof: FuturesUnordered<OutgoingTransferProof>,
 
-    /// Tracks [`transfer_proof::Request`]s which could not yet be sent because
-    /// we are currently disconnected from the peer.
-    buffered_transfer_proofs: HashMap<PeerId, Vec<(transfer_proof::Request, bmrng::Responder<()>)>>,
+    /// Tracks the swaps waiting for Bob to acknowledge their transfer proof.
+    ///
+    /// The [`transfer_proof::Request`]s themselves are kept in the database
+    /// until they are acknowledged and are sent again whenever Bob reconnects.
+    transfer_proof_responders: HashMap<Uuid, bmrng::Responder<()>>,
 
     /// Tracks [`transfer_proof::Request`]s which are currently inflight and
     /// awaiting an acknowledgement.
-    inflight_transfer_proofs: HashMap<RequestId, bmrng::Responder<()>>,
+    inflight_transfer_proofs: HashMap<RequestId, Uuid>,
+
+    /// Tracks the peer of every swap that still needs to reach Bob, i.e. which
+    /// has not received the encrypted signature yet.
+    active_swaps: HashMap<Uuid, PeerId>,
+
+    /// The onion addresses connected peers told us about.
+    ///
+    /// They are only persisted for peers we have an active swap with, so that
+    /// we can redial them to deliver pending messages.
+    advertised_addresses: HashMap<PeerId, Vec<Multiaddr>>,
 }
 
 impl<LR> EventLoop<LR>
@@ -70,11 +92,13 @@ where
         swarm: Swarm<Behaviour<LR>>,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
+        payout_wallet: Option<Arc<bitcoin::PayoutWallet>>,
         judecoin_wallet: Arc<judecoin::Wallet>,
         db: Arc<dyn Database + Send + Sync>,
         latest_rate: LR,
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        jude_floor: Option<inventory::Floor>,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
 
@@ -82,17 +106,21 @@ where
             swarm,
             env_config,
             bitcoin_wallet,
+            payout_wallet,
             judecoin_wallet,
             db,
             latest_rate,
             swap_sender: swap_channel.sender,
             min_buy,
             max_buy,
+            jude_floor,
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
             send_transfer_proof: Default::default(),
-            buffered_transfer_proofs: Default::default(),
+            transfer_proof_responders: Default::default(),
             inflight_transfer_proofs: Default::default(),
+            active_swaps: Default::default(),
+            advertised_addresses: Default::default(),
         };
         Ok((event_loop, swap_channel.receiver))
     }
@@ -164,7 +192,7 @@ where
                                 }
                             };
 
-                            let wallet_snapshot = match WalletSnapshot::capture(&self.bitcoin_wallet, &self.judecoin_wallet, btc).await {
+                            let wallet_snapshot = match WalletSnapshot::capture(&self.bitcoin_wallet, self.payout_wallet.as_ref(), &self.judecoin_wallet, btc, self.is_below_jude_floor()).await {
                                 Ok(wallet_snapshot) => wallet_snapshot,
                                 Err(error) => {
                                     tracing::error!("Swap request will be ignored because we were unable to create wallet snapshot for swap: {:#}", error);
@@ -196,8 +224,22 @@ where
                         }
                         SwarmEvent::Behaviour(OutEvent::TransferProofAcknowledged { peer, id }) => {
                             tracing::debug!(%peer, "Bob acknowledged transfer proof");
-                            if let Some(responder) = self.inflight_transfer_proofs.remove(&id) {
-                                let _ = responder.respond(());
+                            if let Some(swap_id) = self.inflight_transfer_proofs.remove(&id) {
+                                if let Err(error) = self.db.remove_pending_message(swap_id).await {
+                                    tracing::warn!(%swap_id, "Failed to remove acknowledged transfer proof from database: {:#}", error);
+                                }
+
+                                self.redial_until_delivered(peer).await;
+
+                                if let Some(responder) = self.transfer_proof_responders.remove(&swap_id) {
+                                    let _ = responder.respond(());
+                                }
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::TransferProofFailed { peer, id, error }) => {
+                            // the transfer proof stays in the database and is sent again once Bob reconnects
+                            if let Some(swap_id) = self.inflight_transfer_proofs.remove(&id) {
+                                tracing::warn!(%peer, %swap_id, "Failed to send transfer proof: {:#}", error);
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::EncryptedSignatureReceived{ msg, channel, peer }) => {
@@ -234,6 +276,7 @@ where
                                     continue;
                                 }
                             };
+                            self.active_swaps.remove(&swap_id);
 
                             let mut responder = match sender.send(msg.tx_redeem_encsig).await {
                                 Ok(responder) => responder,
@@ -255,21 +298,38 @@ where
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::RegisterFailed(error))) => {
                             tracing::error!("Registration with rendezvous node failed: {:?}", error);
                         }
+                        SwarmEvent::Behaviour(OutEvent::Identified { peer, listen_addrs }) => {
+                            // a CLI listening on an onion service stays reachable behind a NAT and across IP changes
+                            let onion_addresses = listen_addrs
+                                .into_iter()
+                                .filter(|address| matches!(address.iter().next(), Some(Protocol::Onion3(_))))
+                                .take(MAX_ADVERTISED_ADDRESSES)
+                                .collect::<Vec<_>>();
+
+                            if onion_addresses.is_empty() {
+                                continue;
+                            }
+
+                            tracing::debug!(%peer, addresses = ?onion_addresses, "Peer is listening on onion service");
+                            self.advertised_addresses.insert(peer, onion_addresses);
+
+                            if self.active_swaps.values().any(|swap_peer| swap_peer == &peer) {
+                                self.remember_advertised_addresses(peer).await;
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer }) => {
+                            tracing::warn!(%peer, "Giving up redialling peer, pending messages will be sent once it reconnects");
+                        }
                         SwarmEvent::Behaviour(OutEvent::Failure {peer, error}) => {
                             tracing::error!(
                                 %peer,
                                 "Communication error: {:#}", error);
                         }
-                        SwarmEvent::ConnectionEstablished { peer_id: peer, endpoint, .. } => {
+                        SwarmEvent::ConnectionEstablished { peer_id: peer, endpoint, num_established, .. } => {
                             tracing::debug!(%peer, address = %endpoint.get_remote_address(), "New connection established");
 
-                            if let Some(transfer_proofs) = self.buffered_transfer_proofs.remove(&peer) {
-                                for (transfer_proof, responder) in transfer_proofs {
-                                    tracing::debug!(%peer, "Found buffered transfer proof for peer");
-
-                                    let id = self.swarm.behaviour_mut().transfer_proof.send_request(&peer, transfer_proof);
-                                    self.inflight_transfer_proofs.insert(id, responder);
-                                }
+                            if num_established.get() == 1 {
+                                self.replay_pending_transfer_proofs(peer).await;
                             }
                         }
                         SwarmEvent::IncomingConnectionError { send_back_addr: address, error, .. } => {
@@ -277,9 +337,11 @@ where
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: Some(error) } if num_established == 0 => {
                             tracing::debug!(%peer, address = %endpoint.get_remote_address(), "Lost connection to peer: {:#}", error);
+                            self.advertised_addresses.remove(&peer);
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: None } if num_established == 0 => {
                             tracing::info!(%peer, address = %endpoint.get_remote_address(), "Successfully closed connection");
+                            self.advertised_addresses.remove(&peer);
                         }
                         SwarmEvent::NewListenAddr{address, ..} => {
                             tracing::info!(%address, "New listen address reported");
@@ -290,14 +352,25 @@ where
                 next_transfer_proof = self.send_transfer_proof.next() => {
                     match next_transfer_proof {
                         Some(Ok((peer, transfer_proof, responder))) => {
+                            let swap_id = transfer_proof.swap_id;
+
+                            if let Err(error) = self.db.insert_pending_message(peer, PendingMessage::TransferProof(transfer_proof.clone())).await {
+                                tracing::warn!(%swap_id, "Failed to persist transfer proof, it will be lost on restart: {:#}", error);
+                            }
+                            self.transfer_proof_responders.insert(swap_id, responder);
+                            self.redial_until_delivered(peer).await;
+
                             if !self.swarm.behaviour_mut().transfer_proof.is_connected(&peer) {
-                                tracing::warn!(%peer, "No active connection to peer, buffering transfer proof");
-                                self.buffered_transfer_proofs.entry(peer).or_insert_with(Vec::new).push((transfer_proof, responder));
+                                tracing::warn!(%peer, "No active connection to peer, sending transfer proof once it reconnects");
+                                continue;
+                            }
+
+                            // A transfer proof replayed from the database may already be on its way
+                            if self.inflight_transfer_proofs.values().any(|inflight| *inflight == swap_id) {
                                 continue;
                             }
 
-                            let id = self.swarm.behaviour_mut().transfer_proof.send_request(&peer, transfer_proof);
-                            self.inflight_transfer_proofs.insert(id, responder);
+                            self.send_transfer_proof(peer, transfer_proof);
                         },
                         Some(Err(error)) => {
                             tracing::debug!("A swap stopped without sending a transfer proof: {:#}", error);
@@ -314,6 +387,142 @@ where
         }
     }
 
+    fn send_transfer_proof(&mut self, peer: PeerId, transfer_proof: transfer_proof::Request) {
+        let swap_id = transfer_proof.swap_id;
+
+        // requests sent over a previous connection will never be acknowledged
+        self.inflight_transfer_proofs
+            .retain(|_, inflight| *inflight != swap_id);
+
+        let id = self
+            .swarm
+            .behaviour_mut()
+            .transfer_proof
+            .send_request(&peer, transfer_proof);
+        self.inflight_transfer_proofs.insert(id, swap_id);
+    }
+
+    /// Sends all transfer proofs Bob has not yet acknowledged, including those
+    /// queued before a restart.
+    async fn replay_pending_transfer_proofs(&mut self, peer: PeerId) {
+        let pending_messages = match self.db.get_pending_messages(peer).await {
+            Ok(pending_messages) => pending_messages,
+            Err(error) => {
+                tracing::warn!(%peer, "Failed to load pending transfer proofs from database: {:#}", error);
+                return;
+            }
+        };
+
+        for message in pending_messages {
+            if let PendingMessage::TransferProof(transfer_proof) = message {
+                tracing::debug!(%peer, swap_id = %transfer_proof.swap_id, "Sending pending transfer proof to peer");
+                self.send_transfer_proof(peer, transfer_proof);
+            }
+        }
+    }
+
+    /// Redials the peer at every address we know of whenever we are
+    /// disconnected, until all messages pending for it are acknowledged or
+    /// none of them is of use to the peer anymore.
+    async fn redial_until_delivered(&mut self, peer: PeerId) {
+        let policy = match self.redial_policy(peer).await {
+            Ok(policy) => policy,
+            Err(error) => {
+                tracing::warn!(%peer, "Failed to determine how long to redial peer: {:#}", error);
+                return;
+            }
+        };
+        let addresses = match self.db.get_addresses(peer).await {
+            Ok(addresses) => addresses,
+            Err(error) => {
+                tracing::warn!(%peer, "Failed to load addresses of peer from database: {:#}", error);
+                return;
+            }
+        };
+
+        if policy == redial::Policy::Never {
+            self.swarm.behaviour_mut().redial.stop_redial(&peer);
+            return;
+        }
+
+        if addresses.is_empty() {
+            tracing::debug!(%peer, "No known address to redial peer at");
+            return;
+        }
+
+        self.swarm
+            .behaviour_mut()
+            .redial
+            .redial(peer, addresses, policy);
+    }
+
+    /// Redials the peer until the last cancel timelock of the swaps we still
+    /// owe it a transfer proof for expires, the transfer proof is of no use to
+    /// Bob afterwards.
+    async fn redial_policy(&self, peer: PeerId) -> Result<redial::Policy> {
+        let mut deadline = None;
+
+        for message in self.db.get_pending_messages(peer).await? {
+            let state: AliceState = self.db.get_state(message.swap_id()).await?.try_into()?;
+            let state3 = match state {
+                AliceState::XmrLocked { state3, .. } => state3,
+                _ => continue,
+            };
+
+            let blocks_left = match self
+                .bitcoin_wallet
+                .status_of_script(&state3.tx_lock)
+                .await?
+            {
+                ScriptStatus::Confirmed(confirmed) => {
+                    u32::from(state3.cancel_timelock).saturating_sub(confirmed.confirmations())
+                }
+                _ => u32::from(state3.cancel_timelock),
+            };
+            let swap_deadline =
+                Instant::now() + self.env_config.bitcoin_avg_block_time * blocks_left;
+
+            deadline = deadline.max(Some(swap_deadline));
+        }
+
+        Ok(deadline.map_or(redial::Policy::Never, redial::Policy::GiveUpAt))
+    }
+
+    /// Stores the onion addresses the peer advertised so that it can be
+    /// redialled, keeping at most [`MAX_ADVERTISED_ADDRESSES`] per peer.
+    async fn remember_advertised_addresses(&self, peer: PeerId) {
+        let addresses = match self.advertised_addresses.get(&peer) {
+            Some(addresses) => addresses,
+            None => return,
+        };
+
+        let result = async {
+            let known = self.db.get_addresses(peer).await?;
+            let new_addresses = addresses
+                .iter()
+                .filter(|address| !known.contains(address))
+                .take(MAX_ADVERTISED_ADDRESSES.saturating_sub(known.len()));
+
+            for address in new_addresses {
+                self.db.insert_address(peer, address.clone()).await?;
+            }
+
+            Ok::<_, anyhow::Error>(())
+        };
+
+        if let Err(error) = result.await {
+            tracing::warn!(%peer, "Failed to store addresses of peer: {:#}", error);
+        }
+    }
+
+    /// Whether the last inventory check found the unlocked JUDE balance below
+    /// the configured floor.
+    fn is_below_jude_floor(&self) -> bool {
+        self.jude_floor
+            .as_ref()
+            .map_or(false, |floor| floor.is_undercut())
+    }
+
     async fn make_quote(
         &mut self,
         min_buy: bitcoin::Amount,
@@ -324,6 +533,13 @@ where
             .latest_rate()
             .context("Failed to get latest rate")?;
 
+        let max_buy = if self.is_below_jude_floor() {
+            tracing::debug!("JUDE balance is below the configured floor, quoting zero");
+            bitcoin::Amount::ZERO
+        } else {
+            max_buy
+        };
+
         Ok(BidQuote {
             price: rate.ask().context("Failed to compute asking price")?,
             min_quantity: min_buy,
@@ -338,6 +554,7 @@ where
         state3: State3,
     ) {
         let handle = self.new_handle(bob_peer_id, swap_id);
+        self.remember_advertised_addresses(bob_peer_id).await;
 
         let initial_state = AliceState::Started {
             state3: Box::new(state3),
@@ -379,6 +596,7 @@ where
 
         self.recv_encrypted_signature
             .insert(swap_id, encrypted_signature.0);
+        self.active_swaps.insert(swap_id, peer);
 
         self.send_transfer_proof.push(
             async move {
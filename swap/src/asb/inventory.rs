@@ -0,0 +1,158 @@
+use crate::judecoin::{self, Address, Amount};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Keeps the amount of JUDE held by the ASB's wallet within bounds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    /// Below this balance we alert and stop quoting.
+    pub floor: Option<Amount>,
+    /// JUDE above this balance is sent to the cold address.
+    pub ceiling: Option<(Amount, Address)>,
+}
+
+impl Policy {
+    pub fn is_below_floor(&self, balance: Amount) -> bool {
+        self.floor.map_or(false, |floor| balance < floor)
+    }
+
+    fn excess(&self, balance: Amount) -> Option<(Amount, Address)> {
+        let (ceiling, address) = self.ceiling?;
+
+        if balance > ceiling {
+            Some((balance - ceiling, address))
+        } else {
+            None
+        }
+    }
+}
+
+/// The JUDE floor together with the unlocked balance found by the last
+/// inventory check.
+///
+/// Lets quotes and swap requests be checked against the floor without
+/// querying the wallet.
+#[derive(Clone, Debug)]
+pub struct Floor {
+    floor: Amount,
+    unlocked_balance: watch::Receiver<Option<Amount>>,
+}
+
+impl Floor {
+    pub fn new(floor: Amount, unlocked_balance: watch::Receiver<Option<Amount>>) -> Self {
+        Self {
+            floor,
+            unlocked_balance,
+        }
+    }
+
+    /// Whether the last inventory check found the unlocked balance below the
+    /// floor.
+    pub fn is_undercut(&self) -> bool {
+        self.unlocked_balance
+            .borrow()
+            .map_or(false, |balance| balance < self.floor)
+    }
+}
+
+/// Periodically checks the unlocked JUDE balance against the given policy
+/// and publishes it to `unlocked_balance`.
+pub async fn run(
+    judecoin_wallet: Arc<judecoin::Wallet>,
+    policy: Policy,
+    interval: Duration,
+    unlocked_balance: watch::Sender<Option<Amount>>,
+) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        match check(&judecoin_wallet, &policy).await {
+            Ok(balance) => {
+                let _ = unlocked_balance.send(Some(balance));
+            }
+            Err(error) => {
+                tracing::warn!("Failed to check JUDE inventory: {:#}", error);
+            }
+        }
+    }
+}
+
+async fn check(judecoin_wallet: &judecoin::Wallet, policy: &Policy) -> Result<Amount> {
+    let balance = judecoin_wallet.get_unlocked_balance().await?;
+
+    if policy.is_below_floor(balance) {
+        tracing::warn!(
+            %balance,
+            address = %judecoin_wallet.get_main_address(),
+            "JUDE balance is below the configured floor, not accepting swaps until topped up",
+        );
+    }
+
+    if let Some((amount, address)) = policy.excess(balance) {
+        let tx_hash = judecoin_wallet.transfer_to(address, amount).await?;
+
+        tracing::info!(%amount, %address, tx_hash = %tx_hash.0, "Swept JUDE above the configured ceiling");
+    }
+
+    Ok(balance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const COLD_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
+
+    fn policy() -> Policy {
+        Policy {
+            floor: Some(Amount::from_piconero(10)),
+            ceiling: Some((
+                Amount::from_piconero(100),
+                Address::from_str(COLD_ADDRESS).unwrap(),
+            )),
+        }
+    }
+
+    #[test]
+    fn given_balance_below_floor_then_alerts() {
+        assert!(policy().is_below_floor(Amount::from_piconero(9)));
+        assert!(!policy().is_below_floor(Amount::from_piconero(10)));
+    }
+
+    #[test]
+    fn floor_is_undercut_once_the_last_check_found_the_balance_below_it() {
+        let (sender, receiver) = watch::channel(None);
+        let floor = Floor::new(Amount::from_piconero(10), receiver);
+
+        assert!(!floor.is_undercut());
+
+        sender.send(Some(Amount::from_piconero(9))).unwrap();
+        assert!(floor.is_undercut());
+
+        sender.send(Some(Amount::from_piconero(10))).unwrap();
+        assert!(!floor.is_undercut());
+    }
+
+    #[test]
+    fn given_balance_above_ceiling_then_sweeps_excess() {
+        let excess = policy().excess(Amount::from_piconero(150));
+
+        assert_eq!(
+            excess,
+            Some((
+                Amount::from_piconero(50),
+                Address::from_str(COLD_ADDRESS).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn given_balance_at_ceiling_then_nothing_is_swept() {
+        assert_eq!(policy().excess(Amount::from_piconero(100)), None);
+    }
+}
@@ -4,14 +4,15 @@ use crate::network::quote::BidQuote;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::network::swap_setup::alice;
 use crate::network::swap_setup::alice::WalletSnapshot;
+use crate::network::tor_transport::TorDialOnlyTransport;
 use crate::network::transport::authenticate_and_multiplex;
-use crate::network::{encrypted_signature, quote, transfer_proof};
+use crate::network::{encrypted_signature, identify, quote, redial, transfer_proof};
 use crate::protocol::alice::State3;
 use anyhow::{anyhow, Error, Result};
 use futures::FutureExt;
 use libp2p::core::connection::ConnectionId;
 use libp2p::core::muxing::StreamMuxerBox;
-use libp2p::core::transport::Boxed;
+use libp2p::core::transport::{Boxed, OptionalTransport};
 use libp2p::dns::TokioDnsConfig;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
 use libp2p::request_response::{RequestId, ResponseChannel};
@@ -30,12 +31,26 @@ pub mod transport {
     use super::*;
 
     /// Creates the libp2p transport for the ASB.
-    pub fn new(identity: &identity::Keypair) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    ///
+    /// If a Tor socks5 port is given, onion-addresses are dialed through the
+    /// Tor daemon. This allows calling back CLIs that listen on an onion
+    /// service. All other addresses are always dialed directly.
+    pub fn new(
+        identity: &identity::Keypair,
+        maybe_tor_socks5_port: Option<u16>,
+    ) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
         let tcp = TokioTcpConfig::new().nodelay(true);
         let tcp_with_dns = TokioDnsConfig::system(tcp)?;
         let websocket_with_dns = WsConfig::new(tcp_with_dns.clone());
+        let maybe_tor_transport = match maybe_tor_socks5_port {
+            Some(port) => OptionalTransport::some(TorDialOnlyTransport::new(port)),
+            None => OptionalTransport::none(),
+        };
 
-        let transport = tcp_with_dns.or_transport(websocket_with_dns).boxed();
+        let transport = tcp_with_dns
+            .or_transport(websocket_with_dns)
+            .or_transport(maybe_tor_transport)
+            .boxed();
 
         authenticate_and_multiplex(transport, identity)
     }
@@ -67,12 +82,24 @@ pub mod behaviour {
             peer: PeerId,
             id: RequestId,
         },
+        TransferProofFailed {
+            peer: PeerId,
+            id: RequestId,
+            error: Error,
+        },
         EncryptedSignatureReceived {
             msg: encrypted_signature::Request,
             channel: ResponseChannel<()>,
             peer: PeerId,
         },
         Rendezvous(libp2p::rendezvous::client::Event),
+        Identified {
+            peer: PeerId,
+            listen_addrs: Vec<Multiaddr>,
+        },
+        AllRedialAttemptsExhausted {
+            peer: PeerId,
+        },
         Failure {
             peer: PeerId,
             error: Error,
@@ -111,6 +138,8 @@ pub mod behaviour {
         pub swap_setup: alice::Behaviour<LR>,
         pub transfer_proof: transfer_proof::Behaviour,
         pub encrypted_signature: encrypted_signature::Behaviour,
+        pub redial: redial::MultiPeerBehaviour,
+        pub identify: identify::Behaviour,
 
         /// Ping behaviour that ensures that the underlying network connection
         /// is still alive. If the ping fails a connection close event
@@ -128,18 +157,13 @@ pub mod behaviour {
             latest_rate: LR,
             resume_only: bool,
             env_config: env::Config,
-            rendezvous_params: Option<(identity::Keypair, PeerId, Multiaddr, XmrBtcNamespace)>,
+            identity: identity::PublicKey,
+            rendezvous_params: Option<(identity::Keypair, Vec<rendezous::RendezvousNode>)>,
         ) -> Self {
             Self {
                 rendezvous: libp2p::swarm::toggle::Toggle::from(rendezvous_params.map(
-                    |(identity, rendezvous_peer_id, rendezvous_address, namespace)| {
-                        rendezous::Behaviour::new(
-                            identity,
-                            rendezvous_peer_id,
-                            rendezvous_address,
-                            namespace,
-                            None, // use default ttl on rendezvous point
-                        )
+                    |(identity, rendezvous_nodes)| {
+                        rendezous::Behaviour::new(identity, rendezvous_nodes)
                     },
                 )),
                 quote: quote::asb(),
@@ -152,6 +176,8 @@ pub mod behaviour {
                 ),
                 transfer_proof: transfer_proof::alice(),
                 encrypted_signature: encrypted_signature::alice(),
+                redial: redial::MultiPeerBehaviour::new(Duration::from_secs(10)),
+                identify: identify::new(identity),
                 ping: Ping::new(PingConfig::new().with_keep_alive(true)),
             }
         }
@@ -172,9 +198,13 @@ pub mod behaviour {
 
 pub mod rendezous {
     use super::*;
+    use libp2p::rendezvous::client::RegisterError;
     use libp2p::swarm::DialError;
     use std::pin::Pin;
 
+    /// How long to wait before registering again after a registration failed.
+    const RETRY_REGISTRATION_AFTER: Duration = Duration::from_secs(60);
+
     #[derive(PartialEq)]
     enum ConnectionStatus {
         Disconnected,
@@ -190,28 +220,26 @@ pub mod rendezous {
         },
     }
 
-    pub struct Behaviour {
-        inner: libp2p::rendezvous::client::Behaviour,
-        rendezvous_point: Multiaddr,
-        rendezvous_peer_id: PeerId,
+    /// A rendezvous point we register with, independently of all others.
+    pub struct RendezvousNode {
+        pub address: Multiaddr,
+        pub peer_id: PeerId,
         namespace: XmrBtcNamespace,
         registration_status: RegistrationStatus,
         connection_status: ConnectionStatus,
         registration_ttl: Option<u64>,
     }
 
-    impl Behaviour {
+    impl RendezvousNode {
         pub fn new(
-            identity: identity::Keypair,
-            rendezvous_peer_id: PeerId,
-            rendezvous_address: Multiaddr,
+            address: Multiaddr,
+            peer_id: PeerId,
             namespace: XmrBtcNamespace,
             registration_ttl: Option<u64>,
         ) -> Self {
             Self {
-                inner: libp2p::rendezvous::client::Behaviour::new(identity),
-                rendezvous_point: rendezvous_address,
-                rendezvous_peer_id,
+                address,
+                peer_id,
                 namespace,
                 registration_status: RegistrationStatus::RegisterOnNextConnection,
                 connection_status: ConnectionStatus::Disconnected,
@@ -219,12 +247,34 @@ pub mod rendezous {
             }
         }
 
-        fn register(&mut self) {
-            self.inner.register(
-                self.namespace.into(),
-                self.rendezvous_peer_id,
-                self.registration_ttl,
-            );
+        fn re_register_in(&mut self, duration: Duration) {
+            self.registration_status = RegistrationStatus::Registered {
+                re_register_in: Box::pin(tokio::time::sleep(duration)),
+            };
+        }
+    }
+
+    pub struct Behaviour {
+        inner: libp2p::rendezvous::client::Behaviour,
+        rendezvous_nodes: Vec<RendezvousNode>,
+    }
+
+    impl Behaviour {
+        pub fn new(identity: identity::Keypair, rendezvous_nodes: Vec<RendezvousNode>) -> Self {
+            Self {
+                inner: libp2p::rendezvous::client::Behaviour::new(identity),
+                rendezvous_nodes,
+            }
+        }
+
+        fn register(inner: &mut libp2p::rendezvous::client::Behaviour, node: &RendezvousNode) {
+            inner.register(node.namespace.into(), node.peer_id, node.registration_ttl);
+        }
+
+        fn node_mut(&mut self, peer_id: &PeerId) -> Option<&mut RendezvousNode> {
+            self.rendezvous_nodes
+                .iter_mut()
+                .find(|node| &node.peer_id == peer_id)
         }
     }
 
@@ -238,21 +288,27 @@ pub mod rendezous {
         }
 
         fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
-            if peer_id == &self.rendezvous_peer_id {
-                return vec![self.rendezvous_point.clone()];
-            }
-
-            vec![]
+            self.rendezvous_nodes
+                .iter()
+                .filter(|node| &node.peer_id == peer_id)
+                .map(|node| node.address.clone())
+                .collect()
         }
 
         fn inject_connected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Connected;
+            let inner = &mut self.inner;
+
+            if let Some(node) = self
+                .rendezvous_nodes
+                .iter_mut()
+                .find(|node| &node.peer_id == peer_id)
+            {
+                node.connection_status = ConnectionStatus::Connected;
 
-                match &self.registration_status {
+                match &node.registration_status {
                     RegistrationStatus::RegisterOnNextConnection => {
-                        self.register();
-                        self.registration_status = RegistrationStatus::Pending;
+                        Self::register(inner, node);
+                        node.registration_status = RegistrationStatus::Pending;
                     }
                     RegistrationStatus::Registered { .. } => {}
                     RegistrationStatus::Pending => {}
@@ -261,8 +317,13 @@ pub mod rendezous {
         }
 
         fn inject_disconnected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Disconnected;
+            if let Some(node) = self.node_mut(peer_id) {
+                node.connection_status = ConnectionStatus::Disconnected;
+
+                // a registration that was in flight is lost with the connection
+                if let RegistrationStatus::Pending = node.registration_status {
+                    node.registration_status = RegistrationStatus::RegisterOnNextConnection;
+                }
             }
         }
 
@@ -281,8 +342,11 @@ pub mod rendezous {
             _handler: Self::ProtocolsHandler,
             _error: DialError,
         ) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Disconnected;
+            if let Some(node) = self.node_mut(peer_id) {
+                node.connection_status = ConnectionStatus::Disconnected;
+
+                // dialling again right away would hammer an unreachable rendezvous node
+                node.re_register_in(RETRY_REGISTRATION_AFTER);
             }
         }
 
@@ -292,59 +356,92 @@ pub mod rendezous {
             cx: &mut std::task::Context<'_>,
             params: &mut impl PollParameters,
         ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
-            match &mut self.registration_status {
-                RegistrationStatus::RegisterOnNextConnection => match self.connection_status {
-                    ConnectionStatus::Disconnected => {
-                        self.connection_status = ConnectionStatus::Dialling;
-
-                        return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                            peer_id: self.rendezvous_peer_id,
-                            condition: DialPeerCondition::Disconnected,
-                            handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                        });
-                    }
-                    ConnectionStatus::Dialling => {}
-                    ConnectionStatus::Connected => {
-                        self.registration_status = RegistrationStatus::Pending;
-                        self.register();
-                    }
-                },
-                RegistrationStatus::Registered { re_register_in } => {
-                    if let Poll::Ready(()) = re_register_in.poll_unpin(cx) {
-                        match self.connection_status {
-                            ConnectionStatus::Connected => {
-                                self.registration_status = RegistrationStatus::Pending;
-                                self.register();
-                            }
-                            ConnectionStatus::Disconnected => {
-                                self.registration_status =
-                                    RegistrationStatus::RegisterOnNextConnection;
-
-                                return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                                    peer_id: self.rendezvous_peer_id,
-                                    condition: DialPeerCondition::Disconnected,
-                                    handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                                });
+            for node in self.rendezvous_nodes.iter_mut() {
+                match &mut node.registration_status {
+                    RegistrationStatus::RegisterOnNextConnection => match node.connection_status {
+                        ConnectionStatus::Disconnected => {
+                            node.connection_status = ConnectionStatus::Dialling;
+
+                            return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                                peer_id: node.peer_id,
+                                condition: DialPeerCondition::Disconnected,
+                                handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
+                            });
+                        }
+                        ConnectionStatus::Dialling => {}
+                        ConnectionStatus::Connected => {
+                            node.registration_status = RegistrationStatus::Pending;
+                            Self::register(&mut self.inner, node);
+                        }
+                    },
+                    RegistrationStatus::Registered { re_register_in } => {
+                        if let Poll::Ready(()) = re_register_in.poll_unpin(cx) {
+                            match node.connection_status {
+                                ConnectionStatus::Connected => {
+                                    node.registration_status = RegistrationStatus::Pending;
+                                    Self::register(&mut self.inner, node);
+                                }
+                                ConnectionStatus::Disconnected => {
+                                    node.registration_status =
+                                        RegistrationStatus::RegisterOnNextConnection;
+
+                                    return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                                        peer_id: node.peer_id,
+                                        condition: DialPeerCondition::Disconnected,
+                                        handler: Self::ProtocolsHandler::new(Duration::from_secs(
+                                            30,
+                                        )),
+                                    });
+                                }
+                                ConnectionStatus::Dialling => {}
                             }
-                            ConnectionStatus::Dialling => {}
                         }
                     }
+                    RegistrationStatus::Pending => {}
                 }
-                RegistrationStatus::Pending => {}
             }
 
             let inner_poll = self.inner.poll(cx, params);
 
-            // reset the timer if we successfully registered
-            if let Poll::Ready(NetworkBehaviourAction::GenerateEvent(
-                libp2p::rendezvous::client::Event::Registered { ttl, .. },
-            )) = &inner_poll
-            {
-                let half_of_ttl = Duration::from_secs(*ttl) / 2;
-
-                self.registration_status = RegistrationStatus::Registered {
-                    re_register_in: Box::pin(tokio::time::sleep(half_of_ttl)),
-                };
+            match &inner_poll {
+                // reset the timer if we successfully registered
+                Poll::Ready(NetworkBehaviourAction::GenerateEvent(
+                    libp2p::rendezvous::client::Event::Registered {
+                        rendezvous_node,
+                        ttl,
+                        ..
+                    },
+                )) => {
+                    if let Some(node) = self.node_mut(rendezvous_node) {
+                        node.re_register_in(Duration::from_secs(*ttl) / 2);
+                    }
+                }
+                // retry later, without affecting the other rendezvous nodes
+                Poll::Ready(NetworkBehaviourAction::GenerateEvent(
+                    libp2p::rendezvous::client::Event::RegisterFailed(error),
+                )) => {
+                    let failed_nodes = match error {
+                        RegisterError::Remote {
+                            rendezvous_node, ..
+                        } => vec![*rendezvous_node],
+                        // we cannot tell which registration failed, retry all that are pending
+                        _ => self
+                            .rendezvous_nodes
+                            .iter()
+                            .filter(|node| {
+                                matches!(node.registration_status, RegistrationStatus::Pending)
+                            })
+                            .map(|node| node.peer_id)
+                            .collect(),
+                    };
+
+                    for peer_id in failed_nodes {
+                        if let Some(node) = self.node_mut(&peer_id) {
+                            node.re_register_in(RETRY_REGISTRATION_AFTER);
+                        }
+                    }
+                }
+                _ => {}
             }
 
             inner_poll
@@ -358,6 +455,7 @@ pub mod rendezous {
         use futures::StreamExt;
         use libp2p::rendezvous;
         use libp2p::swarm::SwarmEvent;
+        use std::collections::HashSet;
 
         #[tokio::test]
         async fn given_no_initial_connection_when_constructed_asb_connects_and_registers_with_rendezvous_node(
@@ -370,10 +468,12 @@ pub mod rendezous {
             let mut asb = new_swarm(|_, identity| {
                 rendezous::Behaviour::new(
                     identity,
-                    *rendezvous_node.local_peer_id(),
-                    rendezvous_address,
-                    XmrBtcNamespace::Testnet,
-                    None,
+                    vec![RendezvousNode::new(
+                        rendezvous_address,
+                        *rendezvous_node.local_peer_id(),
+                        XmrBtcNamespace::Testnet,
+                        None,
+                    )],
                 )
             });
             asb.listen_on_random_memory_address().await; // this adds an external address
@@ -399,6 +499,36 @@ pub mod rendezous {
                 .unwrap();
         }
 
+        #[tokio::test]
+        async fn given_unreachable_rendezvous_node_asb_does_not_redial_right_away() {
+            let mut asb = new_swarm(|_, identity| {
+                rendezous::Behaviour::new(
+                    identity,
+                    vec![RendezvousNode::new(
+                        format!("/memory/{}", rand::random::<u64>())
+                            .parse()
+                            .unwrap(),
+                        PeerId::random(),
+                        XmrBtcNamespace::Testnet,
+                        None,
+                    )],
+                )
+            });
+            asb.listen_on_random_memory_address().await; // this adds an external address
+
+            let mut dial_attempts = 0;
+            let _ = tokio::time::timeout(Duration::from_secs(2), async {
+                loop {
+                    if let SwarmEvent::Dialing(_) = asb.select_next_some().await {
+                        dial_attempts += 1;
+                    }
+                }
+            })
+            .await;
+
+            assert_eq!(dial_attempts, 1);
+        }
+
         #[tokio::test]
         async fn asb_automatically_re_registers() {
             let mut rendezvous_node = new_swarm(|_, _| {
@@ -411,10 +541,12 @@ pub mod rendezous {
             let mut asb = new_swarm(|_, identity| {
                 rendezous::Behaviour::new(
                     identity,
-                    *rendezvous_node.local_peer_id(),
-                    rendezvous_address,
-                    XmrBtcNamespace::Testnet,
-                    Some(5),
+                    vec![RendezvousNode::new(
+                        rendezvous_address,
+                        *rendezvous_node.local_peer_id(),
+                        XmrBtcNamespace::Testnet,
+                        Some(5),
+                    )],
                 )
             });
             asb.listen_on_random_memory_address().await; // this adds an external address
@@ -445,5 +577,58 @@ pub mod rendezous {
                 .unwrap()
                 .unwrap();
         }
+
+        #[tokio::test]
+        async fn asb_registers_with_every_rendezvous_node() {
+            let mut rendezvous_nodes = Vec::new();
+            let mut rendezvous_peer_ids = HashSet::new();
+
+            for _ in 0..2 {
+                let mut rendezvous_node = new_swarm(|_, _| {
+                    rendezvous::server::Behaviour::new(rendezvous::server::Config::default())
+                });
+                let rendezvous_address = rendezvous_node.listen_on_random_memory_address().await;
+                let rendezvous_peer_id = *rendezvous_node.local_peer_id();
+
+                rendezvous_nodes.push((rendezvous_address, rendezvous_peer_id));
+                rendezvous_peer_ids.insert(rendezvous_peer_id);
+
+                tokio::spawn(async move {
+                    loop {
+                        rendezvous_node.next().await;
+                    }
+                });
+            }
+
+            let mut asb = new_swarm(|_, identity| {
+                rendezous::Behaviour::new(
+                    identity,
+                    rendezvous_nodes
+                        .into_iter()
+                        .map(|(address, peer_id)| {
+                            RendezvousNode::new(address, peer_id, XmrBtcNamespace::Testnet, None)
+                        })
+                        .collect(),
+                )
+            });
+            asb.listen_on_random_memory_address().await; // this adds an external address
+
+            let asb_registered_everywhere = tokio::spawn(async move {
+                while !rendezvous_peer_ids.is_empty() {
+                    if let SwarmEvent::Behaviour(rendezvous::client::Event::Registered {
+                        rendezvous_node,
+                        ..
+                    }) = asb.select_next_some().await
+                    {
+                        rendezvous_peer_ids.remove(&rendezvous_node);
+                    }
+                }
+            });
+
+            tokio::time::timeout(Duration::from_secs(10), asb_registered_everywhere)
+                .await
+                .unwrap()
+                .unwrap();
+        }
     }
 }
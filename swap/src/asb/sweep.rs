@@ -0,0 +1,132 @@
+use crate::bitcoin::{self, Address, Amount, CoinControl, PayoutWallet, TxKind, Txid};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// A transfer of excess Bitcoin from the internal wallet to cold storage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sweep {
+    pub txid: Txid,
+    pub amount: Amount,
+    pub address: Address,
+    pub swept_at: OffsetDateTime,
+}
+
+/// Stores the sweeps done by the ASB.
+#[async_trait]
+pub trait SweepDatabase {
+    async fn insert_sweep(&self, sweep: Sweep) -> Result<()>;
+    async fn all_sweeps(&self) -> Result<Vec<Sweep>>;
+}
+
+/// Where swept Bitcoin is sent to.
+pub enum Destination {
+    Address(Address),
+    PayoutWallet(Arc<PayoutWallet>),
+}
+
+impl Destination {
+    async fn address(&self) -> Result<Address> {
+        match self {
+            Destination::Address(address) => Ok(address.clone()),
+            Destination::PayoutWallet(payout_wallet) => payout_wallet.new_address().await,
+        }
+    }
+}
+
+/// Periodically sends everything above `threshold` from the internal wallet
+/// to `destination`.
+///
+/// The threshold stays in the wallet and pays for the sweep itself.
+pub async fn run(
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    db: Arc<dyn SweepDatabase + Send + Sync>,
+    destination: Destination,
+    threshold: Amount,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        match sweep(&bitcoin_wallet, db.as_ref(), &destination, threshold).await {
+            Ok(Some(sweep)) => {
+                tracing::info!(txid = %sweep.txid, amount = %sweep.amount, address = %sweep.address, "Swept Bitcoin to cold storage");
+            }
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!("Failed to sweep Bitcoin to cold storage: {:#}", error);
+            }
+        }
+    }
+}
+
+async fn sweep(
+    bitcoin_wallet: &bitcoin::Wallet,
+    db: &(dyn SweepDatabase + Send + Sync),
+    destination: &Destination,
+    threshold: Amount,
+) -> Result<Option<Sweep>> {
+    bitcoin_wallet.sync().await?;
+
+    let amount = match excess(bitcoin_wallet.balance().await?, threshold) {
+        Some(amount) => amount,
+        None => return Ok(None),
+    };
+
+    let address = destination.address().await?;
+    let psbt = bitcoin_wallet
+        .send_to_address(
+            address.clone(),
+            amount,
+            None,
+            TxKind::Withdraw,
+            &CoinControl::default(),
+        )
+        .await?;
+    let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+    let (txid, _) = bitcoin_wallet.broadcast(signed_tx, "sweep").await?;
+
+    let sweep = Sweep {
+        txid,
+        amount,
+        address,
+        swept_at: OffsetDateTime::now_utc(),
+    };
+    db.insert_sweep(sweep.clone()).await?;
+
+    Ok(Some(sweep))
+}
+
+fn excess(balance: Amount, threshold: Amount) -> Option<Amount> {
+    balance
+        .checked_sub(threshold)
+        .filter(|excess| *excess > Amount::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_balance_above_threshold_then_excess_is_swept() {
+        let excess = excess(Amount::from_sat(150_000), Amount::from_sat(100_000));
+
+        assert_eq!(excess, Some(Amount::from_sat(50_000)));
+    }
+
+    #[test]
+    fn given_balance_at_or_below_threshold_then_nothing_is_swept() {
+        assert_eq!(
+            excess(Amount::from_sat(100_000), Amount::from_sat(100_000)),
+            None
+        );
+        assert_eq!(
+            excess(Amount::from_sat(50_000), Amount::from_sat(100_000)),
+            None
+        );
+    }
+}
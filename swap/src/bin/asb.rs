@@ -14,29 +14,30 @@
 
 use anyhow::{bail, Context, Result};
 use comfy_table::Table;
-use libp2p::core::multiaddr::Protocol;
-use libp2p::core::Multiaddr;
 use libp2p::swarm::AddressScore;
-use libp2p::Swarm;
+use libp2p::{PeerId, Swarm};
 use std::convert::TryInto;
 use std::env;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::clap;
 use structopt::clap::ErrorKind;
 use swap::asb::command::{parse_args, Arguments, Command};
 use swap::asb::config::{
     initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
 };
+use swap::asb::sweep::{Destination, SweepDatabase};
 use swap::asb::{cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate};
 use swap::database::open_db;
 use swap::judecoin::Amount;
 use swap::network::rendezvous::XmrBtcNamespace;
 use swap::network::swarm;
+use swap::protocol;
 use swap::protocol::alice::{run, AliceState};
+use swap::protocol::Database;
 use swap::seed::Seed;
-use swap::tor::AuthenticatedClient;
 use swap::{asb, bitcoin, kraken, judecoin, tor};
+use tokio::sync::watch;
 use tracing_subscriber::filter::LevelFilter;
 
 const DEFAULT_WALLET_NAME: &str = "asb-wallet";
@@ -90,15 +91,28 @@ async fn main() -> Result<()> {
             env_config.bitcoin_network, config.bitcoin.network
         ));
     }
+    if let Some(sweep) = &config.bitcoin.sweep {
+        sweep.validate(env_config.bitcoin_network)?;
+    }
 
     let db = open_db(config.data.dir.join("sqlite")).await?;
 
+    if let Command::RestoreSeed {
+        gap_limit,
+        birthday_height,
+    } = cmd
+    {
+        return restore_seed(&config, env_config, gap_limit, birthday_height).await;
+    }
+
     let seed =
         Seed::from_file_or_generate(&config.data.dir).expect("Could not retrieve/initialize seed");
 
     match cmd {
         Command::Start { resume_only } => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let payout_wallet = init_payout_wallet(&config, env_config)?;
+            let inventory_policy = config.judecoin.inventory.policy()?;
 
             let judecoin_wallet = init_judecoin_wallet(&config, env_config).await?;
 
@@ -121,17 +135,20 @@ async fn main() -> Result<()> {
             // setup Tor hidden services
             let tor_client =
                 tor::Client::new(config.tor.socks5_port).with_control_port(config.tor.control_port);
-            let _ac = match tor_client.assert_tor_running().await {
+            let (_ac, maybe_tor_socks5_port) = match tor_client.assert_tor_running().await {
                 Ok(_) => {
                     tracing::info!("Setting up Tor hidden service");
-                    let ac =
-                        register_tor_services(config.network.clone().listen, tor_client, &seed)
-                            .await?;
-                    Some(ac)
+                    let ac = tor::register_services(
+                        config.network.clone().listen,
+                        tor_client,
+                        &seed.derive_torv3_key(),
+                    )
+                    .await?;
+                    (Some(ac), Some(config.tor.socks5_port))
                 }
                 Err(_) => {
                     tracing::warn!("Tor not found. Running on clear net");
-                    None
+                    (None, None)
                 }
             };
 
@@ -143,9 +160,9 @@ async fn main() -> Result<()> {
                 kraken_rate.clone(),
                 resume_only,
                 env_config,
-                config.network.rendezvous_point.map(|rendezvous_point| {
+                (!config.network.rendezvous_point.is_empty()).then(|| {
                     (
-                        rendezvous_point,
+                        config.network.rendezvous_point.clone(),
                         if testnet {
                             XmrBtcNamespace::Testnet
                         } else {
@@ -153,6 +170,7 @@ async fn main() -> Result<()> {
                         },
                     )
                 }),
+                maybe_tor_socks5_port,
             )?;
 
             for listen in config.network.listen.clone() {
@@ -170,15 +188,52 @@ async fn main() -> Result<()> {
                 );
             }
 
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let payout_wallet = payout_wallet.map(Arc::new);
+
+            if let Some(policy) = config.bitcoin.sweep {
+                let destination = match policy.address {
+                    Some(address) => Destination::Address(address),
+                    None => Destination::PayoutWallet(payout_wallet.clone().context(
+                        "Sweeping requires a sweep address or a payout descriptor to be configured",
+                    )?),
+                };
+
+                tokio::spawn(asb::sweep::run(
+                    bitcoin_wallet.clone(),
+                    db.clone(),
+                    destination,
+                    policy.threshold_btc,
+                    Duration::from_secs(policy.interval_secs),
+                ));
+            }
+
+            let judecoin_wallet = Arc::new(judecoin_wallet);
+            let (unlocked_balance_sender, unlocked_balance) = watch::channel(None);
+            let jude_floor = inventory_policy
+                .floor
+                .map(|floor| asb::inventory::Floor::new(floor, unlocked_balance));
+
+            if inventory_policy.floor.is_some() || inventory_policy.ceiling.is_some() {
+                tokio::spawn(asb::inventory::run(
+                    judecoin_wallet.clone(),
+                    inventory_policy,
+                    Duration::from_secs(config.judecoin.inventory.check_interval_secs),
+                    unlocked_balance_sender,
+                ));
+            }
+
             let (event_loop, mut swap_receiver) = EventLoop::new(
                 swarm,
                 env_config,
-                Arc::new(bitcoin_wallet),
-                Arc::new(judecoin_wallet),
+                bitcoin_wallet,
+                payout_wallet,
+                judecoin_wallet,
                 db,
                 kraken_rate.clone(),
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
+                jude_floor,
             )
             .unwrap();
 
@@ -212,6 +267,24 @@ async fn main() -> Result<()> {
             }
 
             println!("{}", table);
+
+            let sweeps = db.all_sweeps().await?;
+            if !sweeps.is_empty() {
+                let mut table = Table::new();
+
+                table.set_header(vec!["SWEPT AT", "TXID", "AMOUNT", "ADDRESS"]);
+
+                for sweep in sweeps {
+                    table.add_row(vec![
+                        sweep.swept_at.to_string(),
+                        sweep.txid.to_string(),
+                        sweep.amount.to_string(),
+                        sweep.address.to_string(),
+                    ]);
+                }
+
+                println!("{}", table);
+            }
         }
         Command::Config => {
             let config_json = serde_json::to_string_pretty(&config)?;
@@ -224,13 +297,23 @@ async fn main() -> Result<()> {
                 Some(amount) => amount,
                 None => {
                     bitcoin_wallet
-                        .max_giveable(address.script_pubkey().len())
+                        .max_giveable(
+                            address.script_pubkey().len(),
+                            bitcoin::TxKind::Withdraw,
+                            &bitcoin::CoinControl::default(),
+                        )
                         .await?
                 }
             };
 
             let psbt = bitcoin_wallet
-                .send_to_address(address, amount, None)
+                .send_to_address(
+                    address,
+                    amount,
+                    None,
+                    bitcoin::TxKind::Withdraw,
+                    &bitcoin::CoinControl::default(),
+                )
                 .await?;
             let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
 
@@ -248,6 +331,42 @@ async fn main() -> Result<()> {
                 %judecoin_balance,
                 "Current balance");
         }
+        Command::BtcHistory => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+
+            let mut labels = protocol::bitcoin_labels(db.as_ref()).await?;
+            for sweep in db.all_sweeps().await? {
+                labels.insert_sweep(sweep.txid);
+            }
+            let history = bitcoin_wallet.history(&labels).await?;
+
+            bitcoin::print_history(history, json)?;
+        }
+        Command::ExportSeed => {
+            println!("{}", seed.to_mnemonic());
+            eprintln!(
+                "Anyone with these words can spend the funds of the ASB, store them offline."
+            );
+        }
+        Command::RestoreSeed { .. } => bail!("The seed must be restored before it is loaded"),
+        Command::Rescan {
+            gap_limit,
+            birthday_height,
+        } => {
+            let bitcoin_wallet = bitcoin::Wallet::rescan(
+                config.bitcoin.electrum_rpc_url.clone(),
+                &config.data.dir.join("wallet"),
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+                config.bitcoin.fee_strategy(),
+                gap_limit,
+                birthday_height,
+            )
+            .await?;
+
+            let bitcoin_balance = bitcoin_wallet.balance().await?;
+            tracing::info!(%bitcoin_balance, %gap_limit, "Rescanned Bitcoin wallet");
+        }
         Command::Cancel { swap_id } => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
@@ -320,7 +439,7 @@ async fn init_bitcoin_wallet(
         &wallet_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
-        config.bitcoin.target_block,
+        config.bitcoin.fee_strategy(),
     )
     .await
     .context("Failed to initialize Bitcoin wallet")?;
@@ -330,6 +449,25 @@ async fn init_bitcoin_wallet(
     Ok(wallet)
 }
 
+fn init_payout_wallet(
+    config: &Config,
+    env_config: swap::env::Config,
+) -> Result<Option<bitcoin::PayoutWallet>> {
+    let descriptor = match config.bitcoin.payout_descriptor.as_deref() {
+        Some(descriptor) => descriptor,
+        None => return Ok(None),
+    };
+
+    tracing::debug!("Opening Bitcoin payout wallet");
+    let wallet_dir = config.data.dir.join("payout-wallet");
+
+    let wallet = bitcoin::PayoutWallet::new(descriptor, env_config.bitcoin_network, &wallet_dir)
+        .context("Failed to initialize Bitcoin payout wallet")?;
+    tracing::info!("Redeemed and punished Bitcoin will be sent to the payout wallet");
+
+    Ok(Some(wallet))
+}
+
 async fn init_judecoin_wallet(
     config: &Config,
     env_config: swap::env::Config,
@@ -345,45 +483,44 @@ async fn init_judecoin_wallet(
     Ok(wallet)
 }
 
-/// Registers a hidden service for each network.
-/// Note: Once ac goes out of scope, the services will be de-registered.
-async fn register_tor_services(
-    networks: Vec<Multiaddr>,
-    tor_client: tor::Client,
-    seed: &Seed,
-) -> Result<AuthenticatedClient> {
-    let mut ac = tor_client.into_authenticated_client().await?;
-
-    let hidden_services_details = networks
-        .iter()
-        .flat_map(|network| {
-            network.iter().map(|protocol| match protocol {
-                Protocol::Tcp(port) => Some((
-                    port,
-                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), port),
-                )),
-                _ => {
-                    // We only care for Tcp for now.
-                    None
-                }
-            })
-        })
-        .flatten()
-        .collect::<Vec<_>>();
-
-    let key = seed.derive_torv3_key();
-
-    ac.add_services(&hidden_services_details, &key).await?;
-
-    let onion_address = key
+/// Restores the seed from a mnemonic and rescans the Bitcoin wallet.
+///
+/// The libp2p and Tor identities are derived from the seed, so the ASB comes
+/// back under its previous peer id and onion address.
+async fn restore_seed(
+    config: &Config,
+    env_config: swap::env::Config,
+    gap_limit: u32,
+    birthday_height: Option<u32>,
+) -> Result<()> {
+    eprintln!("Enter the 24 words of the seed:");
+    let mut mnemonic = String::new();
+    std::io::stdin().read_line(&mut mnemonic)?;
+
+    let seed = Seed::from_mnemonic(&mnemonic).context("Failed to parse mnemonic")?;
+    seed.restore(&config.data.dir)
+        .context("Failed to restore seed")?;
+
+    let peer_id = PeerId::from(seed.derive_libp2p_identity().public());
+    let onion_address = seed
+        .derive_torv3_key()
         .public()
         .get_onion_address()
         .get_address_without_dot_onion();
+    tracing::info!(%peer_id, %onion_address, "Restored seed");
 
-    hidden_services_details.iter().for_each(|(port, _)| {
-        let onion_address = format!("/onion3/{}:{}", onion_address, port);
-        tracing::info!(%onion_address, "Successfully created hidden service");
-    });
+    let bitcoin_wallet = bitcoin::Wallet::rescan(
+        config.bitcoin.electrum_rpc_url.clone(),
+        &config.data.dir.join("wallet"),
+        seed.derive_extended_private_key(env_config.bitcoin_network)?,
+        env_config,
+        config.bitcoin.fee_strategy(),
+        gap_limit,
+        birthday_height,
+    )
+    .await?;
+    let bitcoin_balance = bitcoin_wallet.balance().await?;
+    tracing::info!(%bitcoin_balance, %gap_limit, "Rescanned Bitcoin wallet");
 
-    Ok(ac)
+    Ok(())
 }
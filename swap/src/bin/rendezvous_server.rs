@@ -0,0 +1,128 @@
+#![warn(
+    unused_extern_crates,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use libp2p::rendezvous::server::Event;
+use libp2p::swarm::{AddressScore, SwarmEvent};
+use libp2p::Swarm;
+use std::env;
+use structopt::clap;
+use structopt::clap::ErrorKind;
+use swap::network::swarm;
+use swap::rendezvous_server::command::{parse_args, Arguments};
+use swap::rendezvous_server::config::read_or_create_config;
+use swap::rendezvous_server::OutEvent;
+use swap::seed::Seed;
+use swap::{rendezvous_server, tor};
+use tracing_subscriber::filter::LevelFilter;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let Arguments {
+        json,
+        disable_timestamp,
+        config_path,
+    } = match parse_args(env::args_os()) {
+        Ok(args) => args,
+        Err(e) => {
+            if let Some(clap_err) = e.downcast_ref::<clap::Error>() {
+                match clap_err.kind {
+                    ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => {
+                        println!("{}", clap_err.message);
+                        std::process::exit(0);
+                    }
+                    _ => {
+                        bail!(e);
+                    }
+                }
+            }
+            bail!(e);
+        }
+    };
+
+    rendezvous_server::tracing::init(LevelFilter::DEBUG, json, !disable_timestamp)
+        .expect("initialize tracing");
+
+    let config = read_or_create_config(config_path)?;
+
+    let seed =
+        Seed::from_file_or_generate(&config.data.dir).expect("Could not retrieve/initialize seed");
+
+    // the onion service is removed once the client is dropped
+    let _ac = match config.tor {
+        Some(tor_conf) => {
+            tracing::info!("Setting up Tor hidden service");
+            let tor_client =
+                tor::Client::new(tor_conf.socks5_port).with_control_port(tor_conf.control_port);
+            let ac = tor::register_services(
+                config.network.listen.clone(),
+                tor_client,
+                &seed.derive_torv3_key(),
+            )
+            .await?;
+            Some(ac)
+        }
+        None => None,
+    };
+
+    let mut swarm = swarm::rendezvous_server(&seed, config.registrations.server_config())?;
+
+    for listen in config.network.listen.clone() {
+        Swarm::listen_on(&mut swarm, listen.clone())
+            .with_context(|| format!("Failed to listen on network interface {}", listen))?;
+    }
+
+    for external_address in config.network.external_addresses {
+        let _ = Swarm::add_external_address(&mut swarm, external_address, AddressScore::Infinite);
+    }
+
+    tracing::info!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
+
+    loop {
+        match swarm.select_next_some().await {
+            SwarmEvent::Behaviour(OutEvent::Rendezvous(Event::PeerRegistered {
+                peer,
+                registration,
+            })) => {
+                tracing::info!(%peer, namespace = ?registration.namespace, "Peer registered");
+            }
+            SwarmEvent::Behaviour(OutEvent::Rendezvous(Event::PeerUnregistered {
+                peer,
+                namespace,
+            })) => {
+                tracing::info!(%peer, ?namespace, "Peer unregistered");
+            }
+            SwarmEvent::Behaviour(OutEvent::Rendezvous(Event::RegistrationExpired(
+                registration,
+            ))) => {
+                let peer = registration.record.peer_id();
+                tracing::info!(%peer, namespace = ?registration.namespace, "Registration expired");
+            }
+            SwarmEvent::Behaviour(OutEvent::Rendezvous(Event::DiscoverServed {
+                enquirer,
+                registrations,
+            })) => {
+                tracing::debug!(
+                    %enquirer,
+                    registrations = registrations.len(),
+                    "Served discovery request"
+                );
+            }
+            SwarmEvent::NewListenAddr { address, .. } => {
+                tracing::info!(%address, "New listen address detected");
+            }
+            _ => {}
+        }
+    }
+}
@@ -14,27 +14,37 @@
 
 use anyhow::{bail, Context, Result};
 use comfy_table::Table;
+use libp2p::{Multiaddr, PeerId};
 use qrcode::render::unicode;
 use qrcode::QrCode;
+use serde::Serialize;
 use std::cmp::min;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::env;
 use std::future::Future;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use swap::bitcoin::TxLock;
-use swap::cli::command::{parse_args_and_apply_defaults, Arguments, Command, ParseResult};
+use swap::bitcoin::{TxCancel, TxLock, TxRefund};
+use swap::cli::command::{
+    parse_args_and_apply_defaults, Arguments, Command, ParseResult, SellerSelection, SwapAmount,
+};
+use swap::cli::seller_cache::{self, CachedSeller};
 use swap::cli::{list_sellers, EventLoop, SellerStatus};
 use swap::database::open_db;
 use swap::env::Config;
 use swap::libp2p_ext::MultiAddrExt;
 use swap::network::quote::BidQuote;
+use swap::network::swap_setup::bob::PriceProtection;
 use swap::network::swarm;
+use swap::protocol;
 use swap::protocol::bob;
 use swap::protocol::bob::{BobState, Swap};
+use swap::protocol::Database;
 use swap::seed::Seed;
-use swap::{bitcoin, cli, judecoin};
+use swap::{bitcoin, cli, judecoin, tor};
+use time::OffsetDateTime;
 use url::Url;
 use uuid::Uuid;
 
@@ -58,12 +68,19 @@ async fn main() -> Result<()> {
         Command::BuyXmr {
             seller,
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_fee_strategy,
             bitcoin_change_address,
             judecoin_receive_address,
             judecoin_daemon_address,
             tor_socks5_port,
+            tor_control_port,
+            coin_control,
+            external_funding,
+            price_protection,
+            amount,
         } => {
+            // Every seller that is tried uses the same swap id, so a single log file covers
+            // the whole attempt.
             let swap_id = Uuid::new_v4();
 
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
@@ -76,65 +93,216 @@ async fn main() -> Result<()> {
                 &seed,
                 data_dir.clone(),
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy,
             )
             .await?;
             let (judecoin_wallet, _process) =
                 init_judecoin_wallet(data_dir, judecoin_daemon_address, env_config).await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
-            let seller_peer_id = seller
-                .extract_peer_id()
-                .context("Seller address must contain peer ID")?;
-            db.insert_address(seller_peer_id, seller.clone()).await?;
+            let judecoin_wallet = Arc::new(judecoin_wallet);
+
+            let sellers = match seller {
+                SellerSelection::Address(seller) => vec![seller],
+                SellerSelection::Best {
+                    rendezvous_point,
+                    namespace,
+                } => {
+                    let sellers = list_sellers(
+                        with_peer_ids(rendezvous_point)?,
+                        namespace,
+                        tor_socks5_port,
+                        seed.derive_libp2p_identity(),
+                    )
+                    .await?;
+                    seller_cache::remember_sellers(
+                        db.as_ref(),
+                        &sellers,
+                        OffsetDateTime::now_utc(),
+                    )
+                    .await?;
+                    let reliability = cli::seller_reliability(db.as_ref()).await?;
+                    let sellers = cli::rank_sellers(
+                        sellers,
+                        external_funding.map(SwapAmount::Btc).or(amount),
+                        price_protection.max_price,
+                        &reliability,
+                    );
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
-            let mut swarm =
-                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
-            swarm.behaviour_mut().add_address(seller_peer_id, seller);
-
-            tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
-
-            let (event_loop, mut event_loop_handle) =
-                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
-            let event_loop = tokio::spawn(event_loop.run());
-
-            let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
-            let (amount, fees) = determine_btc_to_swap(
-                json,
-                event_loop_handle.request_quote(),
-                bitcoin_wallet.new_address(),
-                || bitcoin_wallet.balance(),
-                max_givable,
-                || bitcoin_wallet.sync(),
-            )
-            .await?;
+                    match sellers.first() {
+                        Some(best) => {
+                            tracing::info!(%best, candidates = sellers.len(), "Selected seller")
+                        }
+                        None => bail!("None of the discovered sellers accepts the swap"),
+                    }
 
-            tracing::info!(%amount, %fees,  "Determined swap amount");
+                    sellers
+                }
+            };
 
-            db.insert_peer_id(swap_id, seller_peer_id).await?;
-            db.insert_judecoin_address(swap_id, judecoin_receive_address)
-                .await?;
+            // Shared by all sellers we try so that only a single task reads from stdin
+            let external_wallet =
+                external_funding.map(|_| cli::external_funding(env_config.bitcoin_network));
+
+            for (index, seller) in sellers.iter().enumerate() {
+                let seller_peer_id = seller
+                    .extract_peer_id()
+                    .context("Seller address must contain peer ID")?;
+                let known_addresses = db.get_addresses(seller_peer_id).await?;
+                if !known_addresses.contains(seller) {
+                    db.insert_address(seller_peer_id, seller.clone()).await?;
+                }
 
-            let swap = Swap::new(
-                db,
-                swap_id,
-                bitcoin_wallet,
-                Arc::new(judecoin_wallet),
-                env_config,
-                event_loop_handle,
-                judecoin_receive_address,
-                bitcoin_change_address,
-                amount,
-            );
+                let behaviour = cli::Behaviour::new(
+                    seller_peer_id,
+                    env_config,
+                    bitcoin_wallet.clone(),
+                    seed.derive_libp2p_identity().public(),
+                );
+                let mut swarm =
+                    swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
+                let _onion_service = match tor_control_port {
+                    Some(control_port) => Some(
+                        swarm::listen_on_onion_service(
+                            &mut swarm,
+                            tor::Client::new(tor_socks5_port).with_control_port(control_port),
+                            &seed.derive_torv3_key(),
+                        )
+                        .await?,
+                    ),
+                    None => None,
+                };
+                swarm
+                    .behaviour_mut()
+                    .add_address(seller_peer_id, seller.clone());
+                // addresses the seller was known at before are tried as well when redialling
+                for address in known_addresses
+                    .into_iter()
+                    .filter(|address| address != seller)
+                {
+                    swarm.behaviour_mut().add_address(seller_peer_id, address);
+                }
 
-            tokio::select! {
-                result = event_loop => {
-                    result
-                        .context("EventLoop panicked")?;
-                },
-                result = bob::run(swap) => {
-                    result.context("Failed to complete swap")?;
+                tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
+
+                let (event_loop, mut event_loop_handle) =
+                    EventLoop::new(swap_id, swarm, seller_peer_id, db.clone(), env_config)?;
+                let mut event_loop = tokio::spawn(event_loop.run());
+
+                let result = async {
+                    let mut price_protection = price_protection;
+
+                    let bid_quote = event_loop_handle.request_quote().await?;
+                    ensure_price_within_limits(&bid_quote, &price_protection)?;
+                    price_protection.quoted_price = Some(bid_quote.price);
+
+                    let max_givable = || {
+                        bitcoin_wallet.max_giveable(
+                            TxLock::script_size(),
+                            bitcoin::TxKind::Lock,
+                            &coin_control,
+                        )
+                    };
+
+                    let (amount, external_funding) = match (external_funding, amount) {
+                        (Some(amount), _) => {
+                            ensure_amount_within_quote(amount, async { Ok(bid_quote) }).await?;
+                            tracing::info!(%amount, "Lock transaction will be funded externally");
+
+                            (amount, external_wallet.clone())
+                        }
+                        (None, Some(fixed_amount)) => {
+                            // Abort if the seller's price moved such that we would get less JUDE
+                            // than requested, beyond the price tolerance.
+                            if let SwapAmount::Jude(jude) = fixed_amount {
+                                price_protection.expect_jude(jude);
+                            }
+
+                            let (amount, refund_fees) = determine_fixed_btc_to_swap(
+                                json,
+                                bid_quote,
+                                fixed_amount,
+                                |amount| estimate_refund_fees(&bitcoin_wallet, amount),
+                                bitcoin_wallet.new_address(),
+                                || bitcoin_wallet.balance(),
+                                max_givable,
+                                || bitcoin_wallet.sync(),
+                            )
+                            .await?;
+
+                            tracing::info!(%amount, %refund_fees, "Determined swap amount, the refund fees are kept in the wallet");
+
+                            (amount, None)
+                        }
+                        (None, None) => {
+                            let (amount, fees) = determine_btc_to_swap(
+                                json,
+                                async { Ok(bid_quote) },
+                                bitcoin_wallet.new_address(),
+                                || bitcoin_wallet.balance(),
+                                max_givable,
+                                || bitcoin_wallet.sync(),
+                            )
+                            .await?;
+
+                            tracing::info!(%amount, %fees,  "Determined swap amount");
+
+                            (amount, None)
+                        }
+                    };
+
+                    db.insert_peer_id(swap_id, seller_peer_id).await?;
+                    db.insert_judecoin_address(swap_id, judecoin_receive_address)
+                        .await?;
+
+                    let swap = Swap::new(
+                        db.clone(),
+                        swap_id,
+                        bitcoin_wallet.clone(),
+                        judecoin_wallet.clone(),
+                        env_config,
+                        event_loop_handle,
+                        judecoin_receive_address,
+                        bitcoin_change_address.clone(),
+                        amount,
+                        coin_control.clone(),
+                        external_funding,
+                        price_protection,
+                    );
+
+                    tokio::select! {
+                        result = &mut event_loop => {
+                            result
+                                .context("EventLoop panicked")?;
+                        },
+                        result = bob::run(swap) => {
+                            result.context("Failed to complete swap")?;
+                        }
+                    }
+
+                    Ok::<_, anyhow::Error>(())
                 }
+                .await;
+
+                event_loop.abort();
+
+                let error = match result {
+                    Ok(()) => break,
+                    Err(error) => error,
+                };
+
+                // No state is persisted before the swap setup completed, so without one no
+                // Bitcoin was locked and we can safely forget the seller and try the next one.
+                if db.get_state(swap_id).await.is_ok() {
+                    return Err(error);
+                }
+                db.forget_swap(swap_id).await?;
+
+                let is_last_seller = index + 1 == sellers.len();
+                if is_last_seller {
+                    return Err(error);
+                }
+
+                tracing::warn!(%seller, "Failed to swap with seller, trying the next one: {:#}", error);
             }
         }
         Command::History => {
@@ -173,9 +341,10 @@ async fn main() -> Result<()> {
         }
         Command::WithdrawBtc {
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_fee_strategy,
             amount,
             address,
+            coin_control,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
@@ -185,7 +354,7 @@ async fn main() -> Result<()> {
                 &seed,
                 data_dir.clone(),
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy,
             )
             .await?;
 
@@ -193,13 +362,23 @@ async fn main() -> Result<()> {
                 Some(amount) => amount,
                 None => {
                     bitcoin_wallet
-                        .max_giveable(address.script_pubkey().len())
+                        .max_giveable(
+                            address.script_pubkey().len(),
+                            bitcoin::TxKind::Withdraw,
+                            &coin_control,
+                        )
                         .await?
                 }
             };
 
             let psbt = bitcoin_wallet
-                .send_to_address(address, amount, None)
+                .send_to_address(
+                    address,
+                    amount,
+                    None,
+                    bitcoin::TxKind::Withdraw,
+                    &coin_control,
+                )
                 .await?;
             let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
 
@@ -208,7 +387,7 @@ async fn main() -> Result<()> {
 
         Command::Balance {
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_fee_strategy,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
@@ -218,7 +397,7 @@ async fn main() -> Result<()> {
                 &seed,
                 data_dir.clone(),
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy,
             )
             .await?;
 
@@ -228,12 +407,129 @@ async fn main() -> Result<()> {
                 "Checked Bitcoin balance",
             );
         }
+        Command::BtcHistory {
+            bitcoin_electrum_rpc_url,
+            bitcoin_fee_strategy,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = open_db(data_dir.join("sqlite")).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_url,
+                &seed,
+                data_dir.clone(),
+                env_config,
+                bitcoin_fee_strategy,
+            )
+            .await?;
+
+            let labels = protocol::bitcoin_labels(db.as_ref()).await?;
+            let history = bitcoin_wallet.history(&labels).await?;
+
+            bitcoin::print_history(history, json)?;
+        }
+        Command::ListProfiles { base_dir } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            for name in cli::profile::list(&base_dir)? {
+                println!("{}", name);
+            }
+        }
+        Command::CreateProfile { base_dir, name } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            let path = cli::profile::create(&base_dir, &name)?;
+            tracing::info!(%name, path=%path.display(), "Created profile, use it with --profile");
+        }
+        Command::DeleteProfile {
+            base_dir,
+            name,
+            force,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            cli::profile::delete(&base_dir, &name, force)?;
+            tracing::info!(%name, "Deleted profile");
+        }
+        Command::ExportSeed => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+
+            println!("{}", seed.to_mnemonic());
+            eprintln!("Anyone with these words can spend your funds, store them offline.");
+        }
+        Command::RestoreSeed {
+            bitcoin_electrum_rpc_url,
+            bitcoin_fee_strategy,
+            gap_limit,
+            birthday_height,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            eprintln!("Enter the 24 words of your seed:");
+            let mut mnemonic = String::new();
+            std::io::stdin().read_line(&mut mnemonic)?;
+
+            let seed = Seed::from_mnemonic(&mnemonic).context("Failed to parse mnemonic")?;
+            seed.restore(data_dir.as_path())
+                .context("Failed to restore seed")?;
+            tracing::info!(path=%data_dir.join("seed.pem").display(), "Restored seed");
+
+            let bitcoin_wallet = bitcoin::Wallet::rescan(
+                bitcoin_electrum_rpc_url,
+                &data_dir.join("wallet"),
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+                bitcoin_fee_strategy,
+                gap_limit,
+                birthday_height,
+            )
+            .await?;
+
+            let bitcoin_balance = bitcoin_wallet.balance().await?;
+            tracing::info!(
+                balance = %bitcoin_balance,
+                %gap_limit,
+                "Rescanned Bitcoin wallet",
+            );
+        }
+        Command::Rescan {
+            bitcoin_electrum_rpc_url,
+            bitcoin_fee_strategy,
+            gap_limit,
+            birthday_height,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = bitcoin::Wallet::rescan(
+                bitcoin_electrum_rpc_url,
+                &data_dir.join("wallet"),
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+                bitcoin_fee_strategy,
+                gap_limit,
+                birthday_height,
+            )
+            .await?;
+
+            let bitcoin_balance = bitcoin_wallet.balance().await?;
+            tracing::info!(
+                balance = %bitcoin_balance,
+                %gap_limit,
+                "Rescanned Bitcoin wallet",
+            );
+        }
         Command::Resume {
             swap_id,
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_fee_strategy,
             judecoin_daemon_address,
             tor_socks5_port,
+            tor_control_port,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
             let db = open_db(data_dir.join("sqlite")).await?;
@@ -245,7 +541,7 @@ async fn main() -> Result<()> {
                 &seed,
                 data_dir.clone(),
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy,
             )
             .await?;
             let (judecoin_wallet, _process) =
@@ -255,9 +551,25 @@ async fn main() -> Result<()> {
             let seller_peer_id = db.get_peer_id(swap_id).await?;
             let seller_addresses = db.get_addresses(seller_peer_id).await?;
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
+            let behaviour = cli::Behaviour::new(
+                seller_peer_id,
+                env_config,
+                bitcoin_wallet.clone(),
+                seed.derive_libp2p_identity().public(),
+            );
             let mut swarm =
                 swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
+            let _onion_service = match tor_control_port {
+                Some(control_port) => Some(
+                    swarm::listen_on_onion_service(
+                        &mut swarm,
+                        tor::Client::new(tor_socks5_port).with_control_port(control_port),
+                        &seed.derive_torv3_key(),
+                    )
+                    .await?,
+                ),
+                None => None,
+            };
             let our_peer_id = swarm.local_peer_id();
             tracing::debug!(peer_id = %our_peer_id, "Network layer initialized");
 
@@ -268,7 +580,7 @@ async fn main() -> Result<()> {
             }
 
             let (event_loop, event_loop_handle) =
-                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
+                EventLoop::new(swap_id, swarm, seller_peer_id, db.clone(), env_config)?;
             let handle = tokio::spawn(event_loop.run());
 
             let judecoin_receive_address = db.get_judecoin_address(swap_id).await?;
@@ -282,6 +594,11 @@ async fn main() -> Result<()> {
                 judecoin_receive_address,
             )
             .await?;
+            let swap = if swap.state.requires_external_wallet() {
+                swap.with_external_funding(cli::external_funding(env_config.bitcoin_network))
+            } else {
+                swap
+            };
 
             tokio::select! {
                 event_loop_result = handle => {
@@ -295,7 +612,7 @@ async fn main() -> Result<()> {
         Command::Cancel {
             swap_id,
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_fee_strategy,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
             let db = open_db(data_dir.join("sqlite")).await?;
@@ -307,7 +624,7 @@ async fn main() -> Result<()> {
                 &seed,
                 data_dir,
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy,
             )
             .await?;
 
@@ -317,7 +634,7 @@ async fn main() -> Result<()> {
         Command::Refund {
             swap_id,
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_fee_strategy,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
             let db = open_db(data_dir.join("sqlite")).await?;
@@ -329,7 +646,7 @@ async fn main() -> Result<()> {
                 &seed,
                 data_dir,
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy,
             )
             .await?;
 
@@ -339,46 +656,58 @@ async fn main() -> Result<()> {
             rendezvous_point,
             namespace,
             tor_socks5_port,
+            cached,
         } => {
-            let rendezvous_node_peer_id = rendezvous_point
-                .extract_peer_id()
-                .context("Rendezvous node address must contain peer ID")?;
+            let rendezvous_points = with_peer_ids(rendezvous_point)?;
 
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
-                .context("Failed to read in seed file")?;
-            let identity = seed.derive_libp2p_identity();
-
-            let sellers = list_sellers(
-                rendezvous_node_peer_id,
-                rendezvous_point,
-                namespace,
-                tor_socks5_port,
-                identity,
-            )
-            .await?;
+            let db = open_db(data_dir.join("sqlite")).await?;
+
+            let sellers = if cached {
+                None
+            } else {
+                let seed = Seed::from_file_or_generate(data_dir.as_path())
+                    .context("Failed to read in seed file")?;
+                let identity = seed.derive_libp2p_identity();
+
+                let sellers =
+                    list_sellers(rendezvous_points, namespace, tor_socks5_port, identity).await?;
+                seller_cache::remember_sellers(db.as_ref(), &sellers, OffsetDateTime::now_utc())
+                    .await?;
+
+                Some(sellers)
+            };
+            let reliability = cli::seller_reliability(db.as_ref()).await?;
+            let cached_sellers = seller_cache::cached_sellers(db.as_ref(), &reliability).await?;
+
+            let details = match sellers {
+                Some(sellers) => {
+                    let mut history = cached_sellers
+                        .into_iter()
+                        .filter_map(|cached| Some((cached.multiaddr.extract_peer_id()?, cached)))
+                        .collect::<HashMap<_, _>>();
+
+                    sellers
+                        .into_iter()
+                        .map(|seller| {
+                            let cached = seller
+                                .multiaddr
+                                .extract_peer_id()
+                                .and_then(|peer_id| history.remove(&peer_id));
+
+                            SellerDetails::discovered(seller, cached.as_ref())
+                        })
+                        .collect::<Vec<_>>()
+                }
+                None => cached_sellers
+                    .iter()
+                    .map(SellerDetails::cached)
+                    .collect::<Vec<_>>(),
+            };
 
             if json {
-                for seller in sellers {
-                    match seller.status {
-                        SellerStatus::Online(quote) => {
-                            tracing::info!(
-                                price = %quote.price.to_string(),
-                                min_quantity = %quote.min_quantity.to_string(),
-                                max_quantity = %quote.max_quantity.to_string(),
-                                status = "Online",
-                                address = %seller.multiaddr.to_string(),
-                                "Fetched peer status"
-                            );
-                        }
-                        SellerStatus::Unreachable => {
-                            tracing::info!(
-                                status = "Unreachable",
-                                address = %seller.multiaddr.to_string(),
-                                "Fetched peer status"
-                            );
-                        }
-                    }
+                for seller in details {
+                    println!("{}", serde_json::to_string(&seller)?);
                 }
             } else {
                 let mut table = Table::new();
@@ -388,32 +717,27 @@ async fn main() -> Result<()> {
                     "MIN_QUANTITY",
                     "MAX_QUANTITY",
                     "STATUS",
+                    "LATENCY",
+                    "LAST_SEEN",
+                    "PRICE_HISTORY",
+                    "RELIABILITY",
                     "ADDRESS",
                 ]);
 
-                for seller in sellers {
-                    let row = match seller.status {
-                        SellerStatus::Online(quote) => {
-                            vec![
-                                quote.price.to_string(),
-                                quote.min_quantity.to_string(),
-                                quote.max_quantity.to_string(),
-                                "Online".to_owned(),
-                                seller.multiaddr.to_string(),
-                            ]
-                        }
-                        SellerStatus::Unreachable => {
-                            vec![
-                                "???".to_owned(),
-                                "???".to_owned(),
-                                "???".to_owned(),
-                                "Unreachable".to_owned(),
-                                seller.multiaddr.to_string(),
-                            ]
-                        }
-                    };
-
-                    table.add_row(row);
+                for seller in details {
+                    let unknown = || "???".to_owned();
+
+                    table.add_row(vec![
+                        seller.price.unwrap_or_else(unknown),
+                        seller.min_quantity.unwrap_or_else(unknown),
+                        seller.max_quantity.unwrap_or_else(unknown),
+                        seller.status.to_owned(),
+                        seller.latency.unwrap_or_else(unknown),
+                        seller.last_seen.unwrap_or_else(|| "never".to_owned()),
+                        seller.price_history.unwrap_or_else(unknown),
+                        seller.reliability.unwrap_or_else(|| "no swaps".to_owned()),
+                        seller.address,
+                    ]);
                 }
 
                 println!("{}", table);
@@ -421,7 +745,7 @@ async fn main() -> Result<()> {
         }
         Command::ExportBitcoinWallet {
             bitcoin_electrum_rpc_url,
-            bitcoin_target_block,
+            bitcoin_fee_strategy,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
 
@@ -432,7 +756,7 @@ async fn main() -> Result<()> {
                 &seed,
                 data_dir.clone(),
                 env_config,
-                bitcoin_target_block,
+                bitcoin_fee_strategy,
             )
             .await?;
             let wallet_export = bitcoin_wallet.wallet_export("cli").await?;
@@ -484,7 +808,7 @@ async fn init_bitcoin_wallet(
     seed: &Seed,
     data_dir: PathBuf,
     env_config: Config,
-    bitcoin_target_block: usize,
+    bitcoin_fee_strategy: bitcoin::FeeStrategy,
 ) -> Result<bitcoin::Wallet> {
     let wallet_dir = data_dir.join("wallet");
 
@@ -493,7 +817,7 @@ async fn init_bitcoin_wallet(
         &wallet_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
-        bitcoin_target_block,
+        bitcoin_fee_strategy,
     )
     .await
     .context("Failed to initialize Bitcoin wallet")?;
@@ -538,6 +862,228 @@ fn qr_code(value: &impl ToString) -> Result<String> {
     Ok(qr_code)
 }
 
+/// A row of the `list-sellers` output, combining a fresh discovery with what
+/// we remember about the seller.
+///
+/// Fields we know nothing about are `None` and serialized as `null`.
+#[derive(Serialize)]
+struct SellerDetails {
+    price: Option<String>,
+    min_quantity: Option<String>,
+    max_quantity: Option<String>,
+    status: &'static str,
+    latency: Option<String>,
+    last_seen: Option<String>,
+    price_history: Option<String>,
+    reliability: Option<String>,
+    address: String,
+}
+
+impl SellerDetails {
+    fn discovered(seller: cli::Seller, cached: Option<&CachedSeller>) -> Self {
+        let (quote, status) = match seller.status {
+            SellerStatus::Online(quote) => (Some(quote), "Online"),
+            SellerStatus::Unreachable => (None, "Unreachable"),
+        };
+
+        Self::new(quote, status, seller.latency, cached, seller.multiaddr)
+    }
+
+    fn cached(cached: &CachedSeller) -> Self {
+        let latest_quote = cached.latest_quote();
+
+        Self::new(
+            latest_quote.map(|quote| quote.quote),
+            "Cached",
+            latest_quote.and_then(|quote| quote.latency),
+            Some(cached),
+            cached.multiaddr.clone(),
+        )
+    }
+
+    fn new(
+        quote: Option<BidQuote>,
+        status: &'static str,
+        latency: Option<Duration>,
+        cached: Option<&CachedSeller>,
+        address: Multiaddr,
+    ) -> Self {
+        Self {
+            price: quote.map(|quote| quote.price.to_string()),
+            min_quantity: quote.map(|quote| quote.min_quantity.to_string()),
+            max_quantity: quote.map(|quote| quote.max_quantity.to_string()),
+            status,
+            latency: latency.map(|latency| format!("{} ms", latency.as_millis())),
+            last_seen: cached
+                .and_then(|cached| cached.last_seen)
+                .map(|last_seen| last_seen.to_string()),
+            price_history: cached.and_then(|cached| {
+                let (lowest, highest) = cached.price_range()?;
+
+                Some(format!(
+                    "{} quotes, {} - {}",
+                    cached.quotes.len(),
+                    lowest,
+                    highest
+                ))
+            }),
+            reliability: cached.and_then(|cached| {
+                let score = cached.reliability.score()?;
+                let finished = cached.reliability.completed + cached.reliability.refunded;
+
+                Some(format!("{}% of {} swaps", score, finished))
+            }),
+            address: address.to_string(),
+        }
+    }
+}
+
+fn with_peer_ids(rendezvous_points: Vec<Multiaddr>) -> Result<Vec<(PeerId, Multiaddr)>> {
+    rendezvous_points
+        .into_iter()
+        .map(|address| {
+            let peer_id = address
+                .extract_peer_id()
+                .context("Rendezvous node address must contain peer ID")?;
+
+            Ok((peer_id, address))
+        })
+        .collect()
+}
+
+async fn ensure_amount_within_quote(
+    amount: bitcoin::Amount,
+    bid_quote: impl Future<Output = Result<BidQuote>>,
+) -> Result<()> {
+    let bid_quote = bid_quote.await?;
+
+    if amount < bid_quote.min_quantity || amount > bid_quote.max_quantity {
+        bail!(
+            "Seller only accepts between {} and {}, got {}",
+            bid_quote.min_quantity,
+            bid_quote.max_quantity,
+            amount
+        )
+    }
+
+    Ok(())
+}
+
+fn ensure_price_within_limits(
+    bid_quote: &BidQuote,
+    price_protection: &PriceProtection,
+) -> Result<()> {
+    if let Some(max_price) = price_protection.max_price {
+        if bid_quote.price > max_price {
+            bail!(
+                "Seller's price of {} per JUDE is above the maximum of {}",
+                bid_quote.price,
+                max_price
+            )
+        }
+    }
+
+    Ok(())
+}
+
+/// The fees of the cancel and refund transactions, both paid from the locked
+/// amount.
+async fn estimate_refund_fees(
+    bitcoin_wallet: &bitcoin::Wallet,
+    amount: bitcoin::Amount,
+) -> Result<bitcoin::Amount> {
+    let cancel_fee = bitcoin_wallet
+        .estimate_fee(bitcoin::TxKind::Cancel, TxCancel::weight(), amount)
+        .await?;
+    let refund_fee = bitcoin_wallet
+        .estimate_fee(bitcoin::TxKind::Refund, TxRefund::weight(), amount)
+        .await?;
+
+    Ok(cancel_fee + refund_fee)
+}
+
+/// Waits until the wallet can fund the given fixed amount of the swap plus the
+/// fees of a potential refund.
+///
+/// The refund fees are not locked but stay in the wallet, so a refund makes up
+/// for the full amount. The change of the lock transaction goes to the change
+/// address, so any Bitcoin deposited on top is returned.
+///
+/// Returns the amount to swap and the refund fees.
+async fn determine_fixed_btc_to_swap<FRF, TRF, FB, TB, FMG, TMG, FS, TS>(
+    json: bool,
+    bid_quote: BidQuote,
+    amount: SwapAmount,
+    refund_fees_fn: FRF,
+    get_new_address: impl Future<Output = Result<bitcoin::Address>>,
+    balance: FB,
+    max_giveable_fn: FMG,
+    sync: FS,
+) -> Result<(bitcoin::Amount, bitcoin::Amount)>
+where
+    TRF: Future<Output = Result<bitcoin::Amount>>,
+    FRF: FnOnce(bitcoin::Amount) -> TRF,
+    TB: Future<Output = Result<bitcoin::Amount>>,
+    FB: Fn() -> TB,
+    TMG: Future<Output = Result<bitcoin::Amount>>,
+    FMG: Fn() -> TMG,
+    TS: Future<Output = Result<()>>,
+    FS: Fn() -> TS,
+{
+    let btc_swap_amount = amount.btc(bid_quote.price);
+    ensure_amount_within_quote(btc_swap_amount, async { Ok(bid_quote) }).await?;
+
+    let refund_fees = refund_fees_fn(btc_swap_amount).await?;
+    if refund_fees >= btc_swap_amount {
+        bail!(
+            "The refund fees of {} would consume the whole swap amount of {}",
+            refund_fees,
+            btc_swap_amount
+        );
+    }
+    let required_amount = btc_swap_amount + refund_fees;
+
+    tracing::info!(
+        price = %bid_quote.price,
+        amount = %btc_swap_amount,
+        %refund_fees,
+        "Computed Bitcoin amount from quote",
+    );
+
+    let mut max_giveable = max_giveable_fn().await?;
+
+    if max_giveable < required_amount {
+        let deposit_address = get_new_address.await?;
+
+        if !json {
+            eprintln!("{}", qr_code(&deposit_address)?);
+        }
+
+        tracing::info!(
+            %deposit_address,
+            %max_giveable,
+            %required_amount,
+            "Waiting for Bitcoin deposit",
+        );
+
+        while max_giveable < required_amount {
+            sync().await?;
+            let new_max_givable = max_giveable_fn().await?;
+
+            if new_max_givable > max_giveable {
+                max_giveable = new_max_givable;
+
+                let new_balance = balance().await?;
+                tracing::info!(%new_balance, %max_giveable, "Received Bitcoin");
+            } else {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    Ok((btc_swap_amount, refund_fees))
+}
+
 async fn determine_btc_to_swap<FB, TB, FMG, TMG, FS, TS>(
     json: bool,
     bid_quote: impl Future<Output = Result<BidQuote>>,
@@ -915,6 +1461,105 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn given_jude_amount_then_swaps_btc_needed_at_quoted_price() {
+        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+            Amount::from_btc(0.01).unwrap()
+        ])));
+
+        let (amount, refund_fees) = determine_fixed_btc_to_swap(
+            true,
+            quote_with_max(0.01),
+            SwapAmount::Jude(judecoin::Amount::parse_judecoin("2.5").unwrap()),
+            |_| async { Ok(Amount::from_btc(0.0001)?) },
+            async { panic!("should not request new address when balance is sufficient") },
+            || async { Ok(Amount::from_btc(0.0101)?) },
+            || async {
+                let mut result = givable.lock().unwrap();
+                result.give()
+            },
+            || async { Ok(()) },
+        )
+        .await
+        .unwrap();
+
+        let expected_amount = Amount::from_btc(0.0025).unwrap();
+        let expected_refund_fees = Amount::from_btc(0.0001).unwrap();
+
+        assert_eq!(
+            (amount, refund_fees),
+            (expected_amount, expected_refund_fees)
+        );
+    }
+
+    #[tokio::test]
+    async fn given_insufficient_balance_for_fixed_amount_and_refund_fees_then_waits_for_deposit() {
+        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+            Amount::from_btc(0.001).unwrap(),
+            Amount::from_btc(0.0035).unwrap(),
+            Amount::from_btc(0.005).unwrap(),
+        ])));
+
+        let (amount, refund_fees) = determine_fixed_btc_to_swap(
+            true,
+            quote_with_max(0.01),
+            SwapAmount::Btc(Amount::from_btc(0.003).unwrap()),
+            |_| async { Ok(Amount::from_btc(0.001)?) },
+            get_dummy_address(),
+            || async { Ok(Amount::from_btc(0.0051)?) },
+            || async {
+                let mut result = givable.lock().unwrap();
+                result.give()
+            },
+            || async { Ok(()) },
+        )
+        .await
+        .unwrap();
+
+        let expected_amount = Amount::from_btc(0.003).unwrap();
+        let expected_refund_fees = Amount::from_btc(0.001).unwrap();
+
+        assert_eq!(
+            (amount, refund_fees),
+            (expected_amount, expected_refund_fees)
+        );
+        assert_eq!(givable.lock().unwrap().call_counter, 3);
+    }
+
+    #[tokio::test]
+    async fn given_refund_fees_above_fixed_amount_then_fails() {
+        let result = determine_fixed_btc_to_swap(
+            true,
+            quote_with_max(0.01),
+            SwapAmount::Btc(Amount::from_btc(0.0001).unwrap()),
+            |_| async { Ok(Amount::from_btc(0.0002)?) },
+            get_dummy_address(),
+            || async { Ok(Amount::from_btc(0.1)?) },
+            || async { Ok(Amount::from_btc(0.1)?) },
+            || async { Ok(()) },
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn given_fixed_amount_above_max_quantity_then_fails() {
+        let result = determine_fixed_btc_to_swap(
+            true,
+            quote_with_max(0.01),
+            SwapAmount::Btc(Amount::from_btc(0.02).unwrap()),
+            |_| async { Ok(Amount::ZERO) },
+            get_dummy_address(),
+            || async { Ok(Amount::from_btc(0.1)?) },
+            || async { Ok(Amount::from_btc(0.1)?) },
+            || async { Ok(()) },
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
     struct MaxGiveable {
         amounts: Vec<Amount>,
         call_counter: usize,
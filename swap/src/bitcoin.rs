@@ -1,14 +1,26 @@
 pub mod wallet;
 
 mod cancel;
+mod coin_control;
+mod fee_estimation;
+mod fee_ladder;
+mod history;
 mod lock;
+mod payout;
 mod punish;
 mod redeem;
 mod refund;
 mod timelocks;
 
 pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
-pub use crate::bitcoin::lock::TxLock;
+pub use crate::bitcoin::coin_control::CoinControl;
+pub use crate::bitcoin::fee_estimation::{FeeEstimator, FeeStrategy, TargetBlocks, TxKind};
+pub use crate::bitcoin::fee_ladder::{
+    estimate_cancel_fee, select_fee_level, FeeLevel, MAX_FEE_LEVELS,
+};
+pub use crate::bitcoin::history::{print_history, HistoryEntry, Label, Labels};
+pub use crate::bitcoin::lock::{ExternallyFundedTxLock, TxLock};
+pub use crate::bitcoin::payout::PayoutWallet;
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
 pub use crate::bitcoin::refund::TxRefund;
@@ -19,7 +31,7 @@ pub use ::bitcoin::{Address, Network, Transaction, Txid};
 pub use ecdsa_fun::adaptor::EncryptedSignature;
 pub use ecdsa_fun::fun::Scalar;
 pub use ecdsa_fun::Signature;
-pub use wallet::Wallet;
+pub use wallet::{Wallet, DEFAULT_RESCAN_GAP_LIMIT};
 
 #[cfg(test)]
 pub use wallet::WalletBuilder;
@@ -206,6 +218,13 @@ pub fn verify_encsig(
 #[error("encrypted signature is invalid")]
 pub struct InvalidEncryptedSignature;
 
+/// Builds the P2WSH 2-of-2 descriptor both parties lock the Bitcoin in.
+///
+/// A P2TR key-path output with MuSig2 aggregation of `A` and `B` would make
+/// swaps indistinguishable from single-sig spends, but is not supported yet:
+/// `bitcoin` 0.27 and `bdk` 0.12 can neither build nor sign taproot outputs,
+/// and the adaptor signatures over `TxRedeem` are ECDSA only. Both would have
+/// to be upgraded before a taproot variant can be negotiated in `swap_setup`.
 pub fn build_shared_output_descriptor(A: Point, B: Point) -> Descriptor<bitcoin::PublicKey> {
     const MINISCRIPT_TEMPLATE: &str = "c:and_v(v:pk(A),pk_k(B))";
 
@@ -380,11 +399,11 @@ mod tests {
         let jude_amount = crate::judecoin::Amount::from_piconero(10000);
 
         let tx_redeem_fee = alice_wallet
-            .estimate_fee(TxRedeem::weight(), btc_amount)
+            .estimate_fee(TxKind::Redeem, TxRedeem::weight(), btc_amount)
             .await
             .unwrap();
         let tx_punish_fee = alice_wallet
-            .estimate_fee(TxPunish::weight(), btc_amount)
+            .estimate_fee(TxKind::Punish, TxPunish::weight(), btc_amount)
             .await
             .unwrap();
         let redeem_address = alice_wallet.new_address().await.unwrap();
@@ -413,6 +432,8 @@ mod tests {
             config.judecoin_finality_confirmations,
             spending_fee,
             spending_fee,
+            FeeLevel::ladder(FeeLevel::new(spending_fee, spending_fee), btc_amount),
+            CoinControl::default(),
         );
 
         let message0 = bob_state0.next_message();
@@ -439,17 +460,25 @@ mod tests {
         let encrypted_signature = bob_state4.tx_redeem_encsig();
         let bob_state6 = bob_state4.cancel();
 
-        let cancel_transaction = alice_state3.signed_cancel_transaction().unwrap();
-        let punish_transaction = alice_state3.signed_punish_transaction().unwrap();
         let redeem_transaction = alice_state3
             .signed_redeem_transaction(encrypted_signature)
             .unwrap();
-        let refund_transaction = bob_state6.signed_refund_transaction().unwrap();
-
         assert_weight(redeem_transaction, TxRedeem::weight(), "TxRedeem");
-        assert_weight(cancel_transaction, TxCancel::weight(), "TxCancel");
-        assert_weight(punish_transaction, TxPunish::weight(), "TxPunish");
-        assert_weight(refund_transaction, TxRefund::weight(), "TxRefund");
+
+        let alice_fee_levels = alice_state3.fee_levels();
+        let bob_fee_levels = bob_state6.fee_levels();
+        assert_eq!(alice_fee_levels.len(), 4);
+        assert_eq!(bob_fee_levels.len(), 4);
+
+        for (alice_level, bob_level) in alice_fee_levels.iter().zip(bob_fee_levels.iter()) {
+            let cancel_transaction = alice_state3.signed_cancel_transaction(alice_level).unwrap();
+            let punish_transaction = alice_state3.signed_punish_transaction(alice_level).unwrap();
+            let refund_transaction = bob_state6.signed_refund_transaction(bob_level).unwrap();
+
+            assert_weight(cancel_transaction, TxCancel::weight(), "TxCancel");
+            assert_weight(punish_transaction, TxPunish::weight(), "TxPunish");
+            assert_weight(refund_transaction, TxRefund::weight(), "TxRefund");
+        }
     }
 
     // Weights fluctuate because of the length of the signatures. Valid ecdsa
@@ -28,6 +28,12 @@ impl CancelTimelock {
     }
 }
 
+impl From<CancelTimelock> for u32 {
+    fn from(timelock: CancelTimelock) -> Self {
+        timelock.0
+    }
+}
+
 impl Add<CancelTimelock> for BlockHeight {
     type Output = BlockHeight;
 
@@ -0,0 +1,218 @@
+use crate::bitcoin::Amount;
+use ::bitcoin::{OutPoint, Txid};
+use anyhow::{bail, Result};
+use bdk::LocalUtxo;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Restricts which outputs of the wallet may be spent by a transaction.
+///
+/// The default does not restrict anything, i.e. coin selection is entirely left
+/// to the wallet.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct CoinControl {
+    /// Only these outputs may be spent. If empty, any output may be spent.
+    #[serde(default)]
+    pub outpoints: Vec<OutPoint>,
+    /// Outputs worth less than this are never spent.
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub min_utxo_value: Option<Amount>,
+    /// Never spend outputs received in different transactions together.
+    ///
+    /// Spending them in one transaction would reveal that they belong to the
+    /// same owner.
+    #[serde(default)]
+    pub single_source: bool,
+}
+
+impl CoinControl {
+    /// Splits the given unspent outputs into the ones we may and the ones we
+    /// must not spend.
+    ///
+    /// Returns a single selection, unless outputs may only be spent together
+    /// with outputs of the same source. In that case there is one selection
+    /// per source, ordered by ascending value, and the caller is expected to
+    /// use the first one that suffices.
+    ///
+    /// Fails if one of the explicitly requested outputs is not among the
+    /// unspent outputs of the wallet.
+    pub(in crate::bitcoin) fn select(&self, utxos: Vec<LocalUtxo>) -> Result<Vec<Selection>> {
+        for outpoint in &self.outpoints {
+            if !utxos.iter().any(|utxo| &utxo.outpoint == outpoint) {
+                bail!("Output {} is not spendable by this wallet", outpoint)
+            }
+        }
+
+        let (spendable, unspendable): (Vec<_>, Vec<_>) =
+            utxos.into_iter().partition(|utxo| self.allows(utxo));
+        let unspendable = unspendable
+            .into_iter()
+            .map(|utxo| utxo.outpoint)
+            .collect::<Vec<_>>();
+
+        if !self.single_source || spendable.is_empty() {
+            return Ok(vec![Selection::new(&spendable, unspendable)]);
+        }
+
+        let mut sources = BTreeMap::<Txid, Vec<LocalUtxo>>::new();
+        for utxo in spendable {
+            sources.entry(utxo.outpoint.txid).or_default().push(utxo);
+        }
+
+        let mut selections = sources
+            .iter()
+            .map(|(txid, source)| {
+                let other_sources = sources
+                    .iter()
+                    .filter(|(other, _)| *other != txid)
+                    .flat_map(|(_, utxos)| utxos.iter().map(|utxo| utxo.outpoint));
+
+                Selection::new(
+                    source,
+                    unspendable.iter().copied().chain(other_sources).collect(),
+                )
+            })
+            .collect::<Vec<_>>();
+        selections.sort_by_key(|selection| selection.value);
+
+        Ok(selections)
+    }
+
+    fn allows(&self, utxo: &LocalUtxo) -> bool {
+        let selected = self.outpoints.is_empty() || self.outpoints.contains(&utxo.outpoint);
+        let above_min = self
+            .min_utxo_value
+            .map_or(true, |min| utxo.txout.value >= min.as_sat());
+
+        selected && above_min
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(in crate::bitcoin) struct Selection {
+    /// The total value of the outputs that may be spent.
+    pub value: Amount,
+    /// The outputs the wallet must not touch.
+    pub unspendable: Vec<OutPoint>,
+}
+
+impl Selection {
+    fn new(spendable: &[LocalUtxo], unspendable: Vec<OutPoint>) -> Self {
+        Self {
+            value: Amount::from_sat(spendable.iter().map(|utxo| utxo.txout.value).sum()),
+            unspendable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::hashes::Hash;
+    use ::bitcoin::{Script, TxOut};
+    use bdk::KeychainKind;
+
+    fn utxo(vout: u32, value: u64) -> LocalUtxo {
+        utxo_of(Txid::default(), vout, value)
+    }
+
+    fn utxo_of(txid: Txid, vout: u32, value: u64) -> LocalUtxo {
+        LocalUtxo {
+            outpoint: OutPoint::new(txid, vout),
+            txout: TxOut {
+                value,
+                script_pubkey: Script::new(),
+            },
+            keychain: KeychainKind::External,
+        }
+    }
+
+    #[test]
+    fn default_allows_all_outputs() {
+        let selection = CoinControl::default()
+            .select(vec![utxo(0, 1_000), utxo(1, 2_000)])
+            .unwrap();
+
+        assert_eq!(selection, vec![Selection {
+            value: Amount::from_sat(3_000),
+            unspendable: vec![],
+        }]);
+    }
+
+    #[test]
+    fn only_selected_outputs_are_spendable() {
+        let coin_control = CoinControl {
+            outpoints: vec![OutPoint::new(Txid::default(), 1)],
+            ..CoinControl::default()
+        };
+
+        let selection = coin_control
+            .select(vec![utxo(0, 1_000), utxo(1, 2_000)])
+            .unwrap();
+
+        assert_eq!(selection, vec![Selection {
+            value: Amount::from_sat(2_000),
+            unspendable: vec![OutPoint::new(Txid::default(), 0)],
+        }]);
+    }
+
+    #[test]
+    fn outputs_below_min_value_are_excluded() {
+        let coin_control = CoinControl {
+            min_utxo_value: Some(Amount::from_sat(1_000)),
+            ..CoinControl::default()
+        };
+
+        let selection = coin_control
+            .select(vec![utxo(0, 600), utxo(1, 2_000)])
+            .unwrap();
+
+        assert_eq!(selection, vec![Selection {
+            value: Amount::from_sat(2_000),
+            unspendable: vec![OutPoint::new(Txid::default(), 0)],
+        }]);
+    }
+
+    #[test]
+    fn given_single_source_then_one_selection_per_source_by_ascending_value() {
+        let (deposit, other_deposit) = (Txid::from_inner([1; 32]), Txid::from_inner([2; 32]));
+        let coin_control = CoinControl {
+            single_source: true,
+            ..CoinControl::default()
+        };
+
+        let selections = coin_control
+            .select(vec![
+                utxo_of(deposit, 0, 5_000),
+                utxo_of(other_deposit, 0, 1_000),
+                utxo_of(other_deposit, 1, 2_000),
+            ])
+            .unwrap();
+
+        assert_eq!(selections, vec![
+            Selection {
+                value: Amount::from_sat(3_000),
+                unspendable: vec![OutPoint::new(deposit, 0)],
+            },
+            Selection {
+                value: Amount::from_sat(5_000),
+                unspendable: vec![
+                    OutPoint::new(other_deposit, 0),
+                    OutPoint::new(other_deposit, 1)
+                ],
+            },
+        ]);
+    }
+
+    #[test]
+    fn given_unknown_output_selection_fails() {
+        let coin_control = CoinControl {
+            outpoints: vec![OutPoint::new(Txid::default(), 5)],
+            ..CoinControl::default()
+        };
+
+        let result = coin_control.select(vec![utxo(0, 1_000)]);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::bitcoin::{Amount, TxCancel, TxKind, Wallet};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Multipliers applied to the cancel and refund fees estimated at setup time
+/// to derive the escalated fee levels that are pre-signed during swap setup.
+const FEE_LADDER_MULTIPLIERS: [u64; 3] = [2, 4, 8];
+
+/// The maximum number of escalated fee levels a party is willing to sign for
+/// its counterparty.
+pub const MAX_FEE_LEVELS: usize = 4;
+
+const DUST_AMOUNT: u64 = 546;
+
+/// A pair of fees at which the cancel and the refund transaction are
+/// pre-signed.
+///
+/// The refund transaction spends the cancel transaction, hence the two fees
+/// always come as a pair: a refund signature is only valid for the cancel
+/// transaction it was created for.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FeeLevel {
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub tx_cancel_fee: Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub tx_refund_fee: Amount,
+}
+
+impl FeeLevel {
+    pub fn new(tx_cancel_fee: Amount, tx_refund_fee: Amount) -> Self {
+        Self {
+            tx_cancel_fee,
+            tx_refund_fee,
+        }
+    }
+
+    /// Derives the escalated fee levels on top of the given base level.
+    ///
+    /// Levels that a lock output worth `btc` cannot afford are omitted, see
+    /// [`FeeLevel::is_affordable`].
+    pub fn ladder(base: FeeLevel, btc: Amount) -> Vec<FeeLevel> {
+        FEE_LADDER_MULTIPLIERS
+            .iter()
+            .map(|multiplier| FeeLevel {
+                tx_cancel_fee: base.tx_cancel_fee * *multiplier,
+                tx_refund_fee: base.tx_refund_fee * *multiplier,
+            })
+            .filter(|level| level.is_affordable(btc))
+            .collect()
+    }
+
+    /// Validates a fee ladder proposed by the counterparty.
+    ///
+    /// The levels must be strictly increasing, above the base level and
+    /// affordable by a lock output worth `btc`. This is the same rule
+    /// [`FeeLevel::ladder`] applies, so a ladder we generate is always accepted
+    /// by the counterparty.
+    pub fn validate_ladder(ladder: &[FeeLevel], base: FeeLevel, btc: Amount) -> Result<()> {
+        if ladder.len() > MAX_FEE_LEVELS {
+            bail!(
+                "Fee ladder has {} levels, at most {} are supported",
+                ladder.len(),
+                MAX_FEE_LEVELS
+            )
+        }
+
+        let mut previous = base;
+        for level in ladder {
+            if level.tx_cancel_fee <= previous.tx_cancel_fee
+                || level.tx_refund_fee <= previous.tx_refund_fee
+            {
+                bail!("Fee ladder levels must be strictly increasing")
+            }
+            if !level.is_affordable(btc) {
+                bail!(
+                    "Cancel fee {} of fee ladder exceeds what {} can cover",
+                    level.tx_cancel_fee,
+                    btc
+                )
+            }
+
+            previous = *level;
+        }
+
+        Ok(())
+    }
+
+    /// Whether paying both fees of this level out of a lock output worth `btc`
+    /// leaves an output above the dust limit.
+    ///
+    /// The refund transaction spends the output of the cancel transaction, so
+    /// both fees come out of the lock output. Both parties know the full
+    /// level during swap setup, hence they reach the same decision.
+    fn is_affordable(&self, btc: Amount) -> bool {
+        self.tx_cancel_fee
+            .checked_add(self.tx_refund_fee)
+            .and_then(|fees| btc.checked_sub(fees))
+            .map_or(false, |remaining| remaining.as_sat() > DUST_AMOUNT)
+    }
+}
+
+/// Returns the index of the cheapest level whose cancel fee is at least
+/// `required_fee`.
+///
+/// If none of the levels pays enough the most expensive one is returned. The
+/// levels are expected to be sorted by ascending fee.
+pub fn select_fee_level(levels: &[FeeLevel], required_fee: Amount) -> usize {
+    levels
+        .iter()
+        .position(|level| level.tx_cancel_fee >= required_fee)
+        .unwrap_or_else(|| levels.len().saturating_sub(1))
+}
+
+/// Estimates the fee a cancel transaction spending a lock output worth
+/// `lock_amount` has to pay to confirm in time.
+///
+/// Falls back to zero, i.e. to the lowest fee level, if no estimate is
+/// available.
+pub async fn estimate_cancel_fee(bitcoin_wallet: &Wallet, lock_amount: Amount) -> Amount {
+    match bitcoin_wallet
+        .estimate_fee(TxKind::Cancel, TxCancel::weight(), lock_amount)
+        .await
+    {
+        Ok(fee) => fee,
+        Err(error) => {
+            tracing::warn!(
+                "Failed to estimate cancel fee, falling back to the lowest fee level: {:#}",
+                error
+            );
+            Amount::ZERO
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> FeeLevel {
+        FeeLevel::new(Amount::from_sat(1_000), Amount::from_sat(900))
+    }
+
+    #[test]
+    fn ladder_escalates_base_fees() {
+        let ladder = FeeLevel::ladder(base(), Amount::from_sat(1_000_000));
+
+        assert_eq!(ladder, vec![
+            FeeLevel::new(Amount::from_sat(2_000), Amount::from_sat(1_800)),
+            FeeLevel::new(Amount::from_sat(4_000), Amount::from_sat(3_600)),
+            FeeLevel::new(Amount::from_sat(8_000), Amount::from_sat(7_200)),
+        ]);
+    }
+
+    #[test]
+    fn ladder_omits_levels_that_would_produce_dust() {
+        let ladder = FeeLevel::ladder(base(), Amount::from_sat(8_000));
+
+        assert_eq!(ladder, vec![FeeLevel::new(
+            Amount::from_sat(2_000),
+            Amount::from_sat(1_800)
+        )]);
+    }
+
+    #[test]
+    fn generated_ladder_passes_validation() {
+        let btc = Amount::from_sat(1_000_000);
+        let ladder = FeeLevel::ladder(base(), btc);
+
+        FeeLevel::validate_ladder(&ladder, base(), btc).unwrap();
+    }
+
+    #[test]
+    fn generated_ladder_of_small_swap_passes_validation() {
+        let btc = Amount::from_sat(8_000);
+        let ladder = FeeLevel::ladder(base(), btc);
+
+        FeeLevel::validate_ladder(&ladder, base(), btc).unwrap();
+    }
+
+    #[test]
+    fn given_decreasing_levels_validation_fails() {
+        let ladder = vec![
+            FeeLevel::new(Amount::from_sat(4_000), Amount::from_sat(3_600)),
+            FeeLevel::new(Amount::from_sat(2_000), Amount::from_sat(1_800)),
+        ];
+
+        let result = FeeLevel::validate_ladder(&ladder, base(), Amount::from_sat(1_000_000));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_cancel_fee_exceeding_lock_amount_validation_fails() {
+        let ladder = vec![FeeLevel::new(
+            Amount::from_sat(2_000_000),
+            Amount::from_sat(1_800),
+        )];
+
+        let result = FeeLevel::validate_ladder(&ladder, base(), Amount::from_sat(1_000_000));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_too_many_levels_validation_fails() {
+        let ladder = (1..=MAX_FEE_LEVELS as u64 + 1)
+            .map(|i| FeeLevel::new(Amount::from_sat(1_000 + i), Amount::from_sat(900 + i)))
+            .collect::<Vec<_>>();
+
+        let result = FeeLevel::validate_ladder(&ladder, base(), Amount::from_sat(1_000_000));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn selects_cheapest_sufficient_level() {
+        let levels = vec![
+            base(),
+            FeeLevel::new(Amount::from_sat(2_000), Amount::from_sat(1_800)),
+            FeeLevel::new(Amount::from_sat(4_000), Amount::from_sat(3_600)),
+        ];
+
+        assert_eq!(select_fee_level(&levels, Amount::from_sat(500)), 0);
+        assert_eq!(select_fee_level(&levels, Amount::from_sat(1_000)), 0);
+        assert_eq!(select_fee_level(&levels, Amount::from_sat(1_001)), 1);
+        assert_eq!(select_fee_level(&levels, Amount::from_sat(3_000)), 2);
+    }
+
+    #[test]
+    fn given_no_sufficient_level_selects_most_expensive() {
+        let levels = vec![
+            base(),
+            FeeLevel::new(Amount::from_sat(2_000), Amount::from_sat(1_800)),
+        ];
+
+        assert_eq!(select_fee_level(&levels, Amount::from_sat(10_000)), 1);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::bitcoin::{Amount, Txid};
+use anyhow::Result;
+use comfy_table::Table;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// What a transaction of the internal Bitcoin wallet was made for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Label {
+    Deposit,
+    Lock,
+    Redeem,
+    Refund,
+    Punish,
+    Cancel,
+    Withdrawal,
+    /// The ASB moved funds above its threshold to cold storage.
+    Sweep,
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Label::Deposit => "deposit",
+            Label::Lock => "swap lock",
+            Label::Redeem => "redeem",
+            Label::Refund => "refund",
+            Label::Punish => "punish",
+            Label::Cancel => "cancel",
+            Label::Withdrawal => "withdrawal",
+            Label::Sweep => "sweep",
+        };
+
+        write!(f, "{}", label)
+    }
+}
+
+/// Transactions known from the database, by txid.
+#[derive(Clone, Debug, Default)]
+pub struct Labels(HashMap<Txid, (Label, Option<Uuid>)>);
+
+impl Labels {
+    pub fn insert(&mut self, txid: Txid, label: Label, swap_id: Uuid) {
+        self.0.insert(txid, (label, Some(swap_id)));
+    }
+
+    pub fn insert_sweep(&mut self, txid: Txid) {
+        self.0.insert(txid, (Label::Sweep, None));
+    }
+
+    /// Transactions that are not part of a swap are labelled by the direction
+    /// in which they moved funds.
+    pub(in crate::bitcoin) fn get(
+        &self,
+        txid: Txid,
+        received: Amount,
+        sent: Amount,
+    ) -> (Label, Option<Uuid>) {
+        match self.0.get(&txid) {
+            Some((label, swap_id)) => (*label, *swap_id),
+            None if received > sent => (Label::Deposit, None),
+            None => (Label::Withdrawal, None),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub txid: Txid,
+    pub label: Label,
+    pub swap_id: Option<Uuid>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub received: Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub sent: Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub fee: Option<Amount>,
+    /// `None` while the transaction is unconfirmed.
+    pub confirmation_height: Option<u32>,
+}
+
+/// Prints the wallet history as a table, or as one JSON object per line.
+pub fn print_history(history: Vec<HistoryEntry>, json: bool) -> Result<()> {
+    if json {
+        for entry in history {
+            println!("{}", serde_json::to_string(&entry)?);
+        }
+
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+
+    table.set_header(vec![
+        "TXID",
+        "LABEL",
+        "SWAP ID",
+        "RECEIVED",
+        "SENT",
+        "CONFIRMED AT",
+    ]);
+
+    for entry in history {
+        table.add_row(vec![
+            entry.txid.to_string(),
+            entry.label.to_string(),
+            entry.swap_id.map(|id| id.to_string()).unwrap_or_default(),
+            entry.received.to_string(),
+            entry.sent.to_string(),
+            entry
+                .confirmation_height
+                .map_or_else(|| "unconfirmed".to_string(), |height| height.to_string()),
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::hashes::Hash;
+
+    #[test]
+    fn given_txid_of_swap_then_uses_swap_label() {
+        let txid = Txid::from_inner([1; 32]);
+        let swap_id = Uuid::new_v4();
+        let mut labels = Labels::default();
+        labels.insert(txid, Label::Refund, swap_id);
+
+        let label = labels.get(txid, Amount::from_sat(10_000), Amount::ZERO);
+
+        assert_eq!(label, (Label::Refund, Some(swap_id)));
+    }
+
+    #[test]
+    fn given_unknown_txid_then_labels_by_direction_of_funds() {
+        let labels = Labels::default();
+
+        let incoming = labels.get(
+            Txid::from_inner([2; 32]),
+            Amount::from_sat(10_000),
+            Amount::ZERO,
+        );
+        let outgoing = labels.get(
+            Txid::from_inner([3; 32]),
+            Amount::from_sat(1_000),
+            Amount::from_sat(10_000),
+        );
+
+        assert_eq!(incoming, (Label::Deposit, None));
+        assert_eq!(outgoing, (Label::Withdrawal, None));
+    }
+
+    #[test]
+    fn given_txid_of_sweep_then_uses_sweep_label() {
+        let txid = Txid::from_inner([4; 32]);
+        let mut labels = Labels::default();
+        labels.insert_sweep(txid);
+
+        let label = labels.get(txid, Amount::ZERO, Amount::from_sat(10_000));
+
+        assert_eq!(label, (Label::Sweep, None));
+    }
+}
@@ -1,16 +1,22 @@
 use crate::bitcoin::wallet::{EstimateFeeRate, Watchable};
 use crate::bitcoin::{
-    build_shared_output_descriptor, Address, Amount, PublicKey, Transaction, Wallet,
+    build_shared_output_descriptor, Address, Amount, CoinControl, PublicKey, Transaction, TxKind,
+    Wallet,
 };
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::{OutPoint, TxIn, TxOut, Txid};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use bdk::database::BatchDatabase;
 use bdk::miniscript::{Descriptor, DescriptorTrait};
 use bitcoin::Script;
 use serde::{Deserialize, Serialize};
 
 const SCRIPT_SIZE: usize = 34;
+/// Weight of the witness of a signed P2WPKH input.
+const P2WPKH_WITNESS_WEIGHT: usize = 108;
+/// How far the fee of an externally funded lock transaction may be off from
+/// our own estimate, in either direction.
+const EXTERNAL_FEE_TOLERANCE: u64 = 3;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TxLock {
@@ -25,6 +31,7 @@ impl TxLock {
         A: PublicKey,
         B: PublicKey,
         change: bitcoin::Address,
+        coin_control: &CoinControl,
     ) -> Result<Self>
     where
         C: EstimateFeeRate,
@@ -36,7 +43,7 @@ impl TxLock {
             .expect("can derive address from descriptor");
 
         let psbt = wallet
-            .send_to_address(address, amount, Some(change))
+            .send_to_address(address, amount, Some(change), TxKind::Lock, coin_control)
             .await?;
 
         Ok(Self {
@@ -95,6 +102,77 @@ impl TxLock {
         })
     }
 
+    /// Creates an instance of `TxLock` from a lock transaction that was
+    /// funded by an external wallet.
+    ///
+    /// The placeholder output is replaced with the shared output, which
+    /// leaves the inputs untouched. The external wallet only signs this
+    /// transaction after the swap setup, see [`TxLock::extract_signed`].
+    pub fn from_externally_funded(
+        funded: ExternallyFundedTxLock,
+        A: PublicKey,
+        B: PublicKey,
+        btc: Amount,
+    ) -> Result<Self> {
+        let ExternallyFundedTxLock {
+            mut psbt,
+            placeholder,
+        } = funded;
+
+        let shared_output_script = build_shared_output_descriptor(A.0, B.0).script_pubkey();
+        let vout = psbt
+            .global
+            .unsigned_tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == placeholder)
+            .context("Funded lock transaction does not pay to the placeholder")?;
+        psbt.global.unsigned_tx.output[vout].script_pubkey = shared_output_script;
+        psbt.outputs[vout] = Default::default();
+
+        Self::from_psbt(psbt, A, B, btc)
+    }
+
+    /// Extracts the lock transaction from the PSBT an external wallet signed.
+    ///
+    /// The signed transaction must be the one Alice signed the cancel and
+    /// refund transactions for and all inputs must be finalized native segwit
+    /// inputs. Anything else would allow the transaction id to change after
+    /// the cancel and refund transactions have been signed.
+    pub fn extract_signed(&self, signed: PartiallySignedTransaction) -> Result<Transaction> {
+        let signed_txid = signed.global.unsigned_tx.txid();
+        if signed_txid != self.txid() {
+            bail!(
+                "Signed lock transaction {} is not the agreed lock transaction {}",
+                signed_txid,
+                self.txid()
+            )
+        }
+
+        for (index, input) in signed.inputs.iter().enumerate() {
+            let has_witness = input
+                .final_script_witness
+                .as_ref()
+                .map_or(false, |witness| !witness.is_empty());
+            let has_script_sig = input
+                .final_script_sig
+                .as_ref()
+                .map_or(false, |script_sig| !script_sig.is_empty());
+
+            if !has_witness {
+                bail!("Input {} of the PSBT is not signed", index)
+            }
+            if has_script_sig {
+                bail!(
+                    "Input {} of the PSBT is not a native segwit input, its transaction id could be malleated",
+                    index
+                )
+            }
+        }
+
+        Ok(signed.extract_tx())
+    }
+
     pub fn lock_amount(&self) -> Amount {
         Amount::from_sat(self.inner.clone().extract_tx().output[self.lock_output_vout()].value)
     }
@@ -179,6 +257,153 @@ impl Watchable for TxLock {
     }
 }
 
+/// A lock transaction funded by an external wallet.
+///
+/// The shared output is only known during the swap setup, so the external
+/// wallet funds a template that pays to a placeholder instead. Funding
+/// happens before the swap setup and signing after it, which leaves the
+/// external wallet as much time as it needs for either step.
+#[derive(Clone, Debug)]
+pub struct ExternallyFundedTxLock {
+    psbt: PartiallySignedTransaction,
+    placeholder: Script,
+}
+
+impl ExternallyFundedTxLock {
+    /// Creates the PSBT an external wallet has to fund.
+    ///
+    /// The PSBT has no inputs and a single output paying the specified
+    /// amount to the placeholder; inputs, change and fees are up to the
+    /// funding wallet.
+    pub fn template(amount: Amount, placeholder: &Address) -> Result<PartiallySignedTransaction> {
+        let transaction = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: amount.as_sat(),
+                script_pubkey: placeholder.script_pubkey(),
+            }],
+        };
+
+        PartiallySignedTransaction::from_unsigned_tx(transaction)
+            .context("Failed to create PSBT for lock transaction")
+    }
+
+    /// Validates the template after it was funded by the external wallet.
+    ///
+    /// All inputs must spend native segwit outputs and have to specify the
+    /// output they spend, which we need to know the fee.
+    pub fn new(
+        psbt: PartiallySignedTransaction,
+        placeholder: &Address,
+        btc: Amount,
+    ) -> Result<Self> {
+        if psbt.inputs.is_empty() {
+            bail!("PSBT does not spend any inputs")
+        }
+
+        for (index, input) in psbt.inputs.iter().enumerate() {
+            let spent_output = input.witness_utxo.as_ref().with_context(|| {
+                format!(
+                    "Input {} of the PSBT does not specify the output it spends",
+                    index
+                )
+            })?;
+
+            if !spent_output.script_pubkey.is_witness_program() {
+                bail!(
+                    "Input {} of the PSBT is not a native segwit input, its transaction id could be malleated",
+                    index
+                )
+            }
+        }
+
+        let placeholder = placeholder.script_pubkey();
+        let pays_placeholder = psbt
+            .global
+            .unsigned_tx
+            .output
+            .iter()
+            .filter(|output| output.script_pubkey == placeholder)
+            .collect::<Vec<_>>();
+
+        match pays_placeholder.as_slice() {
+            [output] if output.value == btc.as_sat() => {}
+            [_] => bail!("Funded lock transaction does not pay the right amount"),
+            _ => bail!("Funded lock transaction has to pay to the placeholder exactly once"),
+        }
+
+        let funded = Self { psbt, placeholder };
+        funded.fee()?;
+
+        Ok(funded)
+    }
+
+    /// The fee paid by the lock transaction.
+    pub fn fee(&self) -> Result<Amount> {
+        let inputs = self
+            .psbt
+            .inputs
+            .iter()
+            .filter_map(|input| input.witness_utxo.as_ref())
+            .map(|output| output.value)
+            .sum::<u64>();
+        let outputs = self
+            .psbt
+            .global
+            .unsigned_tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<u64>();
+
+        inputs
+            .checked_sub(outputs)
+            .map(Amount::from_sat)
+            .context("Funded lock transaction spends more than its inputs")
+    }
+
+    /// The weight of the lock transaction once signed, assuming that all
+    /// inputs are P2WPKH.
+    pub fn estimated_weight(&self) -> usize {
+        let tx = &self.psbt.global.unsigned_tx;
+        let segwit_marker_and_flag = 2;
+        let shared_output_growth = SCRIPT_SIZE.saturating_sub(self.placeholder.len()) * 4;
+
+        tx.get_weight()
+            + segwit_marker_and_flag
+            + tx.input.len() * P2WPKH_WITNESS_WEIGHT
+            + shared_output_growth
+    }
+
+    /// Fails if the fee chosen by the external wallet is far off from our
+    /// own estimate for a transaction of [`Self::estimated_weight`].
+    ///
+    /// A fee that is too low can keep the lock transaction from confirming
+    /// in time, a fee that is too high is most likely a mistake.
+    pub fn ensure_fee_close_to(&self, estimate: Amount) -> Result<()> {
+        let fee = self.fee()?;
+
+        if fee * EXTERNAL_FEE_TOLERANCE < estimate {
+            bail!(
+                "Fee of {} for the lock transaction is too low, expected about {}",
+                fee,
+                estimate
+            )
+        }
+        if fee > estimate * EXTERNAL_FEE_TOLERANCE {
+            bail!(
+                "Fee of {} for the lock transaction is too high, expected about {}",
+                fee,
+                estimate
+            )
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,6 +466,121 @@ mod tests {
         result.expect_err("PSBT to be invalid");
     }
 
+    #[tokio::test]
+    async fn given_externally_funded_psbt_then_signed_lock_has_agreed_txid() {
+        let (A, B) = alice_and_bob();
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+        let placeholder = wallet.new_address().await.unwrap();
+
+        let funded = external_wallet_fund(
+            ExternallyFundedTxLock::template(agreed_amount, &placeholder).unwrap(),
+            wallet.new_address().await.unwrap().script_pubkey(),
+        );
+        let funded = ExternallyFundedTxLock::new(funded, &placeholder, agreed_amount).unwrap();
+        let tx_lock = TxLock::from_externally_funded(funded, A, B, agreed_amount).unwrap();
+
+        let signed = external_wallet_sign(tx_lock.clone().into());
+        let signed_tx = tx_lock.extract_signed(signed).unwrap();
+
+        assert_eq!(signed_tx.txid(), tx_lock.txid());
+        assert_eq!(tx_lock.lock_amount(), agreed_amount);
+    }
+
+    #[tokio::test]
+    async fn given_signed_psbt_of_different_transaction_then_fails() {
+        let (A, B) = alice_and_bob();
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+        let placeholder = wallet.new_address().await.unwrap();
+
+        let funded = external_wallet_fund(
+            ExternallyFundedTxLock::template(agreed_amount, &placeholder).unwrap(),
+            wallet.new_address().await.unwrap().script_pubkey(),
+        );
+        let funded = ExternallyFundedTxLock::new(funded, &placeholder, agreed_amount).unwrap();
+        let tx_lock = TxLock::from_externally_funded(funded, A, B, agreed_amount).unwrap();
+
+        let mut signed = external_wallet_sign(tx_lock.clone().into());
+        signed.global.unsigned_tx.input[0].sequence = 0;
+        let result = tx_lock.extract_signed(signed);
+
+        result.expect_err("signed transaction to be rejected");
+    }
+
+    #[tokio::test]
+    async fn given_unsigned_external_psbt_then_fails() {
+        let (A, B) = alice_and_bob();
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+        let placeholder = wallet.new_address().await.unwrap();
+
+        let funded = external_wallet_fund(
+            ExternallyFundedTxLock::template(agreed_amount, &placeholder).unwrap(),
+            wallet.new_address().await.unwrap().script_pubkey(),
+        );
+        let funded = ExternallyFundedTxLock::new(funded, &placeholder, agreed_amount).unwrap();
+        let tx_lock = TxLock::from_externally_funded(funded, A, B, agreed_amount).unwrap();
+
+        let result = tx_lock.extract_signed(tx_lock.clone().into());
+
+        result.expect_err("unsigned transaction to be rejected");
+    }
+
+    #[tokio::test]
+    async fn given_external_input_spending_non_segwit_output_then_fails() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+        let placeholder = wallet.new_address().await.unwrap();
+
+        let funded = external_wallet_fund(
+            ExternallyFundedTxLock::template(agreed_amount, &placeholder).unwrap(),
+            Script::from(vec![0u8; 25]),
+        );
+        let result = ExternallyFundedTxLock::new(funded, &placeholder, agreed_amount);
+
+        result.expect_err("PSBT to be invalid");
+    }
+
+    #[tokio::test]
+    async fn given_external_input_without_spent_output_then_fails() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+        let placeholder = wallet.new_address().await.unwrap();
+
+        let mut funded = external_wallet_fund(
+            ExternallyFundedTxLock::template(agreed_amount, &placeholder).unwrap(),
+            wallet.new_address().await.unwrap().script_pubkey(),
+        );
+        funded.inputs[0].witness_utxo = None;
+        let result = ExternallyFundedTxLock::new(funded, &placeholder, agreed_amount);
+
+        result.expect_err("PSBT to be invalid");
+    }
+
+    #[tokio::test]
+    async fn given_external_fee_far_off_estimate_then_fails() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let agreed_amount = Amount::from_sat(10000);
+        let placeholder = wallet.new_address().await.unwrap();
+
+        let funded = external_wallet_fund(
+            ExternallyFundedTxLock::template(agreed_amount, &placeholder).unwrap(),
+            wallet.new_address().await.unwrap().script_pubkey(),
+        );
+        let funded = ExternallyFundedTxLock::new(funded, &placeholder, agreed_amount).unwrap();
+        let fee = funded.fee().unwrap();
+
+        assert_eq!(fee, Amount::from_sat(EXTERNAL_FUNDING_FEE));
+        funded.ensure_fee_close_to(fee).unwrap();
+        funded
+            .ensure_fee_close_to(fee * 4)
+            .expect_err("fee to be too low");
+        funded
+            .ensure_fee_close_to(Amount::from_sat(fee.as_sat() / 4))
+            .expect_err("fee to be too high");
+    }
+
     proptest::proptest! {
         #[test]
         fn estimated_tx_lock_script_size_never_changes(a in crate::proptest::ecdsa_fun::point(), b in crate::proptest::ecdsa_fun::point()) {
@@ -262,12 +602,46 @@ mod tests {
         amount: Amount,
     ) -> PartiallySignedTransaction {
         let change = wallet.new_address().await.unwrap();
-        TxLock::new(&wallet, amount, A, B, change)
+        TxLock::new(&wallet, amount, A, B, change, &CoinControl::default())
             .await
             .unwrap()
             .into()
     }
 
+    const EXTERNAL_FUNDING_FEE: u64 = 1_000;
+
+    /// Helper function that mimics an external wallet funding the template
+    /// with a single input spending an output with the given script.
+    fn external_wallet_fund(
+        mut psbt: PartiallySignedTransaction,
+        spent_script: Script,
+    ) -> PartiallySignedTransaction {
+        let value = psbt.global.unsigned_tx.output[0].value + EXTERNAL_FUNDING_FEE;
+
+        psbt.global.unsigned_tx.input.push(TxIn {
+            previous_output: OutPoint::new(Txid::default(), 0),
+            script_sig: Script::new(),
+            sequence: 0xFFFF_FFFF,
+            witness: Vec::new(),
+        });
+        psbt.inputs.push(Default::default());
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value,
+            script_pubkey: spent_script,
+        });
+
+        psbt
+    }
+
+    /// Helper function that mimics an external wallet finalizing all inputs.
+    fn external_wallet_sign(mut psbt: PartiallySignedTransaction) -> PartiallySignedTransaction {
+        for input in psbt.inputs.iter_mut() {
+            input.final_script_witness = Some(vec![vec![0u8; 72], vec![2u8; 33]]);
+        }
+
+        psbt
+    }
+
     fn alice_and_bob() -> (PublicKey, PublicKey) {
         (PublicKey::random(), PublicKey::random())
     }
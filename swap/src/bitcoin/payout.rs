@@ -0,0 +1,113 @@
+use crate::bitcoin::wallet::SLED_TREE_NAME;
+use crate::bitcoin::{Address, Network};
+use ::bitcoin::util::bip32::ExtendedPubKey;
+use anyhow::{bail, Context, Result};
+use bdk::database::BatchDatabase;
+use bdk::wallet::AddressIndex;
+use bdk::KeychainKind;
+use std::path::Path;
+use std::str::FromStr;
+use tokio::sync::Mutex;
+
+/// Watch-only wallet the redeem and punish proceeds are paid to.
+///
+/// It only ever derives addresses; the keys to spend from it are kept
+/// elsewhere, e.g. on a hardware signer.
+pub struct PayoutWallet<D = bdk::sled::Tree> {
+    wallet: Mutex<bdk::Wallet<(), D>>,
+}
+
+impl PayoutWallet {
+    /// Opens the payout wallet for the given descriptor.
+    ///
+    /// Besides a full output descriptor, a bare extended public key is
+    /// accepted and taken as `wpkh(<xpub>/0/*)`.
+    pub fn new(descriptor: &str, network: Network, wallet_dir: &Path) -> Result<Self> {
+        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+
+        Self::with_database(descriptor, network, db)
+    }
+}
+
+impl<D> PayoutWallet<D>
+where
+    D: BatchDatabase,
+{
+    fn with_database(descriptor: &str, network: Network, db: D) -> Result<Self> {
+        let descriptor = match ExtendedPubKey::from_str(descriptor) {
+            Ok(xpub) => format!("wpkh({}/0/*)", xpub),
+            Err(_) => descriptor.to_owned(),
+        };
+
+        let wallet = bdk::Wallet::new_offline(descriptor.as_str(), None, network, db)
+            .context("Invalid payout descriptor")?;
+
+        if !wallet.get_signers(KeychainKind::External).ids().is_empty() {
+            bail!("Payout descriptor must not contain private keys")
+        }
+
+        Ok(Self {
+            wallet: Mutex::new(wallet),
+        })
+    }
+
+    pub async fn new_address(&self) -> Result<Address> {
+        let address = self
+            .wallet
+            .lock()
+            .await
+            .get_address(AddressIndex::New)
+            .context("Failed to get new payout address")?
+            .address;
+
+        Ok(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::database::MemoryDatabase;
+
+    const XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+    const XPRV: &str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+
+    #[tokio::test]
+    async fn given_xpub_then_derives_fresh_addresses() {
+        let wallet =
+            PayoutWallet::with_database(XPUB, Network::Bitcoin, MemoryDatabase::new()).unwrap();
+
+        let first = wallet.new_address().await.unwrap();
+        let second = wallet.new_address().await.unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn xpub_is_taken_as_wpkh_descriptor() {
+        let from_xpub =
+            PayoutWallet::with_database(XPUB, Network::Bitcoin, MemoryDatabase::new()).unwrap();
+        let from_descriptor = PayoutWallet::with_database(
+            &format!("wpkh({}/0/*)", XPUB),
+            Network::Bitcoin,
+            MemoryDatabase::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            from_xpub.new_address().await.unwrap(),
+            from_descriptor.new_address().await.unwrap()
+        );
+    }
+
+    #[test]
+    fn given_private_key_then_fails() {
+        let result = PayoutWallet::with_database(
+            &format!("wpkh({}/0/*)", XPRV),
+            Network::Bitcoin,
+            MemoryDatabase::new(),
+        );
+
+        assert!(result.is_err());
+    }
+}
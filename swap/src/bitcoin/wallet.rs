@@ -1,10 +1,15 @@
+use crate::bitcoin::coin_control::CoinControl;
+use crate::bitcoin::fee_estimation::{self, FeeEstimator, FeeStrategy, TxKind};
+use crate::bitcoin::history::{HistoryEntry, Labels};
 use crate::bitcoin::timelocks::BlockHeight;
 use crate::bitcoin::{Address, Amount, Transaction};
 use crate::env;
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::Txid;
-use anyhow::{bail, Context, Result};
-use bdk::blockchain::{noop_progress, Blockchain, ElectrumBlockchain};
+use anyhow::{anyhow, bail, Context, Result};
+use bdk::blockchain::{
+    noop_progress, Blockchain, ConfigurableBlockchain, ElectrumBlockchain, ElectrumBlockchainConfig,
+};
 use bdk::database::BatchDatabase;
 use bdk::descriptor::Segwitv0;
 use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
@@ -25,7 +30,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{watch, Mutex};
 
-const SLED_TREE_NAME: &str = "default_tree";
+pub(in crate::bitcoin) const SLED_TREE_NAME: &str = "default_tree";
 
 /// Assuming we add a spread of 3% we don't want to pay more than 3% of the
 /// amount for tx fees.
@@ -33,12 +38,20 @@ const MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
 const MAX_ABSOLUTE_TX_FEE: Decimal = dec!(100_000);
 const DUST_AMOUNT: u64 = 546;
 
+/// Requests to the Electrum server are retried this many times.
+const ELECTRUM_RETRY: u8 = 5;
+/// Seconds to wait for a response of the Electrum server.
+const ELECTRUM_TIMEOUT_SECS: u8 = 30;
+
+/// Consecutive unused addresses after which a rescan stops looking for funds.
+pub const DEFAULT_RESCAN_GAP_LIMIT: &str = "100";
+
 pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
     client: Arc<Mutex<C>>,
     wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
     finality_confirmations: u32,
     network: Network,
-    target_block: usize,
+    fee_strategy: FeeStrategy,
 }
 
 impl Wallet {
@@ -47,26 +60,124 @@ impl Wallet {
         wallet_dir: &Path,
         key: impl DerivableKey<Segwitv0> + Clone,
         env_config: env::Config,
-        target_block: usize,
+        fee_strategy: FeeStrategy,
+    ) -> Result<Self> {
+        let client = bdk::electrum_client::Client::from_config(
+            electrum_rpc_url.as_str(),
+            electrum_config()?,
+        )
+        .context("Failed to initialize Electrum RPC client")?;
+
+        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+
+        Self::from_parts(
+            electrum_rpc_url,
+            ElectrumBlockchain::from(client),
+            db,
+            key,
+            env_config,
+            fee_strategy,
+        )
+    }
+
+    /// Discards everything the wallet learned from the blockchain and syncs it
+    /// from scratch.
+    ///
+    /// Addresses are discovered until `gap_limit` consecutive ones are unused.
+    /// Electrum serves the complete history of each address, hence the rescan
+    /// always covers the whole chain. The `birthday_height` is checked against
+    /// the chain and the transactions found, so a wrong birthday is noticed
+    /// instead of silently hiding funds.
+    pub async fn rescan(
+        electrum_rpc_url: Url,
+        wallet_dir: &Path,
+        key: impl DerivableKey<Segwitv0> + Clone,
+        env_config: env::Config,
+        fee_strategy: FeeStrategy,
+        gap_limit: u32,
+        birthday_height: Option<u32>,
     ) -> Result<Self> {
-        let config = bdk::electrum_client::ConfigBuilder::default()
-            .retry(5)
-            .build();
-        let client = bdk::electrum_client::Client::from_config(electrum_rpc_url.as_str(), config)
-            .context("Failed to initialize Electrum RPC client")?;
+        let blockchain = ElectrumBlockchain::from_config(&ElectrumBlockchainConfig {
+            url: electrum_rpc_url.to_string(),
+            socks5: None,
+            retry: ELECTRUM_RETRY,
+            timeout: Some(ELECTRUM_TIMEOUT_SECS),
+            stop_gap: gap_limit as usize,
+        })
+        .context("Failed to initialize Electrum RPC client")?;
 
         let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+        db.clear()
+            .context("Failed to clear Bitcoin wallet database")?;
+
+        let wallet = Self::from_parts(
+            electrum_rpc_url,
+            blockchain,
+            db,
+            key,
+            env_config,
+            fee_strategy,
+        )?;
+
+        if let Some(birthday_height) = birthday_height {
+            let latest_block_height = u32::from(wallet.client.lock().await.latest_block_height);
+
+            if birthday_height > latest_block_height {
+                bail!(
+                    "Birthday height {} is above the latest block height {}",
+                    birthday_height,
+                    latest_block_height
+                );
+            }
+        }
+
+        let wallet_lock = wallet.wallet.lock().await;
+        wallet_lock
+            .sync(noop_progress(), Some(gap_limit))
+            .context("Failed to rescan Bitcoin wallet")?;
+
+        if let Some(birthday_height) = birthday_height {
+            let before_birthday = wallet_lock
+                .list_transactions(false)?
+                .iter()
+                .filter_map(|tx| tx.confirmation_time.as_ref())
+                .filter(|time| time.height < birthday_height)
+                .count();
+
+            if before_birthday > 0 {
+                tracing::warn!(
+                    %birthday_height,
+                    transactions = before_birthday,
+                    "Found transactions confirmed before the birthday height, the wallet is older than its birthday"
+                );
+            }
+        }
+        drop(wallet_lock);
+
+        Ok(wallet)
+    }
 
+    fn from_parts(
+        electrum_rpc_url: Url,
+        blockchain: ElectrumBlockchain,
+        db: bdk::sled::Tree,
+        key: impl DerivableKey<Segwitv0> + Clone,
+        env_config: env::Config,
+        fee_strategy: FeeStrategy,
+    ) -> Result<Self> {
         let wallet = bdk::Wallet::new(
             bdk::template::Bip84(key.clone(), KeychainKind::External),
             Some(bdk::template::Bip84(key, KeychainKind::Internal)),
             env_config.bitcoin_network,
             db,
-            ElectrumBlockchain::from(client),
+            blockchain,
         )?;
 
-        let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
+        let electrum = bdk::electrum_client::Client::from_config(
+            electrum_rpc_url.as_str(),
+            electrum_config()?,
+        )
+        .context("Failed to initialize Electrum RPC client")?;
 
         let network = wallet.network();
 
@@ -78,7 +189,7 @@ impl Wallet {
             wallet: Arc::new(Mutex::new(wallet)),
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
-            target_block,
+            fee_strategy,
         })
     }
 
@@ -125,6 +236,55 @@ impl Wallet {
         self.client.lock().await.status_of_script(tx)
     }
 
+    /// Returns the index and status of the first of the given transactions
+    /// that has been seen on the network.
+    ///
+    /// Useful for watching a set of mutually exclusive transactions, e.g. the
+    /// same transaction pre-signed at different fee levels.
+    pub async fn status_of_first_seen<T>(&self, txs: &[T]) -> Result<Option<(usize, ScriptStatus)>>
+    where
+        T: Watchable,
+    {
+        let mut client = self.client.lock().await;
+
+        for (index, tx) in txs.iter().enumerate() {
+            let status = client.status_of_script(tx)?;
+
+            if status.has_been_seen() {
+                return Ok(Some((index, status)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Waits until one of the given transactions has been seen on the network
+    /// and returns its index and status.
+    ///
+    /// Electrum may not know about a transaction right after it was
+    /// broadcast, hence we keep asking with an exponential backoff before
+    /// giving up.
+    pub async fn wait_until_first_seen<T>(&self, txs: &[T]) -> Result<(usize, ScriptStatus)>
+    where
+        T: Watchable,
+    {
+        let backoff = backoff::ExponentialBackoff {
+            max_elapsed_time: Some(Duration::from_secs(10 * 60)),
+            ..backoff::ExponentialBackoff::default()
+        };
+
+        backoff::future::retry(backoff, || async move {
+            match self.status_of_first_seen(txs).await {
+                Ok(Some(seen)) => Ok(seen),
+                Ok(None) => Err(backoff::Error::Transient(anyhow!(
+                    "None of the transactions has been seen yet"
+                ))),
+                Err(error) => Err(backoff::Error::Transient(error)),
+            }
+        })
+        .await
+    }
+
     pub async fn subscribe_to(&self, tx: impl Watchable + Send + 'static) -> Subscription {
         let txid = tx.id();
         let script = tx.script();
@@ -192,6 +352,14 @@ impl Wallet {
     }
 }
 
+/// Settings of every connection to the Electrum server, rescans use the same.
+fn electrum_config() -> Result<bdk::electrum_client::Config> {
+    Ok(bdk::electrum_client::ConfigBuilder::default()
+        .retry(ELECTRUM_RETRY)
+        .timeout(Some(ELECTRUM_TIMEOUT_SECS))?
+        .build())
+}
+
 fn print_status_change(txid: Txid, old: Option<ScriptStatus>, new: ScriptStatus) -> ScriptStatus {
     match (old, new) {
         (None, new_status) => {
@@ -331,15 +499,52 @@ where
         Ok(Amount::from_sat(fees))
     }
 
+    /// All transactions of the wallet, labelled by what they were made for.
+    ///
+    /// Unconfirmed transactions come last.
+    pub async fn history(&self, labels: &Labels) -> Result<Vec<HistoryEntry>> {
+        let transactions = self
+            .wallet
+            .lock()
+            .await
+            .list_transactions(false)
+            .context("Failed to list Bitcoin transactions")?;
+
+        let mut history = transactions
+            .into_iter()
+            .map(|tx| {
+                let received = Amount::from_sat(tx.received);
+                let sent = Amount::from_sat(tx.sent);
+                let (label, swap_id) = labels.get(tx.txid, received, sent);
+
+                HistoryEntry {
+                    txid: tx.txid,
+                    label,
+                    swap_id,
+                    received,
+                    sent,
+                    fee: tx.fee.map(Amount::from_sat),
+                    confirmation_height: tx.confirmation_time.map(|time| time.height),
+                }
+            })
+            .collect::<Vec<_>>();
+        history.sort_by_key(|entry| entry.confirmation_height.unwrap_or(u32::MAX));
+
+        Ok(history)
+    }
+
     /// Builds a partially signed transaction
     ///
     /// Ensures that the address script is at output index `0`
-    /// for the partially signed transaction.
+    /// for the partially signed transaction. Only outputs allowed by the
+    /// given coin control are spent.
     pub async fn send_to_address(
         &self,
         address: Address,
         amount: Amount,
         change_override: Option<Address>,
+        kind: TxKind,
+        coin_control: &CoinControl,
     ) -> Result<PartiallySignedTransaction> {
         if self.network != address.network {
             bail!("Cannot build PSBT because network of given address is {} but wallet is on network {}", address.network, self.network);
@@ -351,15 +556,25 @@ where
             }
         }
 
+        let fee_rate = self.fee_rate(kind).await?;
         let wallet = self.wallet.lock().await;
-        let client = self.client.lock().await;
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let selections = coin_control.select(wallet.list_unspent()?)?;
         let script = address.script_pubkey();
 
-        let mut tx_builder = wallet.build_tx();
-        tx_builder.add_recipient(script.clone(), amount.as_sat());
-        tx_builder.fee_rate(fee_rate);
-        let (psbt, _details) = tx_builder.finish()?;
+        // use the first selection that can fund the transaction
+        let mut result = Err(anyhow!("No outputs to spend"));
+        for selection in selections {
+            let mut tx_builder = wallet.build_tx();
+            tx_builder.add_recipient(script.clone(), amount.as_sat());
+            tx_builder.fee_rate(fee_rate);
+            tx_builder.unspendable(selection.unspendable);
+
+            result = tx_builder.finish().map_err(anyhow::Error::from);
+            if result.is_ok() {
+                break;
+            }
+        }
+        let (psbt, _details) = result?;
         let mut psbt: PartiallySignedTransaction = psbt;
 
         match psbt.global.unsigned_tx.output.as_mut_slice() {
@@ -395,57 +610,106 @@ where
     ///
     /// We define this as the maximum amount we can pay to a single output,
     /// already accounting for the fees we need to spend to get the
-    /// transaction confirmed.
-    pub async fn max_giveable(&self, locking_script_size: usize) -> Result<Amount> {
+    /// transaction confirmed. Only outputs allowed by the given coin control
+    /// are taken into account.
+    pub async fn max_giveable(
+        &self,
+        locking_script_size: usize,
+        kind: TxKind,
+        coin_control: &CoinControl,
+    ) -> Result<Amount> {
+        let fee_rate = self.fee_rate(kind).await?;
         let wallet = self.wallet.lock().await;
-        let balance = wallet.get_balance()?;
-        if balance < DUST_AMOUNT {
-            return Ok(Amount::ZERO);
-        }
-        let client = self.client.lock().await;
-        let min_relay_fee = client.min_relay_fee()?.as_sat();
+        let selections = coin_control.select(wallet.list_unspent()?)?;
 
-        if balance < min_relay_fee {
-            return Ok(Amount::ZERO);
-        }
-
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let mut max_giveable = Amount::ZERO;
+        for selection in selections {
+            let balance = selection.value.as_sat();
+            if balance < DUST_AMOUNT {
+                continue;
+            }
+            let min_relay_fee = self.client.lock().await.min_relay_fee()?.as_sat();
 
-        let mut tx_builder = wallet.build_tx();
+            if balance < min_relay_fee {
+                continue;
+            }
 
-        let dummy_script = Script::from(vec![0u8; locking_script_size]);
-        tx_builder.drain_to(dummy_script);
-        tx_builder.fee_rate(fee_rate);
-        tx_builder.drain_wallet();
+            let mut tx_builder = wallet.build_tx();
+
+            let dummy_script = Script::from(vec![0u8; locking_script_size]);
+            tx_builder.drain_to(dummy_script);
+            tx_builder.fee_rate(fee_rate);
+            tx_builder.drain_wallet();
+            tx_builder.unspendable(selection.unspendable);
+
+            let response = tx_builder.finish();
+            let giveable = match response {
+                Ok((_, details)) => {
+                    let giveable = details.sent
+                        - details
+                            .fee
+                            .expect("fees are always present with Electrum backend");
+                    Amount::from_sat(giveable)
+                }
+                Err(bdk::Error::InsufficientFunds { .. }) => Amount::ZERO,
+                Err(e) => bail!("Failed to build transaction. {:#}", e),
+            };
 
-        let response = tx_builder.finish();
-        match response {
-            Ok((_, details)) => {
-                let max_giveable = details.sent
-                    - details
-                        .fee
-                        .expect("fees are always present with Electrum backend");
-                Ok(Amount::from_sat(max_giveable))
-            }
-            Err(bdk::Error::InsufficientFunds { .. }) => Ok(Amount::ZERO),
-            Err(e) => bail!("Failed to build transaction. {:#}", e),
+            max_giveable = max_giveable.max(giveable);
         }
+
+        Ok(max_giveable)
     }
 
-    /// Estimate total tx fee for a pre-defined target block based on the
-    /// transaction weight. The max fee cannot be more than MAX_PERCENTAGE_FEE
-    /// of amount
+    /// Estimate total tx fee for the target block configured for the given
+    /// kind of transaction based on the transaction weight. The max fee cannot
+    /// be more than MAX_PERCENTAGE_FEE of amount
     pub async fn estimate_fee(
         &self,
+        kind: TxKind,
         weight: usize,
         transfer_amount: bitcoin::Amount,
     ) -> Result<bitcoin::Amount> {
-        let client = self.client.lock().await;
-        let fee_rate = client.estimate_feerate(self.target_block)?;
-        let min_relay_fee = client.min_relay_fee()?;
+        let fee_rate = self.fee_rate(kind).await?;
+        let min_relay_fee = self.client.lock().await.min_relay_fee()?;
 
         estimate_fee(weight, transfer_amount, fee_rate, min_relay_fee)
     }
+
+    /// Estimates the fee rate for the given kind of transaction according to
+    /// the configured fee strategy.
+    ///
+    /// If an external estimator cannot be reached we fall back to the Electrum
+    /// server so that time-critical transactions can still be published.
+    async fn fee_rate(&self, kind: TxKind) -> Result<FeeRate> {
+        let target_block = self.fee_strategy.target_block(kind);
+
+        let estimate = match &self.fee_strategy.estimator {
+            FeeEstimator::Electrum => None,
+            FeeEstimator::BitcoinCore { rpc_url } => {
+                Some(fee_estimation::estimate_smart_fee(rpc_url, target_block).await)
+            }
+            FeeEstimator::Mempool { api_url } => {
+                Some(fee_estimation::mempool_recommended_fee(api_url, target_block).await)
+            }
+            FeeEstimator::Fixed { sat_per_vb } => Some(Ok(FeeRate::from_sat_per_vb(*sat_per_vb))),
+        };
+
+        let fee_rate = match estimate {
+            Some(Ok(fee_rate)) => fee_rate,
+            Some(Err(error)) => {
+                tracing::warn!(
+                    ?kind,
+                    "Failed to estimate fee rate, falling back to Electrum: {:#}",
+                    error
+                );
+                self.client.lock().await.estimate_feerate(target_block)?
+            }
+            None => self.client.lock().await.estimate_feerate(target_block)?,
+        };
+
+        Ok(self.fee_strategy.clamp(fee_rate))
+    }
 }
 
 fn estimate_fee(
@@ -650,7 +914,7 @@ impl WalletBuilder {
             wallet: Arc::new(Mutex::new(wallet)),
             finality_confirmations: 1,
             network: Network::Regtest,
-            target_block: 1,
+            fee_strategy: FeeStrategy::electrum(1),
         }
     }
 }
@@ -1107,7 +1371,10 @@ mod tests {
     #[tokio::test]
     async fn given_no_balance_returns_amount_0() {
         let wallet = WalletBuilder::new(0).with_fees(1.0, 1).build();
-        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let amount = wallet
+            .max_giveable(TxLock::script_size(), TxKind::Lock, &CoinControl::default())
+            .await
+            .unwrap();
 
         assert_eq!(amount, Amount::ZERO);
     }
@@ -1115,7 +1382,10 @@ mod tests {
     #[tokio::test]
     async fn given_balance_below_min_relay_fee_returns_amount_0() {
         let wallet = WalletBuilder::new(1000).with_fees(1.0, 1001).build();
-        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let amount = wallet
+            .max_giveable(TxLock::script_size(), TxKind::Lock, &CoinControl::default())
+            .await
+            .unwrap();
 
         assert_eq!(amount, Amount::ZERO);
     }
@@ -1123,7 +1393,10 @@ mod tests {
     #[tokio::test]
     async fn given_balance_above_relay_fee_returns_amount_greater_0() {
         let wallet = WalletBuilder::new(10_000).build();
-        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let amount = wallet
+            .max_giveable(TxLock::script_size(), TxKind::Lock, &CoinControl::default())
+            .await
+            .unwrap();
 
         assert!(amount.as_sat() > 0);
     }
@@ -1149,9 +1422,16 @@ mod tests {
         for amount in above_dust..(balance - (above_dust - 1)) {
             let (A, B) = (PublicKey::random(), PublicKey::random());
             let change = wallet.new_address().await.unwrap();
-            let txlock = TxLock::new(&wallet, bitcoin::Amount::from_sat(amount), A, B, change)
-                .await
-                .unwrap();
+            let txlock = TxLock::new(
+                &wallet,
+                bitcoin::Amount::from_sat(amount),
+                A,
+                B,
+                change,
+                &CoinControl::default(),
+            )
+            .await
+            .unwrap();
             let txlock_output = txlock.script_pubkey();
 
             let tx = wallet.sign_and_finalize(txlock.into()).await.unwrap();
@@ -1177,6 +1457,8 @@ mod tests {
                 wallet.new_address().await.unwrap(),
                 Amount::from_sat(10_000),
                 Some(custom_change.clone()),
+                TxKind::Withdraw,
+                &CoinControl::default(),
             )
             .await
             .unwrap();
@@ -1233,8 +1515,8 @@ DEBUG swap::bitcoin::wallet: Bitcoin transaction status changed txid=00000000000
             tokio::runtime::Runtime::new().unwrap().block_on(async move {
                 let wallet = WalletBuilder::new(funding_amount as u64).with_key(key).with_num_utxos(num_utxos).with_fees(sats_per_vb, 1000).build();
 
-                let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
-                let psbt: PartiallySignedTransaction = TxLock::new(&wallet, amount, PublicKey::from(alice), PublicKey::from(bob), wallet.new_address().await.unwrap()).await.unwrap().into();
+                let amount = wallet.max_giveable(TxLock::script_size(), TxKind::Lock, &CoinControl::default()).await.unwrap();
+                let psbt: PartiallySignedTransaction = TxLock::new(&wallet, amount, PublicKey::from(alice), PublicKey::from(bob), wallet.new_address().await.unwrap(), &CoinControl::default()).await.unwrap().into();
                 let result = wallet.sign_and_finalize(psbt).await;
 
                 result.expect("transaction to be signed");
@@ -2,16 +2,22 @@ mod behaviour;
 pub mod cancel;
 pub mod command;
 mod event_loop;
+pub mod external_funding;
 mod list_sellers;
+pub mod profile;
 pub mod refund;
+pub mod seller_cache;
+mod seller_selection;
 pub mod tracing;
 pub mod transport;
 
 pub use behaviour::{Behaviour, OutEvent};
 pub use cancel::cancel;
 pub use event_loop::{EventLoop, EventLoopHandle};
+pub use external_funding::external_funding;
 pub use list_sellers::{list_sellers, Seller, Status as SellerStatus};
 pub use refund::refund;
+pub use seller_selection::{rank_sellers, seller_reliability, Reliability};
 
 #[cfg(test)]
 mod tests {
@@ -35,14 +41,58 @@ mod tests {
     async fn list_sellers_should_report_all_registered_asbs_with_a_quote() {
         let namespace = XmrBtcNamespace::Mainnet;
         let (rendezvous_address, rendezvous_peer_id) = setup_rendezvous_point().await;
-        let expected_seller_1 =
-            setup_asb(rendezvous_peer_id, rendezvous_address.clone(), namespace).await;
-        let expected_seller_2 =
-            setup_asb(rendezvous_peer_id, rendezvous_address.clone(), namespace).await;
+        let expected_seller_1 = setup_asb(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace,
+        )
+        .await;
+        let expected_seller_2 = setup_asb(
+            vec![(rendezvous_peer_id, rendezvous_address.clone())],
+            namespace,
+        )
+        .await;
+
+        let list_sellers = list_sellers(
+            vec![(rendezvous_peer_id, rendezvous_address)],
+            namespace,
+            0,
+            identity::Keypair::generate_ed25519(),
+        );
+        let sellers = tokio::time::timeout(Duration::from_secs(15), list_sellers)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            without_latency(sellers),
+            HashSet::<Seller>::from_iter([expected_seller_1, expected_seller_2])
+        )
+    }
+
+    #[tokio::test]
+    async fn list_sellers_should_deduplicate_asbs_registered_with_several_rendezvous_points() {
+        let namespace = XmrBtcNamespace::Mainnet;
+        let (rendezvous_address_1, rendezvous_peer_id_1) = setup_rendezvous_point().await;
+        let (rendezvous_address_2, rendezvous_peer_id_2) = setup_rendezvous_point().await;
+        let expected_seller_1 = setup_asb(
+            vec![
+                (rendezvous_peer_id_1, rendezvous_address_1.clone()),
+                (rendezvous_peer_id_2, rendezvous_address_2.clone()),
+            ],
+            namespace,
+        )
+        .await;
+        let expected_seller_2 = setup_asb(
+            vec![(rendezvous_peer_id_2, rendezvous_address_2.clone())],
+            namespace,
+        )
+        .await;
 
         let list_sellers = list_sellers(
-            rendezvous_peer_id,
-            rendezvous_address,
+            vec![
+                (rendezvous_peer_id_1, rendezvous_address_1),
+                (rendezvous_peer_id_2, rendezvous_address_2),
+            ],
             namespace,
             0,
             identity::Keypair::generate_ed25519(),
@@ -52,12 +102,31 @@ mod tests {
             .unwrap()
             .unwrap();
 
+        assert_eq!(sellers.len(), 2);
         assert_eq!(
-            HashSet::<Seller>::from_iter(sellers),
+            without_latency(sellers),
             HashSet::<Seller>::from_iter([expected_seller_1, expected_seller_2])
         )
     }
 
+    /// Asserts that every online seller's latency was measured and drops it, it
+    /// differs between runs.
+    fn without_latency(sellers: Vec<Seller>) -> HashSet<Seller> {
+        sellers
+            .into_iter()
+            .map(|seller| {
+                if let Status::Online(_) = seller.status {
+                    assert!(seller.latency.is_some());
+                }
+
+                Seller {
+                    latency: None,
+                    ..seller
+                }
+            })
+            .collect()
+    }
+
     async fn setup_rendezvous_point() -> (Multiaddr, PeerId) {
         let mut rendezvous_node = new_swarm(|_, _| RendezvousPointBehaviour::default());
         let rendezvous_address = rendezvous_node.listen_on_tcp_localhost().await;
@@ -73,8 +142,7 @@ mod tests {
     }
 
     async fn setup_asb(
-        rendezvous_peer_id: PeerId,
-        rendezvous_address: Multiaddr,
+        rendezvous_points: Vec<(PeerId, Multiaddr)>,
         namespace: XmrBtcNamespace,
     ) -> Seller {
         let static_quote = BidQuote {
@@ -83,18 +151,20 @@ mod tests {
             max_quantity: bitcoin::Amount::from_sat(9001),
         };
 
+        let expected_registrations = rendezvous_points.len();
+        let rendezvous_nodes = rendezvous_points
+            .into_iter()
+            .map(|(peer_id, address)| {
+                asb::rendezous::RendezvousNode::new(address, peer_id, namespace, None)
+            })
+            .collect();
+
         let mut asb = new_swarm(|_, identity| StaticQuoteAsbBehaviour {
-            rendezvous: asb::rendezous::Behaviour::new(
-                identity,
-                rendezvous_peer_id,
-                rendezvous_address,
-                namespace,
-                None,
-            ),
+            rendezvous: asb::rendezous::Behaviour::new(identity, rendezvous_nodes),
             ping: Default::default(),
             quote: quote::asb(),
             static_quote,
-            registered: false,
+            registrations: 0,
         });
 
         let asb_address = asb.listen_on_tcp_localhost().await;
@@ -104,7 +174,7 @@ mod tests {
 
         // avoid race condition where `list_sellers` tries to discover before we are
         // registered block this function until we are registered
-        while !asb.behaviour().registered {
+        while asb.behaviour().registrations < expected_registrations {
             asb.next().await;
         }
 
@@ -117,6 +187,7 @@ mod tests {
         Seller {
             multiaddr: asb_address.with(Protocol::P2p(asb_peer_id.into())),
             status: Status::Online(static_quote),
+            latency: None,
         }
     }
 
@@ -131,12 +202,12 @@ mod tests {
         #[behaviour(ignore)]
         static_quote: BidQuote,
         #[behaviour(ignore)]
-        registered: bool,
+        registrations: usize,
     }
     impl NetworkBehaviourEventProcess<rendezvous::client::Event> for StaticQuoteAsbBehaviour {
         fn inject_event(&mut self, event: rendezvous::client::Event) {
             if let rendezvous::client::Event::Registered { .. } = event {
-                self.registered = true;
+                self.registrations += 1;
             }
         }
     }
@@ -1,13 +1,13 @@
 use crate::network::quote::BidQuote;
 use crate::network::swap_setup::bob;
-use crate::network::{encrypted_signature, quote, redial, transfer_proof};
+use crate::network::{encrypted_signature, identify, quote, redial, transfer_proof};
 use crate::protocol::bob::State2;
 use crate::{bitcoin, env};
 use anyhow::{anyhow, Error, Result};
 use libp2p::core::Multiaddr;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
 use libp2p::request_response::{RequestId, ResponseChannel};
-use libp2p::{NetworkBehaviour, PeerId};
+use libp2p::{identity, NetworkBehaviour, PeerId};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -64,6 +64,7 @@ pub struct Behaviour {
     pub transfer_proof: transfer_proof::Behaviour,
     pub encrypted_signature: encrypted_signature::Behaviour,
     pub redial: redial::Behaviour,
+    pub identify: identify::Behaviour,
 
     /// Ping behaviour that ensures that the underlying network connection is
     /// still alive. If the ping fails a connection close event will be
@@ -76,6 +77,7 @@ impl Behaviour {
         alice: PeerId,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
+        identity: identity::PublicKey,
     ) -> Self {
         Self {
             quote: quote::cli(),
@@ -83,6 +85,7 @@ impl Behaviour {
             transfer_proof: transfer_proof::bob(),
             encrypted_signature: encrypted_signature::bob(),
             redial: redial::Behaviour::new(alice, Duration::from_secs(2)),
+            identify: identify::new(identity),
             ping: Ping::new(PingConfig::new().with_keep_alive(true)),
         }
     }
@@ -1,14 +1,20 @@
-use crate::bitcoin::Amount;
+use crate::bitcoin::{
+    Amount, CoinControl, FeeEstimator, FeeStrategy, TargetBlocks, DEFAULT_RESCAN_GAP_LIMIT,
+};
+use crate::cli::profile;
 use crate::env::GetConfig;
 use crate::fs::system_data_dir;
 use crate::network::rendezvous::XmrBtcNamespace;
+use crate::network::swap_setup::bob::PriceProtection;
 use crate::{env, judecoin};
 use anyhow::{bail, Context, Result};
-use bitcoin::{Address, AddressType};
+use bitcoin::{Address, AddressType, OutPoint};
 use libp2p::core::Multiaddr;
+use rust_decimal::Decimal;
 use serde::Serialize;
+use std::convert::TryFrom;
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use structopt::{clap, StructOpt};
 use url::Url;
@@ -27,6 +33,7 @@ const DEFAULT_BITCOIN_CONFIRMATION_TARGET: usize = 3;
 const DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET: usize = 1;
 
 const DEFAULT_TOR_SOCKS5_PORT: &str = "9050";
+const DEFAULT_TOR_CONTROL_PORT: &str = "9051";
 
 #[derive(Debug, PartialEq)]
 pub struct Arguments {
@@ -68,37 +75,73 @@ where
     let json = args.json;
     let is_testnet = args.testnet;
     let data = args.data;
+    let profile = args.profile;
+    let create_profile = args.create_profile;
 
     let arguments = match args.cmd {
         RawCommand::BuyXmr {
-            seller: Seller { seller },
+            seller: Seller {
+                seller,
+                rendezvous_point,
+            },
             bitcoin,
             bitcoin_change_address,
             judecoin,
             judecoin_receive_address,
             tor: Tor { tor_socks5_port },
+            onion_service,
+            coin_selection,
+            external_funding,
+            price_limits,
+            btc,
+            jude,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
                 bitcoin.apply_defaults(is_testnet)?;
+            let coin_control = CoinControl::from(coin_selection);
+            if external_funding.is_some() && coin_control != CoinControl::default() {
+                bail!("Coin selection cannot be combined with external funding")
+            }
+            let amount = match (btc, jude) {
+                (Some(btc), _) => Some(SwapAmount::Btc(btc)),
+                (None, Some(jude)) => Some(SwapAmount::Jude(jude)),
+                (None, None) => None,
+            };
+            if external_funding.is_some() && amount.is_some() {
+                bail!("A fixed swap amount cannot be combined with external funding")
+            }
             let judecoin_daemon_address = judecoin.apply_defaults(is_testnet);
             let judecoin_receive_address =
                 validate_judecoin_address(judecoin_receive_address, is_testnet)?;
             let bitcoin_change_address =
                 validate_bitcoin_address(bitcoin_change_address, is_testnet)?;
+            let seller = match seller {
+                Some(seller) => SellerSelection::Address(seller),
+                None if !rendezvous_point.is_empty() => SellerSelection::Best {
+                    rendezvous_point,
+                    namespace: rendezvous_namespace_from(is_testnet),
+                },
+                None => bail!("Either a seller or a rendezvous point is required"),
+            };
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
                 cmd: Command::BuyXmr {
                     seller,
                     bitcoin_electrum_rpc_url,
-                    bitcoin_target_block,
+                    bitcoin_fee_strategy,
                     bitcoin_change_address,
                     judecoin_receive_address,
                     judecoin_daemon_address,
                     tor_socks5_port,
+                    tor_control_port: onion_service.control_port(),
+                    coin_control,
+                    external_funding,
+                    price_protection: PriceProtection::from(price_limits),
+                    amount,
                 },
             }
         }
@@ -106,14 +149,14 @@ where
             env_config: env_config_from(is_testnet),
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
             cmd: Command::History,
         },
         RawCommand::Config => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
             cmd: Command::Config,
         },
         RawCommand::Balance {
@@ -122,18 +165,140 @@ where
             let bitcoin = Bitcoin {
                 bitcoin_electrum_rpc_url,
                 bitcoin_target_block: None,
+                bitcoind_rpc_url: None,
+                mempool_api_url: None,
+                bitcoin_fee_rate: None,
+                bitcoin_min_fee_rate: None,
+                bitcoin_max_fee_rate: None,
             };
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
                 cmd: Command::Balance {
                     bitcoin_electrum_rpc_url,
-                    bitcoin_target_block,
+                    bitcoin_fee_strategy,
+                },
+            }
+        }
+        RawCommand::BtcHistory {
+            bitcoin_electrum_rpc_url,
+        } => {
+            let bitcoin = Bitcoin {
+                bitcoin_electrum_rpc_url,
+                bitcoin_target_block: None,
+                bitcoind_rpc_url: None,
+                mempool_api_url: None,
+                bitcoin_fee_rate: None,
+                bitcoin_min_fee_rate: None,
+                bitcoin_max_fee_rate: None,
+            };
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
+                cmd: Command::BtcHistory {
+                    bitcoin_electrum_rpc_url,
+                    bitcoin_fee_strategy,
+                },
+            }
+        }
+        RawCommand::Profile(profile_cmd) => {
+            // managing profiles happens outside of any profile, so only the base directory
+            // matters
+            let base_dir = data::base_dir_from(data)?;
+            let data_dir = data::network_dir(&base_dir, is_testnet);
+
+            let cmd = match profile_cmd {
+                RawProfileCommand::List => Command::ListProfiles { base_dir },
+                RawProfileCommand::Create { name } => Command::CreateProfile { base_dir, name },
+                RawProfileCommand::Delete { name, force } => Command::DeleteProfile {
+                    base_dir,
+                    name,
+                    force,
+                },
+            };
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                data_dir,
+                cmd,
+            }
+        }
+        RawCommand::Seed(RawSeedCommand::Export) => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
+            cmd: Command::ExportSeed,
+        },
+        RawCommand::Seed(RawSeedCommand::Restore {
+            bitcoin_electrum_rpc_url,
+            gap_limit,
+            birthday_height,
+        }) => {
+            let bitcoin = Bitcoin {
+                bitcoin_electrum_rpc_url,
+                bitcoin_target_block: None,
+                bitcoind_rpc_url: None,
+                mempool_api_url: None,
+                bitcoin_fee_rate: None,
+                bitcoin_min_fee_rate: None,
+                bitcoin_max_fee_rate: None,
+            };
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
+                cmd: Command::RestoreSeed {
+                    bitcoin_electrum_rpc_url,
+                    bitcoin_fee_strategy,
+                    gap_limit,
+                    birthday_height,
+                },
+            }
+        }
+        RawCommand::Rescan {
+            bitcoin_electrum_rpc_url,
+            gap_limit,
+            birthday_height,
+        } => {
+            let bitcoin = Bitcoin {
+                bitcoin_electrum_rpc_url,
+                bitcoin_target_block: None,
+                bitcoind_rpc_url: None,
+                mempool_api_url: None,
+                bitcoin_fee_rate: None,
+                bitcoin_min_fee_rate: None,
+                bitcoin_max_fee_rate: None,
+            };
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
+                cmd: Command::Rescan {
+                    bitcoin_electrum_rpc_url,
+                    bitcoin_fee_strategy,
+                    gap_limit,
+                    birthday_height,
                 },
             }
         }
@@ -141,20 +306,22 @@ where
             bitcoin,
             amount,
             address,
+            coin_selection,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
                 cmd: Command::WithdrawBtc {
                     bitcoin_electrum_rpc_url,
-                    bitcoin_target_block,
+                    bitcoin_fee_strategy,
                     amount,
                     address: bitcoin_address(address, is_testnet)?,
+                    coin_control: coin_selection.into(),
                 },
             }
         }
@@ -163,8 +330,9 @@ where
             bitcoin,
             judecoin,
             tor: Tor { tor_socks5_port },
+            onion_service,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
                 bitcoin.apply_defaults(is_testnet)?;
             let judecoin_daemon_address = judecoin.apply_defaults(is_testnet);
 
@@ -172,13 +340,14 @@ where
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
                 cmd: Command::Resume {
                     swap_id,
                     bitcoin_electrum_rpc_url,
-                    bitcoin_target_block,
+                    bitcoin_fee_strategy,
                     judecoin_daemon_address,
                     tor_socks5_port,
+                    tor_control_port: onion_service.control_port(),
                 },
             }
         }
@@ -186,18 +355,18 @@ where
             swap_id: SwapId { swap_id },
             bitcoin,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
                 cmd: Command::Cancel {
                     swap_id,
                     bitcoin_electrum_rpc_url,
-                    bitcoin_target_block,
+                    bitcoin_fee_strategy,
                 },
             }
         }
@@ -205,47 +374,49 @@ where
             swap_id: SwapId { swap_id },
             bitcoin,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
                 cmd: Command::Refund {
                     swap_id,
                     bitcoin_electrum_rpc_url,
-                    bitcoin_target_block,
+                    bitcoin_fee_strategy,
                 },
             }
         }
         RawCommand::ListSellers {
             rendezvous_point,
+            cached,
             tor: Tor { tor_socks5_port },
         } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
             cmd: Command::ListSellers {
                 rendezvous_point,
                 namespace: rendezvous_namespace_from(is_testnet),
                 tor_socks5_port,
+                cached,
             },
         },
         RawCommand::ExportBitcoinWallet { bitcoin } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_url, bitcoin_fee_strategy) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
                 cmd: Command::ExportBitcoinWallet {
                     bitcoin_electrum_rpc_url,
-                    bitcoin_target_block,
+                    bitcoin_fee_strategy,
                 },
             }
         }
@@ -253,7 +424,7 @@ where
             env_config: env_config_from(is_testnet),
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            data_dir: data::data_dir_from(data, profile, create_profile, is_testnet)?,
             cmd: Command::judecoinRecovery {
                 swap_id: swap_id.swap_id,
             },
@@ -266,57 +437,135 @@ where
 #[derive(Debug, PartialEq)]
 pub enum Command {
     BuyXmr {
-        seller: Multiaddr,
+        seller: SellerSelection,
         bitcoin_electrum_rpc_url: Url,
-        bitcoin_target_block: usize,
+        bitcoin_fee_strategy: FeeStrategy,
         bitcoin_change_address: bitcoin::Address,
         judecoin_receive_address: judecoin::Address,
         judecoin_daemon_address: String,
         tor_socks5_port: u16,
+        /// Publish an onion service through the Tor daemon listening on this
+        /// control port.
+        tor_control_port: Option<u16>,
+        coin_control: CoinControl,
+        external_funding: Option<Amount>,
+        price_protection: PriceProtection,
+        amount: Option<SwapAmount>,
     },
     History,
     Config,
     WithdrawBtc {
         bitcoin_electrum_rpc_url: Url,
-        bitcoin_target_block: usize,
+        bitcoin_fee_strategy: FeeStrategy,
         amount: Option<Amount>,
         address: Address,
+        coin_control: CoinControl,
     },
     Balance {
         bitcoin_electrum_rpc_url: Url,
-        bitcoin_target_block: usize,
+        bitcoin_fee_strategy: FeeStrategy,
+    },
+    BtcHistory {
+        bitcoin_electrum_rpc_url: Url,
+        bitcoin_fee_strategy: FeeStrategy,
+    },
+    ListProfiles {
+        base_dir: PathBuf,
+    },
+    CreateProfile {
+        base_dir: PathBuf,
+        name: String,
+    },
+    DeleteProfile {
+        base_dir: PathBuf,
+        name: String,
+        force: bool,
+    },
+    ExportSeed,
+    RestoreSeed {
+        bitcoin_electrum_rpc_url: Url,
+        bitcoin_fee_strategy: FeeStrategy,
+        gap_limit: u32,
+        birthday_height: Option<u32>,
+    },
+    Rescan {
+        bitcoin_electrum_rpc_url: Url,
+        bitcoin_fee_strategy: FeeStrategy,
+        gap_limit: u32,
+        birthday_height: Option<u32>,
     },
     Resume {
         swap_id: Uuid,
         bitcoin_electrum_rpc_url: Url,
-        bitcoin_target_block: usize,
+        bitcoin_fee_strategy: FeeStrategy,
         judecoin_daemon_address: String,
         tor_socks5_port: u16,
+        tor_control_port: Option<u16>,
     },
     Cancel {
         swap_id: Uuid,
         bitcoin_electrum_rpc_url: Url,
-        bitcoin_target_block: usize,
+        bitcoin_fee_strategy: FeeStrategy,
     },
     Refund {
         swap_id: Uuid,
         bitcoin_electrum_rpc_url: Url,
-        bitcoin_target_block: usize,
+        bitcoin_fee_strategy: FeeStrategy,
     },
     ListSellers {
-        rendezvous_point: Multiaddr,
+        rendezvous_point: Vec<Multiaddr>,
         namespace: XmrBtcNamespace,
         tor_socks5_port: u16,
+        /// Only list the sellers remembered from earlier runs, without going
+        /// online.
+        cached: bool,
     },
     ExportBitcoinWallet {
         bitcoin_electrum_rpc_url: Url,
-        bitcoin_target_block: usize,
+        bitcoin_fee_strategy: FeeStrategy,
     },
     judecoinRecovery {
         swap_id: Uuid,
     },
 }
 
+/// A fixed amount to swap, given on either side of the swap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwapAmount {
+    Btc(Amount),
+    Jude(judecoin::Amount),
+}
+
+impl SwapAmount {
+    /// The Bitcoin to swap at the given price of one JUDE, rounded up to the
+    /// next satoshi.
+    pub fn btc(&self, price: Amount) -> Amount {
+        match self {
+            SwapAmount::Btc(btc) => *btc,
+            SwapAmount::Jude(jude) => {
+                let piconero_per_jude = u128::from(judecoin::Amount::ONE_JUDE.as_piconero());
+                let sats = (u128::from(jude.as_piconero()) * u128::from(price.as_sat())
+                    + piconero_per_jude
+                    - 1)
+                    / piconero_per_jude;
+
+                Amount::from_sat(u64::try_from(sats).unwrap_or(u64::MAX))
+            }
+        }
+    }
+}
+
+/// Where to find the seller to swap with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SellerSelection {
+    Address(Multiaddr),
+    /// Discover sellers at the rendezvous points and pick the best one.
+    Best {
+        rendezvous_point: Vec<Multiaddr>,
+        namespace: XmrBtcNamespace,
+    },
+}
+
 #[derive(structopt::StructOpt, Debug)]
 #[structopt(
     name = "swap",
@@ -339,6 +588,21 @@ struct RawArguments {
     )]
     data: Option<PathBuf>,
 
+    #[structopt(
+        long = "profile",
+        help = "Use the named profile, which has its own seed, database and wallets",
+        parse(try_from_str = profile::parse_name),
+        global = true
+    )]
+    profile: Option<String>,
+
+    #[structopt(
+        long = "create-profile",
+        help = "Create the profile given with --profile if it does not exist yet",
+        global = true
+    )]
+    create_profile: bool,
+
     #[structopt(long, help = "Activate debug logging")]
     debug: bool,
 
@@ -380,6 +644,35 @@ enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(flatten)]
+        onion_service: OnionService,
+
+        #[structopt(flatten)]
+        coin_selection: CoinSelection,
+
+        #[structopt(
+            long = "external-funding",
+            help = "Lock the given amount of Bitcoin from an external wallet instead of the internal one. The lock transaction is printed as a PSBT that has to be funded and pasted back before the swap setup, and signed once the seller signed the refund transaction."
+        )]
+        external_funding: Option<Amount>,
+
+        #[structopt(flatten)]
+        price_limits: PriceLimits,
+
+        #[structopt(
+            long = "btc",
+            help = "Swap exactly the given amount of Bitcoin instead of the whole balance.",
+            conflicts_with = "jude"
+        )]
+        btc: Option<Amount>,
+
+        #[structopt(
+            long = "jude",
+            help = "Swap for exactly the given amount of JUDE, e.g. 2.5. The Bitcoin amount is computed from the seller's price.",
+            parse(try_from_str = judecoin::Amount::parse_judecoin)
+        )]
+        jude: Option<judecoin::Amount>,
     },
     /// Show a list of past, ongoing and completed swaps
     History,
@@ -397,12 +690,22 @@ enum RawCommand {
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
+
+        #[structopt(flatten)]
+        coin_selection: CoinSelection,
     },
     #[structopt(about = "Prints the Bitcoin balance.")]
     Balance {
         #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
         bitcoin_electrum_rpc_url: Option<Url>,
     },
+    #[structopt(
+        about = "Prints the transactions of the internal Bitcoin wallet, labelled by what they were made for."
+    )]
+    BtcHistory {
+        #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
+        bitcoin_electrum_rpc_url: Option<Url>,
+    },
     /// Resume a swap
     Resume {
         #[structopt(flatten)]
@@ -416,6 +719,9 @@ enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(flatten)]
+        onion_service: OnionService,
     },
     /// Force submission of the cancel transaction overriding the protocol state
     /// machine and blockheight checks (expert users only)
@@ -439,13 +745,43 @@ enum RawCommand {
     ListSellers {
         #[structopt(
             long,
-            help = "Address of the rendezvous point you want to use to discover ASBs"
+            help = "Address of the rendezvous point you want to use to discover ASBs. Can be given multiple times.",
+            required_unless = "cached"
         )]
-        rendezvous_point: Multiaddr,
+        rendezvous_point: Vec<Multiaddr>,
+
+        #[structopt(
+            long,
+            help = "List the sellers discovered in earlier runs without connecting to the network",
+            conflicts_with = "rendezvous-point"
+        )]
+        cached: bool,
 
         #[structopt(flatten)]
         tor: Tor,
     },
+    /// Back up or restore the seed as a BIP39 mnemonic
+    Seed(RawSeedCommand),
+    /// Manage named profiles with isolated seeds, databases and wallets
+    Profile(RawProfileCommand),
+    /// Discard the cached state of the internal Bitcoin wallet and rescan it
+    Rescan {
+        #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
+        bitcoin_electrum_rpc_url: Option<Url>,
+
+        #[structopt(
+            long = "gap-limit",
+            help = "Stop looking for funds after this many consecutive unused addresses",
+            default_value = DEFAULT_RESCAN_GAP_LIMIT
+        )]
+        gap_limit: u32,
+
+        #[structopt(
+            long = "birthday-height",
+            help = "The block height at which the seed was created, warns if funds are found before it"
+        )]
+        birthday_height: Option<u32>,
+    },
     /// Print the internal bitcoin wallet descriptor
     ExportBitcoinWallet {
         #[structopt(flatten)]
@@ -460,6 +796,53 @@ enum RawCommand {
     },
 }
 
+#[derive(structopt::StructOpt, Debug)]
+enum RawProfileCommand {
+    /// List all profiles
+    List,
+    /// Create a new profile
+    Create {
+        #[structopt(parse(try_from_str = profile::parse_name))]
+        name: String,
+    },
+    /// Delete a profile and all of its data
+    Delete {
+        #[structopt(parse(try_from_str = profile::parse_name))]
+        name: String,
+
+        #[structopt(
+            long = "force",
+            help = "Delete the profile even if it contains a seed that may still control funds"
+        )]
+        force: bool,
+    },
+}
+
+#[derive(structopt::StructOpt, Debug)]
+enum RawSeedCommand {
+    /// Print the seed as a 24 word BIP39 mnemonic
+    Export,
+    /// Restore the seed from a BIP39 mnemonic read from stdin and rescan the
+    /// internal Bitcoin wallet. Fails if a seed already exists.
+    Restore {
+        #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
+        bitcoin_electrum_rpc_url: Option<Url>,
+
+        #[structopt(
+            long = "gap-limit",
+            help = "Stop looking for funds after this many consecutive unused addresses",
+            default_value = DEFAULT_RESCAN_GAP_LIMIT
+        )]
+        gap_limit: u32,
+
+        #[structopt(
+            long = "birthday-height",
+            help = "The block height at which the seed was created, warns if funds are found before it"
+        )]
+        birthday_height: Option<u32>,
+    },
+}
+
 #[derive(structopt::StructOpt, Debug)]
 struct judecoin {
     #[structopt(
@@ -491,10 +874,40 @@ struct Bitcoin {
         help = "Estimate Bitcoin fees such that transactions are confirmed within the specified number of blocks"
     )]
     bitcoin_target_block: Option<usize>,
+
+    #[structopt(
+        long = "bitcoind-rpc",
+        help = "Estimate Bitcoin fees using estimatesmartfee of the Bitcoin Core node at the given RPC URL"
+    )]
+    bitcoind_rpc_url: Option<Url>,
+
+    #[structopt(
+        long = "mempool-api",
+        help = "Estimate Bitcoin fees using the mempool.space compatible API at the given URL, e.g. https://mempool.space/api/"
+    )]
+    mempool_api_url: Option<Url>,
+
+    #[structopt(
+        long = "bitcoin-fee-rate",
+        help = "Use a fixed fee rate in sat/vB instead of estimating Bitcoin fees"
+    )]
+    bitcoin_fee_rate: Option<f32>,
+
+    #[structopt(
+        long = "bitcoin-min-fee-rate",
+        help = "Never pay less than the given fee rate in sat/vB"
+    )]
+    bitcoin_min_fee_rate: Option<f32>,
+
+    #[structopt(
+        long = "bitcoin-max-fee-rate",
+        help = "Never pay more than the given fee rate in sat/vB"
+    )]
+    bitcoin_max_fee_rate: Option<f32>,
 }
 
 impl Bitcoin {
-    fn apply_defaults(self, testnet: bool) -> Result<(Url, usize)> {
+    fn apply_defaults(self, testnet: bool) -> Result<(Url, FeeStrategy)> {
         let bitcoin_electrum_rpc_url = if let Some(url) = self.bitcoin_electrum_rpc_url {
             url
         } else if testnet {
@@ -511,7 +924,29 @@ impl Bitcoin {
             DEFAULT_BITCOIN_CONFIRMATION_TARGET
         };
 
-        Ok((bitcoin_electrum_rpc_url, bitcoin_target_block))
+        let estimator = match (
+            self.bitcoind_rpc_url,
+            self.mempool_api_url,
+            self.bitcoin_fee_rate,
+        ) {
+            (None, None, None) => FeeEstimator::Electrum,
+            (Some(rpc_url), None, None) => FeeEstimator::BitcoinCore { rpc_url },
+            (None, Some(api_url), None) => FeeEstimator::Mempool { api_url },
+            (None, None, Some(sat_per_vb)) => FeeEstimator::Fixed { sat_per_vb },
+            _ => bail!(
+                "Only one of --bitcoind-rpc, --mempool-api and --bitcoin-fee-rate can be given"
+            ),
+        };
+
+        let bitcoin_fee_strategy = FeeStrategy {
+            estimator,
+            target_block: bitcoin_target_block,
+            target_blocks: TargetBlocks::default(),
+            min_sat_per_vb: self.bitcoin_min_fee_rate,
+            max_sat_per_vb: self.bitcoin_max_fee_rate,
+        };
+
+        Ok((bitcoin_electrum_rpc_url, bitcoin_fee_strategy))
     }
 }
 
@@ -525,6 +960,28 @@ struct Tor {
     tor_socks5_port: u16,
 }
 
+#[derive(structopt::StructOpt, Debug)]
+struct OnionService {
+    #[structopt(
+        long = "tor-onion-service",
+        help = "Publish an onion service so that the seller can reach you even if you are behind a NAT. Requires a running Tor daemon with its control port enabled."
+    )]
+    tor_onion_service: bool,
+
+    #[structopt(
+        long = "tor-control-port",
+        help = "Your local Tor control port",
+        default_value = DEFAULT_TOR_CONTROL_PORT
+    )]
+    tor_control_port: u16,
+}
+
+impl OnionService {
+    fn control_port(&self) -> Option<u16> {
+        self.tor_onion_service.then(|| self.tor_control_port)
+    }
+}
+
 #[derive(structopt::StructOpt, Debug)]
 struct SwapId {
     #[structopt(
@@ -538,23 +995,126 @@ struct SwapId {
 struct Seller {
     #[structopt(
         long,
-        help = "The seller's address. Must include a peer ID part, i.e. `/p2p/`"
+        help = "The seller's address. Must include a peer ID part, i.e. `/p2p/`",
+        required_unless = "rendezvous-point"
+    )]
+    seller: Option<Multiaddr>,
+
+    #[structopt(
+        long,
+        help = "Instead of a seller, discover sellers at the given rendezvous point and swap with the best one. Can be given multiple times.",
+        conflicts_with = "seller"
+    )]
+    rendezvous_point: Vec<Multiaddr>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+struct CoinSelection {
+    #[structopt(
+        long = "utxo",
+        help = "Only spend the given output of the internal Bitcoin wallet, in the format <txid>:<vout>. Can be given multiple times."
     )]
-    seller: Multiaddr,
+    utxos: Vec<OutPoint>,
+
+    #[structopt(
+        long = "min-utxo-amount",
+        help = "Never spend outputs of the internal Bitcoin wallet worth less than the given amount, e.g. to exclude dust."
+    )]
+    min_utxo_amount: Option<Amount>,
+
+    #[structopt(
+        long = "single-source",
+        help = "Only spend outputs of the internal Bitcoin wallet that were received in the same transaction, so that coins from different sources are never merged."
+    )]
+    single_source: bool,
+}
+
+impl From<CoinSelection> for CoinControl {
+    fn from(coin_selection: CoinSelection) -> Self {
+        CoinControl {
+            outpoints: coin_selection.utxos,
+            min_utxo_value: coin_selection.min_utxo_amount,
+            single_source: coin_selection.single_source,
+        }
+    }
+}
+
+#[derive(structopt::StructOpt, Debug)]
+struct PriceLimits {
+    #[structopt(
+        long = "max-price",
+        help = "Abort before locking Bitcoin if the seller asks more than the given amount of Bitcoin for one JUDE."
+    )]
+    max_price: Option<Amount>,
+
+    #[structopt(
+        long = "min-jude",
+        help = "Abort before locking Bitcoin if the seller offers less than the given amount of JUDE, e.g. 1.5.",
+        parse(try_from_str = judecoin::Amount::parse_judecoin)
+    )]
+    min_jude: Option<judecoin::Amount>,
+
+    #[structopt(
+        long = "price-tolerance",
+        help = "Abort before locking Bitcoin if the price rose by more than the given fraction since the initial quote, e.g. 0.01 for one percent."
+    )]
+    price_tolerance: Option<Decimal>,
+}
+
+impl From<PriceLimits> for PriceProtection {
+    fn from(price_limits: PriceLimits) -> Self {
+        PriceProtection {
+            max_price: price_limits.max_price,
+            min_jude: price_limits.min_jude,
+            quoted_price: None,
+            tolerance: price_limits.price_tolerance,
+        }
+    }
 }
 
 mod data {
     use super::*;
 
-    pub fn data_dir_from(arg_dir: Option<PathBuf>, testnet: bool) -> Result<PathBuf> {
-        let base_dir = match arg_dir {
-            Some(custom_base_dir) => custom_base_dir,
-            None => os_default()?,
+    pub fn base_dir_from(arg_dir: Option<PathBuf>) -> Result<PathBuf> {
+        match arg_dir {
+            Some(custom_base_dir) => Ok(custom_base_dir),
+            None => os_default(),
+        }
+    }
+
+    /// A profile must have been created before, otherwise a typo in its name
+    /// would silently start a new identity with an empty wallet.
+    pub fn data_dir_from(
+        arg_dir: Option<PathBuf>,
+        profile: Option<String>,
+        create_profile: bool,
+        testnet: bool,
+    ) -> Result<PathBuf> {
+        let base_dir = base_dir_from(arg_dir)?;
+        let base_dir = match profile {
+            Some(name) => {
+                let dir = profile::dir(&base_dir, &name);
+
+                if !dir.exists() && !create_profile {
+                    bail!(
+                        "Profile {} does not exist, create it with `swap profile create {}` or pass --create-profile",
+                        name,
+                        name
+                    )
+                }
+
+                dir
+            }
+            None => base_dir,
         };
 
+        Ok(network_dir(&base_dir, testnet))
+    }
+
+    pub fn network_dir(base_dir: &Path, testnet: bool) -> PathBuf {
         let sub_directory = if testnet { "testnet" } else { "mainnet" };
 
-        Ok(base_dir.join(sub_directory))
+        base_dir.join(sub_directory)
     }
 
     fn os_default() -> Result<PathBuf> {
@@ -665,7 +1225,7 @@ pub struct BitcoinAddressNetworkMismatch {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tor::DEFAULT_SOCKS5_PORT;
+    use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
 
     const BINARY_NAME: &str = "swap";
 
@@ -842,6 +1402,511 @@ mod tests {
         );
     }
 
+    #[test]
+    fn given_seed_export_then_export_seed_command() {
+        let raw_ars = vec![BINARY_NAME, "seed", "export"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::ExportSeed,
+            })
+        );
+    }
+
+    #[test]
+    fn given_seed_restore_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "seed",
+            "restore",
+            "--birthday-height",
+            "2000000",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::RestoreSeed {
+                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
+                        .unwrap(),
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    ),
+                    gap_limit: 100,
+                    birthday_height: Some(2_000_000),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_rescan_with_gap_limit_then_gap_limit_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "rescan",
+            "--gap-limit",
+            "500",
+            "--birthday-height",
+            "700000",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Rescan {
+                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET
+                    ),
+                    gap_limit: 500,
+                    birthday_height: Some(700_000),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_list_sellers_cached_then_no_rendezvous_point_required() {
+        let raw_ars = vec![BINARY_NAME, "list-sellers", "--cached"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::ListSellers {
+                    rendezvous_point: vec![],
+                    namespace: XmrBtcNamespace::Mainnet,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    cached: true,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_list_sellers_without_rendezvous_point_or_cached_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "list-sellers"];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_profile_then_data_dir_is_isolated() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--profile",
+            "savings",
+            "--create-profile",
+            "--testnet",
+            "history",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli()
+                    .join("profiles")
+                    .join("savings")
+                    .join(TESTNET),
+                cmd: Command::History,
+            })
+        );
+    }
+
+    #[test]
+    fn given_profile_that_does_not_exist_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--profile",
+            "profile-that-does-not-exist",
+            "history",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_invalid_profile_name_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "--profile", "../mainnet", "history"];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_profile_delete_then_base_dir_set() {
+        let raw_ars = vec![BINARY_NAME, "profile", "delete", "savings", "--force"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::DeleteProfile {
+                    base_dir: data_dir_path_cli(),
+                    name: "savings".to_string(),
+                    force: true,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_fixed_fee_rate_with_bounds_then_fee_strategy_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "refund",
+            "--swap-id",
+            SWAP_ID,
+            "--bitcoin-fee-rate",
+            "12.5",
+            "--bitcoin-max-fee-rate",
+            "50",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let expected_fee_strategy = FeeStrategy {
+            estimator: FeeEstimator::Fixed { sat_per_vb: 12.5 },
+            max_sat_per_vb: Some(50.0),
+            ..FeeStrategy::electrum(DEFAULT_BITCOIN_CONFIRMATION_TARGET)
+        };
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                cmd: Command::Refund {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_fee_strategy: expected_fee_strategy,
+                },
+                ..Arguments::refund_mainnet_defaults()
+            })
+        );
+    }
+
+    #[test]
+    fn given_multiple_fee_estimators_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "refund",
+            "--swap-id",
+            SWAP_ID,
+            "--bitcoin-fee-rate",
+            "12.5",
+            "--mempool-api",
+            "https://mempool.space/api/",
+        ];
+
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_buy_jude_with_coin_selection_then_coin_control_set() {
+        let utxo = "ea030832a3be9454fbb985ea9a788406ea030832a3be9454fbb985ea9a788406:1";
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--utxo",
+            utxo,
+            "--min-utxo-amount",
+            "0.0001 BTC",
+            "--single-source",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::buy_jude_mainnet_defaults();
+        if let Command::BuyXmr { coin_control, .. } = &mut expected.cmd {
+            *coin_control = CoinControl {
+                outpoints: vec![OutPoint::from_str(utxo).unwrap()],
+                min_utxo_value: Some(Amount::from_sat(10_000)),
+                single_source: true,
+            };
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_buy_jude_with_external_funding_then_amount_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--external-funding",
+            "0.01 BTC",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::buy_jude_mainnet_defaults();
+        if let Command::BuyXmr {
+            external_funding, ..
+        } = &mut expected.cmd
+        {
+            *external_funding = Some(Amount::from_sat(1_000_000));
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_buy_jude_with_price_limits_then_price_protection_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--max-price",
+            "0.005 BTC",
+            "--min-jude",
+            "1.5",
+            "--price-tolerance",
+            "0.01",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::buy_jude_mainnet_defaults();
+        if let Command::BuyXmr {
+            price_protection, ..
+        } = &mut expected.cmd
+        {
+            *price_protection = PriceProtection {
+                max_price: Some(Amount::from_sat(500_000)),
+                min_jude: Some(judecoin::Amount::parse_judecoin("1.5").unwrap()),
+                quoted_price: None,
+                tolerance: Some(Decimal::new(1, 2)),
+            };
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_buy_jude_with_jude_amount_then_amount_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--jude",
+            "2.5",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::buy_jude_mainnet_defaults();
+        if let Command::BuyXmr { amount, .. } = &mut expected.cmd {
+            *amount = Some(SwapAmount::Jude(
+                judecoin::Amount::parse_judecoin("2.5").unwrap(),
+            ));
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_buy_jude_with_onion_service_then_control_port_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--tor-onion-service",
+            "--tor-control-port",
+            "9151",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::buy_jude_mainnet_defaults();
+        if let Command::BuyXmr {
+            tor_control_port, ..
+        } = &mut expected.cmd
+        {
+            *tor_control_port = Some(9151);
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_resume_with_onion_service_then_default_control_port_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--tor-onion-service",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::resume_mainnet_defaults();
+        if let Command::Resume {
+            tor_control_port, ..
+        } = &mut expected.cmd
+        {
+            *tor_control_port = Some(DEFAULT_CONTROL_PORT);
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_buy_jude_with_rendezvous_points_then_best_seller_selected() {
+        let other_rendezvous_point = "/dns4/rendezvous.example.com/tcp/8888/p2p/12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--rendezvous-point",
+            MULTI_ADDRESS,
+            "--rendezvous-point",
+            other_rendezvous_point,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::buy_jude_mainnet_defaults();
+        if let Command::BuyXmr { seller, .. } = &mut expected.cmd {
+            *seller = SellerSelection::Best {
+                rendezvous_point: vec![
+                    Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
+                    Multiaddr::from_str(other_rendezvous_point).unwrap(),
+                ],
+                namespace: XmrBtcNamespace::Mainnet,
+            };
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_buy_jude_without_seller_or_rendezvous_point_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn jude_amount_is_converted_to_btc_rounded_up_to_next_satoshi() {
+        let price = Amount::from_sat(3);
+
+        assert_eq!(
+            SwapAmount::Jude(judecoin::Amount::from_piconero(1)).btc(price),
+            Amount::from_sat(1)
+        );
+        assert_eq!(
+            SwapAmount::Jude(judecoin::Amount::ONE_JUDE).btc(price),
+            Amount::from_sat(3)
+        );
+    }
+
+    #[test]
+    fn given_buy_jude_with_btc_and_jude_amount_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--btc",
+            "0.01 BTC",
+            "--jude",
+            "2.5",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_external_funding_with_coin_selection_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-jude",
+            "--receive-address",
+            JUDECOIN_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--external-funding",
+            "0.01 BTC",
+            "--min-utxo-amount",
+            "0.0001 BTC",
+        ];
+
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
     #[test]
     fn given_with_data_dir_then_data_dir_set() {
         let data_dir = "/some/path/to/dir";
@@ -1166,15 +2231,22 @@ mod tests {
                 json: false,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::BuyXmr {
-                    seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
+                    seller: SellerSelection::Address(Multiaddr::from_str(MULTI_ADDRESS).unwrap()),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
                         .unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    ),
                     bitcoin_change_address: BITCOIN_TESTNET_ADDRESS.parse().unwrap(),
                     judecoin_receive_address: judecoin::Address::from_str(JUDECOIN_STAGENET_ADDRESS)
                         .unwrap(),
                     judecoin_daemon_address: DEFAULT_JUDECOIN_DAEMON_ADDRESS_STAGENET.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_control_port: None,
+                    coin_control: CoinControl::default(),
+                    external_funding: None,
+                    price_protection: PriceProtection::default(),
+                    amount: None,
                 },
             }
         }
@@ -1186,14 +2258,21 @@ mod tests {
                 json: false,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::BuyXmr {
-                    seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
+                    seller: SellerSelection::Address(Multiaddr::from_str(MULTI_ADDRESS).unwrap()),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    ),
                     bitcoin_change_address: BITCOIN_MAINNET_ADDRESS.parse().unwrap(),
                     judecoin_receive_address: judecoin::Address::from_str(JUDECOIN_MAINNET_ADDRESS)
                         .unwrap(),
                     judecoin_daemon_address: DEFAULT_JUDECOIN_DAEMON_ADDRESS.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_control_port: None,
+                    coin_control: CoinControl::default(),
+                    external_funding: None,
+                    price_protection: PriceProtection::default(),
+                    amount: None,
                 },
             }
         }
@@ -1208,9 +2287,12 @@ mod tests {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
                         .unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    ),
                     judecoin_daemon_address: DEFAULT_JUDECOIN_DAEMON_ADDRESS_STAGENET.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_control_port: None,
                 },
             }
         }
@@ -1224,9 +2306,12 @@ mod tests {
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    ),
                     judecoin_daemon_address: DEFAULT_JUDECOIN_DAEMON_ADDRESS.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_control_port: None,
                 },
             }
         }
@@ -1241,7 +2326,9 @@ mod tests {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
                         .unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    ),
                 },
             }
         }
@@ -1255,7 +2342,9 @@ mod tests {
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    ),
                 },
             }
         }
@@ -1270,7 +2359,9 @@ mod tests {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
                         .unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    ),
                 },
             }
         }
@@ -1284,7 +2375,9 @@ mod tests {
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
-                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_fee_strategy: FeeStrategy::electrum(
+                        DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    ),
                 },
             }
         }
@@ -2,17 +2,22 @@ use crate::bitcoin::EncryptedSignature;
 use crate::cli::behaviour::{Behaviour, OutEvent};
 use crate::network::encrypted_signature;
 use crate::network::quote::BidQuote;
+use crate::network::redial;
 use crate::network::swap_setup::bob::NewSwap;
+use crate::network::transfer_proof;
 use crate::protocol::bob::State2;
+use crate::protocol::{Database, PendingMessage};
 use crate::{env, judecoin};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use futures::future::{BoxFuture, OptionFuture};
 use futures::{FutureExt, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
 use libp2p::{PeerId, Swarm};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::watch;
 use uuid::Uuid;
 
 #[allow(missing_debug_implementations)]
@@ -20,19 +25,29 @@ pub struct EventLoop {
     swap_id: Uuid,
     swarm: libp2p::Swarm<Behaviour>,
     alice_peer_id: PeerId,
+    db: Arc<dyn Database + Send + Sync>,
 
     // these streams represents outgoing requests that we have to make
     quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
     encrypted_signatures: bmrng::RequestReceiverStream<EncryptedSignature, ()>,
     swap_setup_requests: bmrng::RequestReceiverStream<NewSwap, Result<State2>>,
 
+    /// How long to try reconnecting to Alice, changes as the swap progresses.
+    redial_policy: watch::Receiver<redial::Policy>,
+
     // these represents requests that are currently in-flight.
     // once we get a response to a matching [`RequestId`], we will use the responder to relay the
     // response.
     inflight_quote_requests: HashMap<RequestId, bmrng::Responder<BidQuote>>,
-    inflight_encrypted_signature_requests: HashMap<RequestId, bmrng::Responder<()>>,
+    inflight_encrypted_signature_request: Option<RequestId>,
     inflight_swap_setup: Option<bmrng::Responder<Result<State2>>>,
 
+    /// The responder waiting for Alice to acknowledge our encrypted signature.
+    ///
+    /// The encrypted signature itself is kept in the database until it is
+    /// acknowledged and is sent again whenever we reconnect to Alice.
+    encrypted_signature_responder: Option<bmrng::Responder<()>>,
+
     /// The sender we will use to relay incoming transfer proofs.
     transfer_proof: bmrng::RequestSender<judecoin::TransferProof, ()>,
     /// The future representing the successful handling of an incoming transfer
@@ -50,24 +65,29 @@ impl EventLoop {
         swap_id: Uuid,
         swarm: Swarm<Behaviour>,
         alice_peer_id: PeerId,
+        db: Arc<dyn Database + Send + Sync>,
         env_config: env::Config,
     ) -> Result<(Self, EventLoopHandle)> {
         let execution_setup = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let transfer_proof = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let encrypted_signature = bmrng::channel(1);
         let quote = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let redial_policy = watch::channel(redial::Policy::default());
 
         let event_loop = EventLoop {
             swap_id,
             swarm,
             alice_peer_id,
+            db,
             swap_setup_requests: execution_setup.1.into(),
             transfer_proof: transfer_proof.0,
             encrypted_signatures: encrypted_signature.1.into(),
             quote_requests: quote.1.into(),
+            redial_policy: redial_policy.1,
             inflight_quote_requests: HashMap::default(),
             inflight_swap_setup: None,
-            inflight_encrypted_signature_requests: HashMap::default(),
+            inflight_encrypted_signature_request: None,
+            encrypted_signature_responder: None,
             pending_transfer_proof: OptionFuture::from(None),
         };
 
@@ -76,6 +96,7 @@ impl EventLoop {
             transfer_proof: transfer_proof.1,
             encrypted_signature: encrypted_signature.0,
             quote: quote.0,
+            redial_policy: redial_policy.0,
             env_config,
         };
 
@@ -119,11 +140,13 @@ impl EventLoop {
                             }
 
                             if swap_id != self.swap_id {
+                                // Alice only deletes the transfer proof once we acknowledged it, keep it for when the other swap is resumed
+                                if let Err(error) = self.store_transfer_proof(peer, *msg).await {
+                                    tracing::warn!(%swap_id, "Failed to store transfer proof for another swap, Alice will send it again: {:#}", error);
+                                    continue;
+                                }
+                                tracing::info!(%swap_id, "Stored transfer proof for swap {} while running swap {}", swap_id, self.swap_id);
 
-                                // TODO: Save unexpected transfer proofs in the database and check for messages in the database when handling swaps
-                                tracing::warn!("Received unexpected transfer proof for swap {} while running swap {}. This transfer proof will be ignored", swap_id, self.swap_id);
-
-                                // When receiving a transfer proof that is unexpected we still have to acknowledge that it was received
                                 let _ = self.swarm.behaviour_mut().transfer_proof.send_response(channel, ());
                                 continue;
                             }
@@ -143,8 +166,16 @@ impl EventLoop {
                             }.boxed()));
                         }
                         SwarmEvent::Behaviour(OutEvent::EncryptedSignatureAcknowledged { id }) => {
-                            if let Some(responder) = self.inflight_encrypted_signature_requests.remove(&id) {
-                                let _ = responder.respond(());
+                            if self.inflight_encrypted_signature_request == Some(id) {
+                                self.inflight_encrypted_signature_request = None;
+
+                                if let Err(error) = self.db.remove_pending_message(self.swap_id).await {
+                                    tracing::warn!("Failed to remove acknowledged encrypted signature from database: {:#}", error);
+                                }
+
+                                if let Some(responder) = self.encrypted_signature_responder.take() {
+                                    let _ = responder.respond(());
+                                }
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer }) if peer == self.alice_peer_id => {
@@ -155,8 +186,12 @@ impl EventLoop {
                             tracing::warn!(%peer, "Communication error: {:#}", error);
                             return;
                         }
-                        SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } if peer_id == self.alice_peer_id => {
+                        SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established } if peer_id == self.alice_peer_id => {
                             tracing::info!("Connected to Alice at {}", endpoint.get_remote_address());
+
+                            if num_established.get() == 1 {
+                                self.replay_pending_encrypted_signature().await;
+                            }
                         }
                         SwarmEvent::Dialing(peer_id) if peer_id == self.alice_peer_id => {
                             tracing::debug!("Dialling Alice at {}", peer_id);
@@ -172,9 +207,21 @@ impl EventLoop {
                         SwarmEvent::UnreachableAddr { peer_id, address, attempts_remaining, error } if peer_id == self.alice_peer_id && attempts_remaining == 0 => {
                             tracing::warn!(%address, "Failed to dial Alice: {}", error);
 
-                            if let Some(duration) = self.swarm.behaviour_mut().redial.until_next_redial() {
+                            let redialling = &self.swarm.behaviour().redial;
+
+                            if let Some(duration) = redialling.until_next_redial() {
                                 tracing::info!("Next redial attempt in {}s", duration.as_secs());
                             }
+
+                            match (redialling.policy(), redialling.time_left()) {
+                                (redial::Policy::KeepTrying { .. }, Some(time_left)) => {
+                                    tracing::info!("It is safe to keep waiting for Alice for about {} more minutes, afterwards the swap will be cancelled", time_left.as_secs() / 60);
+                                }
+                                (_, Some(time_left)) => {
+                                    tracing::info!("Giving up on Alice in {}s", time_left.as_secs());
+                                }
+                                _ => {}
+                            }
                         }
                         _ => {}
                     }
@@ -190,16 +237,29 @@ impl EventLoop {
                     self.swarm.behaviour_mut().swap_setup.start(self.alice_peer_id, swap).await;
                     self.inflight_swap_setup = Some(responder);
                 },
-                Some((tx_redeem_encsig, responder)) = self.encrypted_signatures.next().fuse(), if self.is_connected_to_alice() => {
+                Some((tx_redeem_encsig, responder)) = self.encrypted_signatures.next().fuse() => {
                     let request = encrypted_signature::Request {
                         swap_id: self.swap_id,
                         tx_redeem_encsig
                     };
 
-                    let id = self.swarm.behaviour_mut().encrypted_signature.send_request(&self.alice_peer_id, request);
-                    self.inflight_encrypted_signature_requests.insert(id, responder);
+                    if let Err(error) = self.db.insert_pending_message(self.alice_peer_id, PendingMessage::EncryptedSignature(request.clone())).await {
+                        tracing::warn!("Failed to persist encrypted signature, it will be lost on restart: {:#}", error);
+                    }
+                    self.encrypted_signature_responder = Some(responder);
+
+                    // Otherwise it is sent once we are connected, unless an encrypted signature
+                    // replayed from the database is already on its way
+                    if self.is_connected_to_alice() && self.inflight_encrypted_signature_request.is_none() {
+                        self.send_encrypted_signature(request);
+                    }
                 },
 
+                Ok(()) = self.redial_policy.changed() => {
+                    let policy = *self.redial_policy.borrow();
+                    self.swarm.behaviour_mut().redial.set_policy(policy);
+                }
+
                 Some(response_channel) = &mut self.pending_transfer_proof => {
                     let _ = self.swarm.behaviour_mut().transfer_proof.send_response(response_channel, ());
 
@@ -212,6 +272,71 @@ impl EventLoop {
     fn is_connected_to_alice(&self) -> bool {
         self.swarm.is_connected(&self.alice_peer_id)
     }
+
+    fn send_encrypted_signature(&mut self, request: encrypted_signature::Request) {
+        // replaces a request sent over a previous connection which will never be
+        // acknowledged
+        let id = self
+            .swarm
+            .behaviour_mut()
+            .encrypted_signature
+            .send_request(&self.alice_peer_id, request);
+        self.inflight_encrypted_signature_request = Some(id);
+    }
+
+    /// Sends the encrypted signature Alice has not yet acknowledged, including
+    /// one queued before a restart.
+    /// Stores a transfer proof for another swap with Alice until that swap is
+    /// resumed.
+    async fn store_transfer_proof(
+        &self,
+        peer: PeerId,
+        request: transfer_proof::Request,
+    ) -> Result<()> {
+        let swap_id = request.swap_id;
+
+        if self.db.get_peer_id(swap_id).await? != peer {
+            bail!("Swap {} is not a swap with {}", swap_id, peer);
+        }
+
+        // a message pending for the swap means it already got past the transfer proof
+        let already_pending = self
+            .db
+            .get_pending_messages(peer)
+            .await?
+            .iter()
+            .any(|message| message.swap_id() == swap_id);
+        if already_pending {
+            return Ok(());
+        }
+
+        self.db
+            .insert_pending_message(peer, PendingMessage::TransferProof(request))
+            .await
+    }
+
+    async fn replay_pending_encrypted_signature(&mut self) {
+        let pending_messages = match self.db.get_pending_messages(self.alice_peer_id).await {
+            Ok(pending_messages) => pending_messages,
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to load pending encrypted signature from database: {:#}",
+                    error
+                );
+                return;
+            }
+        };
+
+        for message in pending_messages {
+            match message {
+                PendingMessage::EncryptedSignature(request) if request.swap_id == self.swap_id => {
+                    tracing::debug!("Sending pending encrypted signature to Alice");
+                    self.send_encrypted_signature(request);
+                }
+                _ => {}
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -220,6 +345,7 @@ pub struct EventLoopHandle {
     transfer_proof: bmrng::RequestReceiver<judecoin::TransferProof, ()>,
     encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
     quote: bmrng::RequestSender<(), BidQuote>,
+    redial_policy: watch::Sender<redial::Policy>,
     env_config: env::Config,
 }
 
@@ -241,6 +367,12 @@ impl EventLoopHandle {
         Ok(transfer_proof)
     }
 
+    /// Changes how long the event loop tries to reconnect to Alice.
+    pub fn set_redial_policy(&self, policy: redial::Policy) {
+        // the event loop is gone if this fails, which the swap will notice soon enough
+        let _ = self.redial_policy.send(policy);
+    }
+
     pub async fn request_quote(&mut self) -> Result<BidQuote> {
         Ok(self.quote.send_receive(()).await?)
     }
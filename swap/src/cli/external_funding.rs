@@ -0,0 +1,242 @@
+use crate::bitcoin::{Address, Amount, Network, PartiallySignedTransaction};
+use ::bitcoin::consensus::encode::{deserialize, serialize};
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::io::BufRead;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Hands the lock transaction to an external wallet, first to fund it and
+/// once the swap setup completed to sign it.
+///
+/// Neither step happens during the swap setup itself, so the external wallet
+/// may take as long as it needs.
+#[derive(Clone)]
+pub struct ExternalFunding(Arc<bmrng::RequestSender<Request, PartiallySignedTransaction>>);
+
+#[derive(Debug)]
+pub enum Request {
+    /// Add inputs and change to the template and hand it back unsigned.
+    Fund(PartiallySignedTransaction),
+    /// Sign and finalize the funded lock transaction.
+    Sign(PartiallySignedTransaction),
+}
+
+impl ExternalFunding {
+    pub fn new() -> (
+        Self,
+        bmrng::RequestReceiver<Request, PartiallySignedTransaction>,
+    ) {
+        let (sender, receiver) = bmrng::channel(1);
+
+        (Self(Arc::new(sender)), receiver)
+    }
+
+    pub async fn fund(
+        &self,
+        template: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction> {
+        self.0
+            .send_receive(Request::Fund(template))
+            .await
+            .map_err(|_| anyhow!("Failed to receive funded lock transaction from external wallet"))
+    }
+
+    pub async fn sign(
+        &self,
+        funded: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction> {
+        self.0
+            .send_receive(Request::Sign(funded))
+            .await
+            .map_err(|_| anyhow!("Failed to receive signed lock transaction from external wallet"))
+    }
+}
+
+impl fmt::Debug for ExternalFunding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ExternalFunding")
+    }
+}
+
+/// Sets up funding of the lock transaction from an external wallet.
+///
+/// Every PSBT requested by the swap is printed to stdout as base64 and the
+/// PSBT processed by the external wallet is then read back from stdin.
+///
+/// Stdin is read by a single thread for the lifetime of the process, so the
+/// same instance can be reused for every seller the CLI tries. Lines pasted
+/// while no PSBT was requested are discarded.
+pub fn external_funding(network: Network) -> ExternalFunding {
+    let (external_funding, mut requests) = ExternalFunding::new();
+    let mut lines = stdin_lines();
+
+    tokio::spawn(async move {
+        while let Ok((request, responder)) = requests.recv().await {
+            while lines.try_recv().is_ok() {}
+
+            match prompt(request, network, &mut lines).await {
+                Ok(psbt) => {
+                    let _ = responder.respond(psbt);
+                }
+                Err(error) => {
+                    tracing::error!("Failed to read lock transaction: {:#}", error);
+                }
+            }
+        }
+    });
+
+    external_funding
+}
+
+fn stdin_lines() -> mpsc::UnboundedReceiver<String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => {
+                    tracing::error!("Failed to read from stdin: {:#}", error);
+                    return;
+                }
+            };
+
+            if sender.send(line).is_err() {
+                return;
+            }
+        }
+    });
+
+    receiver
+}
+
+async fn prompt(
+    request: Request,
+    network: Network,
+    lines: &mut mpsc::UnboundedReceiver<String>,
+) -> Result<PartiallySignedTransaction> {
+    let psbt = match request {
+        Request::Fund(template) => {
+            let (address, amount) = first_output(&template, network)?;
+
+            tracing::info!(
+                %address,
+                %amount,
+                "Fund the following PSBT with your external wallet without signing it, then paste it back as base64. The address is a placeholder that is replaced with the swap's lock address before you are asked to sign",
+            );
+
+            template
+        }
+        Request::Sign(funded) => {
+            tracing::info!(
+                "The seller signed the refund transaction. Sign and finalize the following PSBT with your external wallet, then paste it back as base64 within a few minutes, after that the seller stops waiting for the lock transaction",
+            );
+
+            funded
+        }
+    };
+    println!("{}", psbt_to_base64(&psbt));
+
+    read_psbt(lines).await
+}
+
+/// Reads lines until one of them holds a valid PSBT.
+///
+/// A typo or a partial paste must not abort the swap, the user is asked to
+/// paste the PSBT again instead.
+async fn read_psbt(
+    lines: &mut mpsc::UnboundedReceiver<String>,
+) -> Result<PartiallySignedTransaction> {
+    loop {
+        let line = lines.recv().await.context("Stdin was closed")?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match psbt_from_base64(line) {
+            Ok(psbt) => return Ok(psbt),
+            Err(error) => {
+                tracing::warn!("Invalid PSBT, please paste it again as base64: {:#}", error)
+            }
+        }
+    }
+}
+
+fn first_output(psbt: &PartiallySignedTransaction, network: Network) -> Result<(Address, Amount)> {
+    let output = psbt
+        .global
+        .unsigned_tx
+        .output
+        .first()
+        .context("Lock transaction has no output")?;
+    let address = Address::from_script(&output.script_pubkey, network)
+        .context("Failed to derive address of lock output")?;
+
+    Ok((address, Amount::from_sat(output.value)))
+}
+
+pub fn psbt_to_base64(psbt: &PartiallySignedTransaction) -> String {
+    base64::encode(serialize(psbt))
+}
+
+pub fn psbt_from_base64(encoded: &str) -> Result<PartiallySignedTransaction> {
+    let bytes = base64::decode(encoded).context("PSBT is not valid base64")?;
+    let psbt = deserialize(&bytes).context("Failed to deserialize PSBT")?;
+
+    Ok(psbt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{ExternallyFundedTxLock, WalletBuilder};
+
+    #[tokio::test]
+    async fn psbt_base64_roundtrip() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let placeholder = wallet.new_address().await.unwrap();
+        let psbt =
+            ExternallyFundedTxLock::template(Amount::from_sat(10_000), &placeholder).unwrap();
+
+        let decoded = psbt_from_base64(&psbt_to_base64(&psbt)).unwrap();
+
+        assert_eq!(decoded, psbt);
+    }
+
+    #[test]
+    fn given_garbage_then_psbt_from_base64_fails() {
+        assert!(psbt_from_base64("not a psbt").is_err());
+    }
+
+    #[tokio::test]
+    async fn given_invalid_line_then_waits_for_valid_psbt() {
+        let wallet = WalletBuilder::new(50_000).build();
+        let placeholder = wallet.new_address().await.unwrap();
+        let psbt =
+            ExternallyFundedTxLock::template(Amount::from_sat(10_000), &placeholder).unwrap();
+        let (sender, mut lines) = mpsc::unbounded_channel();
+
+        sender.send("not a psbt".to_owned()).unwrap();
+        sender.send("".to_owned()).unwrap();
+        sender.send(psbt_to_base64(&psbt)).unwrap();
+
+        let read = read_psbt(&mut lines).await.unwrap();
+
+        assert_eq!(read, psbt);
+    }
+
+    #[tokio::test]
+    async fn given_stdin_closed_then_read_psbt_fails() {
+        let (sender, mut lines) = mpsc::unbounded_channel::<String>();
+
+        sender.send("not a psbt".to_owned()).unwrap();
+        drop(sender);
+
+        assert!(read_psbt(&mut lines).await.is_err());
+    }
+}
@@ -1,8 +1,9 @@
+use crate::libp2p_ext::MultiAddrExt;
 use crate::network::quote::BidQuote;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::network::{quote, swarm};
 use anyhow::{Context, Result};
-use futures::StreamExt;
+use futures::{future, StreamExt};
 use libp2p::multiaddr::Protocol;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
 use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
@@ -12,15 +13,73 @@ use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Returns sorted list of sellers, with [Online](Status::Online) listed first.
 ///
-/// First uses the rendezvous node to discover peers in the given namespace,
-/// then fetches a quote from each peer that was discovered. If fetching a quote
-/// from a discovered peer fails the seller's status will be
-/// [Unreachable](Status::Unreachable).
+/// Queries all rendezvous points concurrently to discover peers in the given
+/// namespace, then fetches a quote from each peer that was discovered. If
+/// fetching a quote from a discovered peer fails the seller's status will be
+/// [Unreachable](Status::Unreachable). Sellers registered with several
+/// rendezvous points are only listed once.
 pub async fn list_sellers(
+    rendezvous_points: Vec<(PeerId, Multiaddr)>,
+    namespace: XmrBtcNamespace,
+    tor_socks5_port: u16,
+    identity: identity::Keypair,
+) -> Result<Vec<Seller>> {
+    let results = future::join_all(rendezvous_points.into_iter().map(
+        |(rendezvous_node_peer_id, rendezvous_node_addr)| {
+            list_sellers_at(
+                rendezvous_node_peer_id,
+                rendezvous_node_addr,
+                namespace,
+                tor_socks5_port,
+                identity.clone(),
+            )
+        },
+    ))
+    .await;
+
+    let mut sellers = HashMap::<PeerId, Seller>::new();
+    for result in results {
+        let discovered = match result {
+            Ok(discovered) => discovered,
+            Err(error) => {
+                tracing::warn!("Failed to query rendezvous point: {:#}", error);
+                continue;
+            }
+        };
+
+        for seller in discovered {
+            let peer_id = match seller.multiaddr.extract_peer_id() {
+                Some(peer_id) => peer_id,
+                None => continue,
+            };
+
+            match sellers.entry(peer_id) {
+                Entry::Occupied(mut entry) => {
+                    if entry.get().status == Status::Unreachable {
+                        entry.insert(seller);
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(seller);
+                }
+            }
+        }
+    }
+
+    let mut sellers = sellers
+        .into_iter()
+        .map(|(_, seller)| seller)
+        .collect::<Vec<_>>();
+    sellers.sort();
+
+    Ok(sellers)
+}
+
+async fn list_sellers_at(
     rendezvous_node_peer_id: PeerId,
     rendezvous_node_addr: Multiaddr,
     namespace: XmrBtcNamespace,
@@ -63,6 +122,8 @@ pub struct Seller {
     pub status: Status,
     #[serde_as(as = "DisplayFromStr")]
     pub multiaddr: Multiaddr,
+    /// How long the seller took to answer our quote request.
+    pub latency: Option<Duration>,
 }
 
 #[derive(Debug, Serialize, PartialEq, Eq, Hash, Copy, Clone, Ord, PartialOrd)]
@@ -119,6 +180,8 @@ struct EventLoop {
     reachable_asb_address: HashMap<PeerId, Multiaddr>,
     unreachable_asb_address: HashMap<PeerId, Multiaddr>,
     asb_quote_status: HashMap<PeerId, QuoteStatus>,
+    quote_requested_at: HashMap<PeerId, Instant>,
+    asb_latency: HashMap<PeerId, Duration>,
     state: State,
 }
 
@@ -137,6 +200,8 @@ impl EventLoop {
             reachable_asb_address: Default::default(),
             unreachable_asb_address: Default::default(),
             asb_quote_status: Default::default(),
+            quote_requested_at: Default::default(),
+            asb_latency: Default::default(),
             state: State::WaitForDiscovery,
         }
     }
@@ -219,6 +284,7 @@ impl EventLoop {
 
                                 // request the quote, if we are not connected to the peer it will be dialed automatically
                                 let _request_id = self.swarm.behaviour_mut().quote.send_request(&peer, ());
+                                self.quote_requested_at.insert(peer, Instant::now());
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::Quote(quote_response)) => {
@@ -226,6 +292,10 @@ impl EventLoop {
                                 RequestResponseEvent::Message { peer, message } => {
                                     match message {
                                         RequestResponseMessage::Response { response, .. } => {
+                                            if let Some(requested_at) = self.quote_requested_at.remove(&peer) {
+                                                self.asb_latency.insert(peer, requested_at.elapsed());
+                                            }
+
                                             if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response))).is_none() {
                                                 tracing::error!(%peer, "Received bid quote from unexpected peer, this record will be removed!");
                                                 self.asb_quote_status.remove(&peer);
@@ -275,8 +345,9 @@ impl EventLoop {
                                     .expect("if we got a quote we must have stored an address");
 
                                 Ok(Seller {
-                                    multiaddr: address.clone(),
+                                    multiaddr: with_peer_id(address, *peer_id),
                                     status: Status::Online(*quote),
+                                    latency: self.asb_latency.get(peer_id).copied(),
                                 })
                             }
                             QuoteStatus::Received(Status::Unreachable) => {
@@ -286,8 +357,9 @@ impl EventLoop {
                                     .expect("if we got a quote we must have stored an address");
 
                                 Ok(Seller {
-                                    multiaddr: address.clone(),
+                                    multiaddr: with_peer_id(address, *peer_id),
                                     status: Status::Unreachable,
+                                    latency: None,
                                 })
                             }
                         })
@@ -309,6 +381,17 @@ impl EventLoop {
 #[derive(Debug)]
 struct StillPending {}
 
+/// Appends the peer ID to the address so it can be passed as `--seller`.
+fn with_peer_id(address: &Multiaddr, peer_id: PeerId) -> Multiaddr {
+    let p2p_suffix = Protocol::P2p(peer_id.into());
+
+    if address.ends_with(&Multiaddr::empty().with(p2p_suffix.clone())) {
+        address.clone()
+    } else {
+        address.clone().with(p2p_suffix)
+    }
+}
+
 impl From<PingEvent> for OutEvent {
     fn from(event: PingEvent) -> Self {
         OutEvent::Ping(event)
@@ -325,10 +408,12 @@ mod tests {
             Seller {
                 multiaddr: "/ip4/127.0.0.1/tcp/1234".parse().unwrap(),
                 status: Status::Unreachable,
+                latency: None,
             },
             Seller {
                 multiaddr: Multiaddr::empty(),
                 status: Status::Unreachable,
+                latency: None,
             },
             Seller {
                 multiaddr: "/ip4/127.0.0.1/tcp/5678".parse().unwrap(),
@@ -337,6 +422,7 @@ mod tests {
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
                 }),
+                latency: None,
             },
         ];
 
@@ -349,15 +435,18 @@ mod tests {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
-                })
+                }),
+                latency: None,
             },
             Seller {
                 multiaddr: Multiaddr::empty(),
-                status: Status::Unreachable
+                status: Status::Unreachable,
+                latency: None,
             },
             Seller {
                 multiaddr: "/ip4/127.0.0.1/tcp/1234".parse().unwrap(),
-                status: Status::Unreachable
+                status: Status::Unreachable,
+                latency: None,
             },
         ])
     }
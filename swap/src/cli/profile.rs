@@ -0,0 +1,135 @@
+//! Named profiles give the CLI isolated identities on one machine.
+//!
+//! Each profile lives in its own directory below the base data directory and
+//! holds its own seed, database, Bitcoin wallet and judecoin wallet files.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROFILES_DIR: &str = "profiles";
+
+/// The directory of the profile with the given name.
+pub fn dir(base_dir: &Path, name: &str) -> PathBuf {
+    base_dir.join(PROFILES_DIR).join(name)
+}
+
+pub fn parse_name(name: &str) -> Result<String> {
+    let is_valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !is_valid {
+        bail!(
+            "Invalid profile name {:?}, only letters, digits, '-' and '_' are allowed",
+            name
+        )
+    }
+
+    Ok(name.to_string())
+}
+
+pub fn list(base_dir: &Path) -> Result<Vec<String>> {
+    let profiles_dir = base_dir.join(PROFILES_DIR);
+
+    if !profiles_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = fs::read_dir(&profiles_dir)
+        .with_context(|| format!("Failed to read {}", profiles_dir.display()))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+
+            if !entry.file_type().ok()?.is_dir() {
+                return None;
+            }
+
+            entry.file_name().into_string().ok()
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+
+    Ok(names)
+}
+
+pub fn create(base_dir: &Path, name: &str) -> Result<PathBuf> {
+    let dir = dir(base_dir, name);
+
+    if dir.exists() {
+        bail!("Profile {} already exists", name)
+    }
+
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    Ok(dir)
+}
+
+/// Deletes the profile with the given name.
+///
+/// A profile that holds a seed may still control funds, it is only deleted if
+/// `force` is set.
+pub fn delete(base_dir: &Path, name: &str, force: bool) -> Result<()> {
+    let dir = dir(base_dir, name);
+
+    if !dir.exists() {
+        bail!("Profile {} does not exist", name)
+    }
+
+    let has_seed = ["mainnet", "testnet"]
+        .iter()
+        .any(|network| dir.join(network).join("seed.pem").exists());
+
+    if has_seed && !force {
+        bail!(
+            "Profile {} contains a seed, back it up with `swap --profile {} seed export` and pass --force to delete it",
+            name,
+            name
+        )
+    }
+
+    fs::remove_dir_all(&dir).with_context(|| format!("Failed to delete {}", dir.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn only_simple_profile_names_are_allowed() {
+        assert!(parse_name("savings").is_ok());
+        assert!(parse_name("cold_storage-2").is_ok());
+
+        assert!(parse_name("").is_err());
+        assert!(parse_name("../mainnet").is_err());
+        assert!(parse_name("with space").is_err());
+    }
+
+    #[test]
+    fn created_profiles_are_listed() {
+        let base_dir = tempdir().unwrap();
+
+        create(base_dir.path(), "savings").unwrap();
+        create(base_dir.path(), "daily").unwrap();
+
+        assert_eq!(list(base_dir.path()).unwrap(), vec!["daily", "savings"]);
+        assert!(create(base_dir.path(), "daily").is_err());
+    }
+
+    #[test]
+    fn profile_with_seed_is_only_deleted_with_force() {
+        let base_dir = tempdir().unwrap();
+        let dir = create(base_dir.path(), "savings").unwrap();
+        fs::create_dir_all(dir.join("mainnet")).unwrap();
+        fs::write(dir.join("mainnet").join("seed.pem"), "").unwrap();
+
+        assert!(delete(base_dir.path(), "savings", false).is_err());
+        delete(base_dir.path(), "savings", true).unwrap();
+
+        assert!(list(base_dir.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::bitcoin;
+use crate::cli::list_sellers::{Seller, Status};
+use crate::cli::seller_selection::Reliability;
+use crate::libp2p_ext::MultiAddrExt;
+use crate::network::quote::BidQuote;
+use anyhow::Result;
+use async_trait::async_trait;
+use libp2p::{Multiaddr, PeerId};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// How many quotes are kept per seller, older ones are pruned.
+pub const MAX_QUOTES_PER_SELLER: u32 = 100;
+
+/// Stores what the CLI learned about sellers during discovery.
+#[async_trait]
+pub trait SellerCacheDatabase {
+    async fn insert_seen_address(&self, seen: SeenAddress) -> Result<()>;
+    async fn all_seen_addresses(&self) -> Result<Vec<SeenAddress>>;
+    async fn insert_seller_quote(&self, quote: SellerQuote) -> Result<()>;
+    /// Removes all but the `keep` most recent quotes of the seller.
+    async fn prune_seller_quotes(&self, peer_id: PeerId, keep: u32) -> Result<()>;
+    async fn all_seller_quotes(&self) -> Result<Vec<SellerQuote>>;
+}
+
+/// An address a seller was discovered at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeenAddress {
+    pub peer_id: PeerId,
+    pub address: Multiaddr,
+    /// When the seller last answered at this address, if it ever did.
+    pub last_seen: Option<OffsetDateTime>,
+}
+
+/// A quote a seller gave us during discovery.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SellerQuote {
+    pub peer_id: PeerId,
+    pub quote: BidQuote,
+    pub latency: Option<Duration>,
+    pub received_at: OffsetDateTime,
+}
+
+/// What we remember about a seller from earlier discoveries and swaps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedSeller {
+    /// The address the seller was last seen at.
+    pub multiaddr: Multiaddr,
+    pub last_seen: Option<OffsetDateTime>,
+    /// All quotes received from the seller, oldest first.
+    pub quotes: Vec<SellerQuote>,
+    pub reliability: Reliability,
+}
+
+impl CachedSeller {
+    pub fn latest_quote(&self) -> Option<&SellerQuote> {
+        self.quotes.last()
+    }
+
+    /// The lowest and highest price the seller quoted so far.
+    pub fn price_range(&self) -> Option<(bitcoin::Amount, bitcoin::Amount)> {
+        let prices = self.quotes.iter().map(|quote| quote.quote.price);
+
+        Some((prices.clone().min()?, prices.max()?))
+    }
+}
+
+/// Stores the addresses and quotes of freshly discovered sellers.
+///
+/// Unreachable sellers are remembered too, but keep the time they were last
+/// seen online. Only the last [`MAX_QUOTES_PER_SELLER`] quotes of a seller are
+/// kept.
+pub async fn remember_sellers(
+    db: &(dyn SellerCacheDatabase + Send + Sync),
+    sellers: &[Seller],
+    now: OffsetDateTime,
+) -> Result<()> {
+    for seller in sellers {
+        let peer_id = match seller.multiaddr.extract_peer_id() {
+            Some(peer_id) => peer_id,
+            None => continue,
+        };
+
+        let last_seen = match seller.status {
+            Status::Online(quote) => {
+                db.insert_seller_quote(SellerQuote {
+                    peer_id,
+                    quote,
+                    latency: seller.latency,
+                    received_at: now,
+                })
+                .await?;
+                db.prune_seller_quotes(peer_id, MAX_QUOTES_PER_SELLER)
+                    .await?;
+
+                Some(now)
+            }
+            Status::Unreachable => None,
+        };
+
+        db.insert_seen_address(SeenAddress {
+            peer_id,
+            address: seller.multiaddr.clone(),
+            last_seen,
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Returns every seller we know of, most recently seen first.
+///
+/// Works without network access, only the database is consulted.
+pub async fn cached_sellers(
+    db: &(dyn SellerCacheDatabase + Send + Sync),
+    reliability: &HashMap<PeerId, Reliability>,
+) -> Result<Vec<CachedSeller>> {
+    let mut quotes = HashMap::<PeerId, Vec<SellerQuote>>::new();
+    for quote in db.all_seller_quotes().await? {
+        quotes.entry(quote.peer_id).or_default().push(quote);
+    }
+
+    let mut sellers = HashMap::<PeerId, CachedSeller>::new();
+    for seen in db.all_seen_addresses().await? {
+        match sellers.entry(seen.peer_id) {
+            Entry::Occupied(mut entry) => {
+                let seller = entry.get_mut();
+
+                if seen.last_seen > seller.last_seen {
+                    seller.multiaddr = seen.address;
+                    seller.last_seen = seen.last_seen;
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(CachedSeller {
+                    multiaddr: seen.address,
+                    last_seen: seen.last_seen,
+                    quotes: quotes.remove(&seen.peer_id).unwrap_or_default(),
+                    reliability: reliability.get(&seen.peer_id).copied().unwrap_or_default(),
+                });
+            }
+        }
+    }
+
+    let mut sellers = sellers
+        .into_iter()
+        .map(|(_, seller)| seller)
+        .collect::<Vec<_>>();
+    sellers.sort_by_key(|seller| Reverse(seller.last_seen));
+
+    Ok(sellers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SqliteDatabase;
+    use libp2p::multiaddr::Protocol;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn unreachable_seller_keeps_last_seen_time_and_quotes() {
+        let db = setup_test_db().await;
+        let peer_id = PeerId::random();
+        let multiaddr = "/ip4/127.0.0.1/tcp/9939"
+            .parse::<Multiaddr>()
+            .unwrap()
+            .with(Protocol::P2p(peer_id.into()));
+        let quote = BidQuote {
+            price: bitcoin::Amount::from_sat(1_000),
+            min_quantity: bitcoin::Amount::from_sat(100),
+            max_quantity: bitcoin::Amount::from_sat(100_000),
+        };
+        let online_at = OffsetDateTime::from_unix_timestamp(1_640_000_000).unwrap();
+        let unreachable_at = OffsetDateTime::from_unix_timestamp(1_640_003_600).unwrap();
+
+        remember_sellers(
+            &db,
+            &[Seller {
+                status: Status::Online(quote),
+                multiaddr: multiaddr.clone(),
+                latency: Some(Duration::from_millis(250)),
+            }],
+            online_at,
+        )
+        .await
+        .unwrap();
+        remember_sellers(
+            &db,
+            &[Seller {
+                status: Status::Unreachable,
+                multiaddr: multiaddr.clone(),
+                latency: None,
+            }],
+            unreachable_at,
+        )
+        .await
+        .unwrap();
+
+        let sellers = cached_sellers(&db, &HashMap::new()).await.unwrap();
+
+        assert_eq!(sellers, vec![CachedSeller {
+            multiaddr,
+            last_seen: Some(online_at),
+            quotes: vec![SellerQuote {
+                peer_id,
+                quote,
+                latency: Some(Duration::from_millis(250)),
+                received_at: online_at,
+            }],
+            reliability: Reliability::default(),
+        }]);
+    }
+
+    async fn setup_test_db() -> SqliteDatabase {
+        let temp_db = tempdir().unwrap().into_path().join("tempdb");
+
+        // file has to exist in order to connect with sqlite
+        File::create(temp_db.clone()).unwrap();
+
+        SqliteDatabase::open(temp_db).await.unwrap()
+    }
+}
@@ -0,0 +1,214 @@
+use crate::bitcoin;
+use crate::cli::command::SwapAmount;
+use crate::cli::list_sellers::{Seller, Status};
+use crate::libp2p_ext::MultiAddrExt;
+use crate::network::quote::BidQuote;
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use anyhow::Result;
+use libp2p::{Multiaddr, PeerId};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// Outcomes of our past swaps with a seller.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Reliability {
+    pub completed: u32,
+    pub refunded: u32,
+}
+
+impl Reliability {
+    /// The share of completed swaps in percent, `None` if we never finished a
+    /// swap with the seller.
+    pub fn score(&self) -> Option<u32> {
+        let finished = self.completed + self.refunded;
+
+        (finished > 0).then(|| self.completed * 100 / finished)
+    }
+
+    fn is_doubtful(&self) -> bool {
+        self.refunded > self.completed
+    }
+}
+
+/// Counts completed and refunded swaps per seller.
+///
+/// Punished and safely aborted swaps are not counted, they are caused by us
+/// rather than the seller.
+pub async fn seller_reliability(
+    db: &(dyn Database + Send + Sync),
+) -> Result<HashMap<PeerId, Reliability>> {
+    let mut reliability = HashMap::<PeerId, Reliability>::new();
+
+    for (swap_id, state) in db.all().await? {
+        let state: BobState = match state.try_into() {
+            Ok(state) => state,
+            Err(_) => continue,
+        };
+        let peer_id = match db.get_peer_id(swap_id).await {
+            Ok(peer_id) => peer_id,
+            Err(_) => continue,
+        };
+
+        match state {
+            BobState::XmrRedeemed { .. } => reliability.entry(peer_id).or_default().completed += 1,
+            BobState::BtcRefunded(..) => reliability.entry(peer_id).or_default().refunded += 1,
+            _ => {}
+        }
+    }
+
+    Ok(reliability)
+}
+
+/// Orders the online sellers that accept the swap, best first.
+///
+/// Sellers are ranked by price, but sellers with more refunded than completed
+/// swaps come last. Among equally priced sellers, the one we completed more
+/// swaps with is preferred.
+pub fn rank_sellers(
+    sellers: Vec<Seller>,
+    amount: Option<SwapAmount>,
+    max_price: Option<bitcoin::Amount>,
+    reliability: &HashMap<PeerId, Reliability>,
+) -> Vec<Multiaddr> {
+    let mut candidates = sellers
+        .into_iter()
+        .filter_map(|seller| {
+            let quote = match seller.status {
+                Status::Online(quote) => quote,
+                Status::Unreachable => return None,
+            };
+            let peer_id = seller.multiaddr.extract_peer_id()?;
+
+            if !accepts(&quote, amount, max_price) {
+                return None;
+            }
+
+            let reliability = reliability.get(&peer_id).copied().unwrap_or_default();
+
+            Some((reliability, quote.price, seller.multiaddr))
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_by_key(|(reliability, price, _)| {
+        (
+            reliability.is_doubtful(),
+            *price,
+            Reverse(reliability.completed),
+        )
+    });
+
+    candidates
+        .into_iter()
+        .map(|(_, _, multiaddr)| multiaddr)
+        .collect()
+}
+
+fn accepts(
+    quote: &BidQuote,
+    amount: Option<SwapAmount>,
+    max_price: Option<bitcoin::Amount>,
+) -> bool {
+    if max_price.map_or(false, |max_price| quote.price > max_price) {
+        return false;
+    }
+
+    match amount {
+        Some(amount) => {
+            let btc = amount.btc(quote.price);
+
+            quote.min_quantity <= btc && btc <= quote.max_quantity
+        }
+        None => quote.max_quantity > bitcoin::Amount::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::judecoin;
+    use libp2p::multiaddr::Protocol;
+
+    #[test]
+    fn ranks_online_sellers_by_price() {
+        let cheap = address();
+        let expensive = address();
+        let sellers = vec![
+            online(&expensive, 2_000, 100_000),
+            Seller {
+                multiaddr: address(),
+                status: Status::Unreachable,
+                latency: None,
+            },
+            online(&cheap, 1_000, 100_000),
+        ];
+
+        let ranked = rank_sellers(sellers, None, None, &HashMap::new());
+
+        assert_eq!(ranked, vec![cheap, expensive]);
+    }
+
+    #[test]
+    fn sellers_not_accepting_the_amount_or_price_are_skipped() {
+        let too_small = address();
+        let too_expensive = address();
+        let fitting = address();
+        let sellers = vec![
+            online(&too_small, 1_000, 1_500),
+            online(&too_expensive, 5_000, 100_000),
+            online(&fitting, 2_000, 100_000),
+        ];
+        let two_jude = judecoin::Amount::parse_judecoin("2").unwrap();
+
+        let ranked = rank_sellers(
+            sellers,
+            Some(SwapAmount::Jude(two_jude)),
+            Some(bitcoin::Amount::from_sat(3_000)),
+            &HashMap::new(),
+        );
+
+        assert_eq!(ranked, vec![fitting]);
+    }
+
+    #[test]
+    fn sellers_with_mostly_refunded_swaps_come_last() {
+        let cheap = address();
+        let expensive = address();
+        let sellers = vec![
+            online(&cheap, 1_000, 100_000),
+            online(&expensive, 2_000, 100_000),
+        ];
+        let mut reliability = HashMap::new();
+        reliability.insert(
+            cheap.extract_peer_id().unwrap(),
+            Reliability {
+                completed: 1,
+                refunded: 2,
+            },
+        );
+
+        let ranked = rank_sellers(sellers, None, None, &reliability);
+
+        assert_eq!(ranked, vec![expensive, cheap]);
+    }
+
+    fn address() -> Multiaddr {
+        "/ip4/127.0.0.1/tcp/9939"
+            .parse::<Multiaddr>()
+            .unwrap()
+            .with(Protocol::P2p(PeerId::random().into()))
+    }
+
+    fn online(multiaddr: &Multiaddr, price: u64, max_quantity: u64) -> Seller {
+        Seller {
+            multiaddr: multiaddr.clone(),
+            status: Status::Online(BidQuote {
+                price: bitcoin::Amount::from_sat(price),
+                min_quantity: bitcoin::Amount::from_sat(100),
+                max_quantity: bitcoin::Amount::from_sat(max_quantity),
+            }),
+            latency: None,
+        }
+    }
+}
@@ -3,7 +3,7 @@ pub use bob::Bob;
 pub use sqlite::SqliteDatabase;
 
 use crate::fs::ensure_directory_exists;
-use crate::protocol::{Database, State};
+use crate::protocol::State;
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
@@ -83,7 +83,7 @@ impl Swap {
     }
 }
 
-pub async fn open_db(sqlite_path: impl AsRef<Path>) -> Result<Arc<dyn Database + Send + Sync>> {
+pub async fn open_db(sqlite_path: impl AsRef<Path>) -> Result<Arc<SqliteDatabase>> {
     if sqlite_path.as_ref().exists() {
         tracing::debug!("Using existing sqlite database.");
         let sqlite = SqliteDatabase::open(sqlite_path).await?;
@@ -1,4 +1,6 @@
+use crate::bitcoin::CoinControl;
 use crate::judecoin::TransferProof;
+use crate::network::swap_setup::bob::PriceProtection;
 use crate::protocol::bob;
 use crate::protocol::bob::BobState;
 use judecoin_rpc::wallet::BlockHeight;
@@ -14,6 +16,12 @@ pub enum Bob {
         btc_amount: bitcoin::Amount,
         #[serde_as(as = "DisplayFromStr")]
         change_address: bitcoin::Address,
+        #[serde(default)]
+        coin_control: CoinControl,
+        #[serde(default)]
+        external_funding: bool,
+        #[serde(default)]
+        price_protection: PriceProtection,
     },
     ExecutionSetupDone {
         state2: bob::State2,
@@ -53,9 +61,15 @@ impl From<BobState> for Bob {
             BobState::Started {
                 btc_amount,
                 change_address,
+                coin_control,
+                external_funding,
+                price_protection,
             } => Bob::Started {
                 btc_amount,
                 change_address,
+                coin_control,
+                external_funding,
+                price_protection,
             },
             BobState::SwapSetupCompleted(state2) => Bob::ExecutionSetupDone { state2 },
             BobState::BtcLocked {
@@ -97,9 +111,15 @@ impl From<Bob> for BobState {
             Bob::Started {
                 btc_amount,
                 change_address,
+                coin_control,
+                external_funding,
+                price_protection,
             } => BobState::Started {
                 btc_amount,
                 change_address,
+                coin_control,
+                external_funding,
+                price_protection,
             },
             Bob::ExecutionSetupDone { state2 } => BobState::SwapSetupCompleted(state2),
             Bob::BtcLocked {
@@ -1,13 +1,19 @@
+use crate::asb::sweep::{Sweep, SweepDatabase};
+use crate::bitcoin;
+use crate::cli::seller_cache::{SeenAddress, SellerCacheDatabase, SellerQuote};
 use crate::database::Swap;
 use crate::judecoin::Address;
-use crate::protocol::{Database, State};
+use crate::network::quote::BidQuote;
+use crate::protocol::{Database, PendingMessage, State};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use libp2p::{Multiaddr, PeerId};
 use sqlx::sqlite::Sqlite;
 use sqlx::{Pool, SqlitePool};
+use std::convert::TryFrom;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -120,6 +126,33 @@ impl Database for SqliteDatabase {
         Ok(address)
     }
 
+    async fn forget_swap(&self, swap_id: Uuid) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM peers
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        DELETE FROM judecoin_addresses
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
 
@@ -220,6 +253,50 @@ impl Database for SqliteDatabase {
         Ok(swap.into())
     }
 
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<State>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let rows = sqlx::query!(
+            r#"
+           SELECT state
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let swap: Swap = serde_json::from_str(&row.state)?;
+                Ok(State::from(swap))
+            })
+            .collect()
+    }
+
+    async fn all_states(&self) -> Result<Vec<(Uuid, State)>> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+           SELECT swap_id, state
+           FROM swap_states
+           ORDER BY id
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let swap_id = Uuid::from_str(&row.swap_id)?;
+                let swap: Swap = serde_json::from_str(&row.state)?;
+                Ok((swap_id, State::from(swap)))
+            })
+            .collect()
+    }
+
     async fn all(&self) -> Result<Vec<(Uuid, State)>> {
         let mut conn = self.pool.acquire().await?;
         let rows = sqlx::query!(
@@ -249,13 +326,300 @@ impl Database for SqliteDatabase {
 
         result
     }
+
+    async fn insert_pending_message(&self, peer_id: PeerId, message: PendingMessage) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = message.swap_id().to_string();
+        let peer_id = peer_id.to_string();
+        let message = serde_json::to_string(&message)?;
+
+        sqlx::query!(
+            r#"
+        insert or replace into pending_messages (
+            swap_id,
+            peer_id,
+            message
+            ) values (?, ?, ?);
+        "#,
+            swap_id,
+            peer_id,
+            message
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_pending_messages(&self, peer_id: PeerId) -> Result<Vec<PendingMessage>> {
+        let mut conn = self.pool.acquire().await?;
+        let peer_id = peer_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT message
+        FROM pending_messages
+        WHERE peer_id = ?
+        ORDER BY id
+        "#,
+            peer_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(&row.message)?))
+            .collect()
+    }
+
+    async fn remove_pending_message(&self, swap_id: Uuid) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM pending_messages
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SweepDatabase for SqliteDatabase {
+    async fn insert_sweep(&self, sweep: Sweep) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let txid = sweep.txid.to_string();
+        let amount = i64::try_from(sweep.amount.as_sat())?;
+        let address = sweep.address.to_string();
+        let swept_at = sweep.swept_at.unix_timestamp();
+
+        sqlx::query!(
+            r#"
+        insert into bitcoin_sweeps (
+            txid,
+            amount,
+            address,
+            swept_at
+            ) values (?, ?, ?, ?);
+        "#,
+            txid,
+            amount,
+            address,
+            swept_at
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn all_sweeps(&self) -> Result<Vec<Sweep>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT txid, amount, address, swept_at
+        FROM bitcoin_sweeps
+        ORDER BY id
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(Sweep {
+                    txid: bitcoin::Txid::from_str(&row.txid)?,
+                    amount: bitcoin::Amount::from_sat(u64::try_from(row.amount)?),
+                    address: bitcoin::Address::from_str(&row.address)?,
+                    swept_at: OffsetDateTime::from_unix_timestamp(row.swept_at)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl SellerCacheDatabase for SqliteDatabase {
+    async fn insert_seen_address(&self, seen: SeenAddress) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = seen.peer_id.to_string();
+        let address = seen.address.to_string();
+        let last_seen = seen.last_seen.map(|last_seen| last_seen.unix_timestamp());
+
+        // an address we were never able to reach keeps its last seen time
+        let updated = sqlx::query!(
+            r#"
+        UPDATE peer_addresses
+        SET last_seen = COALESCE(?, last_seen)
+        WHERE peer_id = ? AND address = ?
+        "#,
+            last_seen,
+            peer_id,
+            address
+        )
+        .execute(&mut conn)
+        .await?
+        .rows_affected();
+
+        if updated == 0 {
+            sqlx::query!(
+                r#"
+            insert into peer_addresses (
+                peer_id,
+                address,
+                last_seen
+                ) values (?, ?, ?);
+            "#,
+                peer_id,
+                address,
+                last_seen
+            )
+            .execute(&mut conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn all_seen_addresses(&self) -> Result<Vec<SeenAddress>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT peer_id, address, last_seen
+        FROM peer_addresses
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(SeenAddress {
+                    peer_id: PeerId::from_str(&row.peer_id)?,
+                    address: Multiaddr::from_str(&row.address)?,
+                    last_seen: row
+                        .last_seen
+                        .map(OffsetDateTime::from_unix_timestamp)
+                        .transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_seller_quote(&self, quote: SellerQuote) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = quote.peer_id.to_string();
+        let price = i64::try_from(quote.quote.price.as_sat())?;
+        let min_quantity = i64::try_from(quote.quote.min_quantity.as_sat())?;
+        let max_quantity = i64::try_from(quote.quote.max_quantity.as_sat())?;
+        let latency_ms = quote
+            .latency
+            .map(|latency| i64::try_from(latency.as_millis()))
+            .transpose()?;
+        let received_at = quote.received_at.unix_timestamp();
+
+        sqlx::query!(
+            r#"
+        insert into seller_quotes (
+            peer_id,
+            price,
+            min_quantity,
+            max_quantity,
+            latency_ms,
+            received_at
+            ) values (?, ?, ?, ?, ?, ?);
+        "#,
+            peer_id,
+            price,
+            min_quantity,
+            max_quantity,
+            latency_ms,
+            received_at
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn prune_seller_quotes(&self, peer_id: PeerId, keep: u32) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM seller_quotes
+        WHERE peer_id = ? AND id NOT IN (
+            SELECT id FROM seller_quotes
+            WHERE peer_id = ?
+            ORDER BY id DESC
+            LIMIT ?
+        )
+        "#,
+            peer_id,
+            peer_id,
+            keep
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn all_seller_quotes(&self) -> Result<Vec<SellerQuote>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT peer_id, price, min_quantity, max_quantity, latency_ms, received_at
+        FROM seller_quotes
+        ORDER BY id
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(SellerQuote {
+                    peer_id: PeerId::from_str(&row.peer_id)?,
+                    quote: BidQuote {
+                        price: bitcoin::Amount::from_sat(u64::try_from(row.price)?),
+                        min_quantity: bitcoin::Amount::from_sat(u64::try_from(row.min_quantity)?),
+                        max_quantity: bitcoin::Amount::from_sat(u64::try_from(row.max_quantity)?),
+                    },
+                    latency: row
+                        .latency_ms
+                        .map(|latency_ms| u64::try_from(latency_ms).map(Duration::from_millis))
+                        .transpose()?,
+                    received_at: OffsetDateTime::from_unix_timestamp(row.received_at)?,
+                })
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::judecoin;
+    use crate::network::transfer_proof;
     use crate::protocol::alice::AliceState;
     use crate::protocol::bob::BobState;
+    use rand::rngs::OsRng;
     use std::fs::File;
     use tempfile::tempdir;
 
@@ -314,6 +678,59 @@ mod tests {
         assert!(!latest_loaded.contains(&(swap_id_1, state_2)));
     }
 
+    #[tokio::test]
+    async fn test_retrieve_all_states_of_swap() {
+        let db = setup_test_db().await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        let state_2 = State::Alice(AliceState::BtcPunished);
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, state_1.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, state_2.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_2, State::Bob(BobState::SafelyAborted))
+            .await
+            .unwrap();
+
+        let states = db.get_states(swap_id_1).await.unwrap();
+
+        assert_eq!(states, vec![state_1, state_2]);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_all_states_of_all_swaps() {
+        let db = setup_test_db().await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        let state_2 = State::Bob(BobState::SafelyAborted);
+        let state_3 = State::Alice(AliceState::BtcPunished);
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, state_1.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_2, state_2.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, state_3.clone())
+            .await
+            .unwrap();
+
+        let states = db.all_states().await.unwrap();
+
+        assert_eq!(states, vec![
+            (swap_id_1, state_1),
+            (swap_id_2, state_2),
+            (swap_id_1, state_3)
+        ]);
+    }
+
     #[tokio::test]
     async fn test_insert_load_judecoin_address() -> Result<()> {
         let db = setup_test_db().await?;
@@ -350,6 +767,28 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_forget_swap_allows_reusing_its_id() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let judecoin_address = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?;
+
+        db.insert_peer_id(swap_id, PeerId::random()).await?;
+        db.insert_judecoin_address(swap_id, judecoin_address).await?;
+
+        db.forget_swap(swap_id).await?;
+        assert!(db.get_peer_id(swap_id).await.is_err());
+        assert!(db.get_judecoin_address(swap_id).await.is_err());
+
+        let peer_id = PeerId::random();
+        db.insert_peer_id(swap_id, peer_id).await?;
+        db.insert_judecoin_address(swap_id, judecoin_address).await?;
+        assert_eq!(db.get_peer_id(swap_id).await?, peer_id);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_insert_and_load_peer_id() -> Result<()> {
         let db = setup_test_db().await?;
@@ -370,6 +809,152 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_insert_and_load_sweeps() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let sweep = Sweep {
+            txid: bitcoin::Txid::from_str(
+                "ea030832a3be9454fbb985ea9a788406ea030832a3be9454fbb985ea9a788406",
+            )?,
+            amount: bitcoin::Amount::from_sat(50_000),
+            address: bitcoin::Address::from_str("bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw")?,
+            swept_at: OffsetDateTime::from_unix_timestamp(1_640_000_000)?,
+        };
+
+        db.insert_sweep(sweep.clone()).await?;
+
+        assert_eq!(db.all_sweeps().await?, vec![sweep]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_seen_addresses() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let address = "/ip4/127.0.0.1/tcp/9939".parse::<Multiaddr>()?;
+        let seen_at = OffsetDateTime::from_unix_timestamp(1_640_000_000)?;
+
+        db.insert_address(peer_id, address.clone()).await?;
+        db.insert_seen_address(SeenAddress {
+            peer_id,
+            address: address.clone(),
+            last_seen: Some(seen_at),
+        })
+        .await?;
+        db.insert_seen_address(SeenAddress {
+            peer_id,
+            address: address.clone(),
+            last_seen: None,
+        })
+        .await?;
+
+        assert_eq!(db.all_seen_addresses().await?, vec![SeenAddress {
+            peer_id,
+            address: address.clone(),
+            last_seen: Some(seen_at),
+        }]);
+        assert_eq!(db.get_addresses(peer_id).await?, vec![address]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_seller_quotes() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let quote = SellerQuote {
+            peer_id: PeerId::random(),
+            quote: BidQuote {
+                price: bitcoin::Amount::from_sat(1_000),
+                min_quantity: bitcoin::Amount::from_sat(100),
+                max_quantity: bitcoin::Amount::from_sat(100_000),
+            },
+            latency: Some(Duration::from_millis(250)),
+            received_at: OffsetDateTime::from_unix_timestamp(1_640_000_000)?,
+        };
+
+        db.insert_seller_quote(quote).await?;
+
+        assert_eq!(db.all_seller_quotes().await?, vec![quote]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_the_latest_quotes_of_the_seller() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let other_peer_id = PeerId::random();
+        let quote = |peer_id, received_at| -> Result<SellerQuote> {
+            Ok(SellerQuote {
+                peer_id,
+                quote: BidQuote {
+                    price: bitcoin::Amount::from_sat(1_000),
+                    min_quantity: bitcoin::Amount::from_sat(100),
+                    max_quantity: bitcoin::Amount::from_sat(100_000),
+                },
+                latency: None,
+                received_at: OffsetDateTime::from_unix_timestamp(received_at)?,
+            })
+        };
+
+        db.insert_seller_quote(quote(other_peer_id, 1_640_000_000)?)
+            .await?;
+        for received_at in 1_640_000_001..1_640_000_004 {
+            db.insert_seller_quote(quote(peer_id, received_at)?).await?;
+        }
+
+        db.prune_seller_quotes(peer_id, 2).await?;
+
+        assert_eq!(db.all_seller_quotes().await?, vec![
+            quote(other_peer_id, 1_640_000_000)?,
+            quote(peer_id, 1_640_000_002)?,
+            quote(peer_id, 1_640_000_003)?,
+        ]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_load_and_remove_pending_messages() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let swap_id = Uuid::new_v4();
+        let transfer_proof = |tx_hash: &str| {
+            PendingMessage::TransferProof(transfer_proof::Request {
+                swap_id,
+                tx_lock_proof: judecoin::TransferProof::new(
+                    judecoin::TxHash(tx_hash.to_owned()),
+                    judecoin::PrivateKey::from_scalar(judecoin::Scalar::random(&mut OsRng)),
+                ),
+            })
+        };
+
+        db.insert_pending_message(peer_id, transfer_proof("first"))
+            .await?;
+        db.insert_pending_message(peer_id, transfer_proof("second"))
+            .await?;
+
+        let pending = db.get_pending_messages(peer_id).await?;
+        assert_eq!(pending.len(), 1, "a swap has at most one pending message");
+        assert!(matches!(
+            &pending[0],
+            PendingMessage::TransferProof(request) if request.tx_lock_proof.tx_hash().0 == "second"
+        ));
+        assert!(db.get_pending_messages(PeerId::random()).await?.is_empty());
+
+        db.remove_pending_message(swap_id).await?;
+
+        assert!(db.get_pending_messages(peer_id).await?.is_empty());
+
+        Ok(())
+    }
+
     async fn setup_test_db() -> Result<SqliteDatabase> {
         let temp_db = tempdir().unwrap().into_path().join("tempdb");
 
@@ -113,7 +113,7 @@ impl Amount {
         Decimal::from(self.as_piconero())
     }
 
-    fn from_decimal(amount: Decimal) -> Result<Self> {
+    pub fn from_decimal(amount: Decimal) -> Result<Self> {
         let piconeros_dec =
             amount.mul(Decimal::from_u64(PICONERO_OFFSET).expect("constant to fit into u64"));
         let piconeros = piconeros_dec
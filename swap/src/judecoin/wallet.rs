@@ -240,6 +240,25 @@ impl Wallet {
         Ok(())
     }
 
+    /// Sends the given amount from the primary account to an arbitrary
+    /// address.
+    pub async fn transfer_to(&self, address: Address, amount: Amount) -> Result<TxHash> {
+        let inner = self.inner.lock().await;
+
+        inner
+            .open_wallet(self.name.clone())
+            .await
+            .with_context(|| format!("Failed to open wallet {}", self.name))?;
+
+        let res = inner
+            .transfer_single(0, amount.as_piconero(), &address.to_string())
+            .await?;
+
+        tracing::debug!(%amount, to = %address, tx_id = %res.tx_hash, "Successfully initiated judecoin transfer");
+
+        Ok(TxHash(res.tx_hash))
+    }
+
     pub async fn sweep_all(&self, address: Address) -> Result<Vec<TxHash>> {
         let sweep_all = self
             .inner
@@ -259,6 +278,20 @@ impl Wallet {
         Ok(Amount::from_piconero(amount))
     }
 
+    /// Get the unlocked balance of the primary account, i.e. the part of the
+    /// balance that can be spent right away.
+    pub async fn get_unlocked_balance(&self) -> Result<Amount> {
+        let amount = self
+            .inner
+            .lock()
+            .await
+            .get_balance(0)
+            .await?
+            .unlocked_balance;
+
+        Ok(Amount::from_piconero(amount))
+    }
+
     pub async fn block_height(&self) -> Result<BlockHeight> {
         Ok(self.inner.lock().await.get_height().await?)
     }
@@ -27,6 +27,7 @@ pub mod libp2p_ext;
 pub mod judecoin;
 pub mod network;
 pub mod protocol;
+pub mod rendezvous_server;
 pub mod seed;
 pub mod tor;
 pub mod tracing_ext;
@@ -0,0 +1,171 @@
+use crate::{asb, cli};
+use libp2p::core::connection::{ConnectedPoint, ConnectionId, ListenerId};
+use libp2p::identify::{Identify, IdentifyConfig, IdentifyEvent};
+use libp2p::swarm::{
+    DialError, IntoProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+    ProtocolsHandler,
+};
+use libp2p::{identity, Multiaddr, PeerId};
+use std::task::{Context, Poll};
+
+const PROTOCOL_VERSION: &str = "/comit/jude/btc/1.0.0";
+
+/// Constructs a new instance of the `identify` behaviour.
+///
+/// Both sides use it to tell each other the addresses they can be reached on,
+/// most notably the onion service a CLI publishes to be reachable behind NAT.
+pub fn new(identity: identity::PublicKey) -> Behaviour {
+    Behaviour {
+        inner: Identify::new(IdentifyConfig::new(PROTOCOL_VERSION.to_owned(), identity)),
+    }
+}
+
+/// Wraps libp2p's `Identify` to only advertise our external addresses.
+///
+/// `Identify` also sends every address we listen on, which includes the IPs of
+/// all local interfaces. For a maker that is only reachable through an onion
+/// service this would leak its clearnet IPs to every peer that connects.
+pub struct Behaviour {
+    inner: Identify,
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ProtocolsHandler = <Identify as NetworkBehaviour>::ProtocolsHandler;
+    type OutEvent = IdentifyEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        self.inner.new_handler()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.inner.addresses_of_peer(peer_id)
+    }
+
+    fn inject_connected(&mut self, peer_id: &PeerId) {
+        self.inner.inject_connected(peer_id)
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId) {
+        self.inner.inject_disconnected(peer_id)
+    }
+
+    fn inject_connection_established(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        endpoint: &ConnectedPoint,
+    ) {
+        self.inner
+            .inject_connection_established(peer_id, connection, endpoint)
+    }
+
+    fn inject_connection_closed(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        endpoint: &ConnectedPoint,
+        handler: <Self::ProtocolsHandler as IntoProtocolsHandler>::Handler,
+    ) {
+        self.inner
+            .inject_connection_closed(peer_id, connection, endpoint, handler)
+    }
+
+    fn inject_address_change(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        old: &ConnectedPoint,
+        new: &ConnectedPoint,
+    ) {
+        self.inner
+            .inject_address_change(peer_id, connection, old, new)
+    }
+
+    fn inject_event(
+        &mut self,
+        peer_id: PeerId,
+        connection: ConnectionId,
+        event: <<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent,
+    ) {
+        self.inner.inject_event(peer_id, connection, event)
+    }
+
+    fn inject_dial_failure(
+        &mut self,
+        peer_id: &PeerId,
+        handler: Self::ProtocolsHandler,
+        error: DialError,
+    ) {
+        self.inner.inject_dial_failure(peer_id, handler, error)
+    }
+
+    fn inject_new_listen_addr(&mut self, id: ListenerId, addr: &Multiaddr) {
+        self.inner.inject_new_listen_addr(id, addr)
+    }
+
+    fn inject_expired_listen_addr(&mut self, id: ListenerId, addr: &Multiaddr) {
+        self.inner.inject_expired_listen_addr(id, addr)
+    }
+
+    fn inject_new_external_addr(&mut self, addr: &Multiaddr) {
+        self.inner.inject_new_external_addr(addr)
+    }
+
+    fn inject_expired_external_addr(&mut self, addr: &Multiaddr) {
+        self.inner.inject_expired_external_addr(addr)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        params: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+        self.inner.poll(cx, &mut ExternalAddressesOnly(params))
+    }
+}
+
+/// Hides the addresses we listen on from the wrapped `PollParameters`.
+struct ExternalAddressesOnly<'a, P>(&'a mut P);
+
+impl<'a, P> PollParameters for ExternalAddressesOnly<'a, P>
+where
+    P: PollParameters,
+{
+    type SupportedProtocolsIter = P::SupportedProtocolsIter;
+    type ListenedAddressesIter = std::iter::Empty<Multiaddr>;
+    type ExternalAddressesIter = P::ExternalAddressesIter;
+
+    fn supported_protocols(&self) -> Self::SupportedProtocolsIter {
+        self.0.supported_protocols()
+    }
+
+    fn listened_addresses(&self) -> Self::ListenedAddressesIter {
+        std::iter::empty()
+    }
+
+    fn external_addresses(&self) -> Self::ExternalAddressesIter {
+        self.0.external_addresses()
+    }
+
+    fn local_peer_id(&self) -> &PeerId {
+        self.0.local_peer_id()
+    }
+}
+
+impl From<IdentifyEvent> for asb::OutEvent {
+    fn from(event: IdentifyEvent) -> Self {
+        match event {
+            IdentifyEvent::Received { peer_id, info } => Self::Identified {
+                peer: peer_id,
+                listen_addrs: info.listen_addrs,
+            },
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<IdentifyEvent> for cli::OutEvent {
+    fn from(_: IdentifyEvent) -> Self {
+        Self::Other
+    }
+}
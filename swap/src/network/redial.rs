@@ -1,12 +1,15 @@
-use crate::cli;
+use crate::{asb, cli};
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
 use futures::future::FutureExt;
 use libp2p::core::connection::ConnectionId;
 use libp2p::core::Multiaddr;
 use libp2p::swarm::protocols_handler::DummyProtocolsHandler;
-use libp2p::swarm::{DialPeerCondition, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use libp2p::swarm::{
+    DialError, DialPeerCondition, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+};
 use libp2p::PeerId;
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
@@ -17,34 +20,74 @@ pub enum OutEvent {
     AllAttemptsExhausted { peer: PeerId },
 }
 
-/// A [`NetworkBehaviour`] that tracks whether we are connected to the given
-/// peer and attempts to re-establish a connection with an exponential backoff
-/// if we lose the connection.
-pub struct Behaviour {
-    /// The peer we are interested in.
-    peer: PeerId,
+/// Decides for how long we try to re-establish a lost connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Give up once the connection could not be re-established within the
+    /// given duration.
+    GiveUpAfter(Duration),
+    /// Give up once the connection could not be re-established before the
+    /// given point in time.
+    GiveUpAt(Instant),
+    /// Never give up.
+    ///
+    /// The deadline is the point in time until which it is safe to keep
+    /// trying, it is only reported through [`Behaviour::time_left`].
+    KeepTrying { deadline: Instant },
+    /// Don't re-establish the connection, the peer is not needed anymore.
+    Never,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::GiveUpAfter(Duration::from_secs(5 * 60))
+    }
+}
+
+/// The re-dialling state of a single peer.
+struct Redial {
     /// If present, tracks for how long we need to sleep until we dial again.
     sleep: Option<Pin<Box<Sleep>>>,
     /// Tracks the current backoff state.
     backoff: ExponentialBackoff,
+    policy: Policy,
+    /// When we give up re-dialling, if the policy allows giving up.
+    give_up_at: Option<Instant>,
 }
 
-impl Behaviour {
-    pub fn new(peer: PeerId, interval: Duration) -> Self {
+impl Redial {
+    fn new(interval: Duration, policy: Policy) -> Self {
         Self {
-            peer,
             sleep: None,
             backoff: ExponentialBackoff {
                 initial_interval: interval,
                 current_interval: interval,
-                // give up dialling after 5 minutes
-                max_elapsed_time: Some(Duration::from_secs(5 * 60)),
+                // when to give up is decided by the policy
+                max_elapsed_time: None,
                 ..ExponentialBackoff::default()
             },
+            policy,
+            give_up_at: None,
         }
     }
 
-    pub fn until_next_redial(&self) -> Option<Duration> {
+    fn set_policy(&mut self, policy: Policy) {
+        if self.policy == policy {
+            return;
+        }
+
+        self.policy = policy;
+
+        if self.sleep.is_some() {
+            self.start();
+        }
+    }
+
+    fn is_redialling(&self) -> bool {
+        self.sleep.is_some()
+    }
+
+    fn until_next_redial(&self) -> Option<Duration> {
         let until_next_redial = self
             .sleep
             .as_ref()?
@@ -53,6 +96,117 @@ impl Behaviour {
 
         Some(until_next_redial)
     }
+
+    fn time_left(&self) -> Option<Duration> {
+        if self.sleep.is_none() {
+            return None;
+        }
+
+        let deadline = match self.policy {
+            Policy::GiveUpAfter(_) | Policy::GiveUpAt(_) => self.give_up_at?,
+            Policy::KeepTrying { deadline } => deadline,
+            Policy::Never => return None,
+        };
+
+        Some(deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Starts re-dialling with a fresh backoff.
+    fn start(&mut self) {
+        self.backoff.reset();
+        self.give_up_at = self.give_up_at_from_now();
+        self.sleep = match self.policy {
+            Policy::Never => None,
+            _ => Some(Box::pin(tokio::time::sleep(self.backoff.initial_interval))),
+        };
+    }
+
+    /// Starts re-dialling where the backoff left off, so a peer that keeps
+    /// dropping the connection is not dialled ever more often.
+    fn resume(&mut self) {
+        if self.give_up_at.is_none() {
+            self.give_up_at = self.give_up_at_from_now();
+        }
+        self.sleep = match self.policy {
+            Policy::Never => None,
+            _ => Some(Box::pin(tokio::time::sleep(self.backoff.current_interval))),
+        };
+    }
+
+    fn stop(&mut self) {
+        self.sleep = None;
+    }
+
+    fn give_up_at_from_now(&self) -> Option<Instant> {
+        match self.policy {
+            Policy::GiveUpAfter(duration) => Some(Instant::now() + duration),
+            Policy::GiveUpAt(at) => Some(at),
+            Policy::KeepTrying { .. } | Policy::Never => None,
+        }
+    }
+
+    /// Resolves to `true` once it is time to dial again and to `false` once
+    /// the policy does not allow any further attempts.
+    fn poll_dial(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        let sleep = match self.sleep.as_mut() {
+            None => return Poll::Pending, // early exit if we shouldn't be re-dialling
+            Some(future) => future,
+        };
+
+        futures::ready!(sleep.poll_unpin(cx));
+
+        match self.backoff.next_backoff() {
+            Some(next_dial_in) if self.give_up_at.map_or(true, |at| Instant::now() < at) => {
+                self.sleep = Some(Box::pin(tokio::time::sleep(next_dial_in)));
+
+                Poll::Ready(true)
+            }
+            _ => {
+                self.sleep = None;
+
+                Poll::Ready(false)
+            }
+        }
+    }
+}
+
+/// A [`NetworkBehaviour`] that tracks whether we are connected to the given
+/// peer and attempts to re-establish a connection with an exponential backoff
+/// if we lose the connection.
+pub struct Behaviour {
+    /// The peer we are interested in.
+    peer: PeerId,
+    connected: bool,
+    redial: Redial,
+}
+
+impl Behaviour {
+    pub fn new(peer: PeerId, interval: Duration) -> Self {
+        Self {
+            peer,
+            connected: false,
+            redial: Redial::new(interval, Policy::default()),
+        }
+    }
+
+    /// Changes the policy, also for re-dialling that is already in progress.
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.redial.set_policy(policy);
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.redial.policy
+    }
+
+    pub fn until_next_redial(&self) -> Option<Duration> {
+        self.redial.until_next_redial()
+    }
+
+    /// How much time is left to re-establish the connection according to the
+    /// policy, if we are currently re-dialling.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.redial.time_left()
+    }
 }
 
 impl NetworkBehaviour for Behaviour {
@@ -73,7 +227,8 @@ impl NetworkBehaviour for Behaviour {
         }
 
         // established a connection to the desired peer, cancel any active re-dialling
-        self.sleep = None;
+        self.connected = true;
+        self.redial.stop();
     }
 
     fn inject_disconnected(&mut self, peer_id: &PeerId) {
@@ -83,8 +238,17 @@ impl NetworkBehaviour for Behaviour {
 
         // lost connection to the configured peer, trigger re-dialling with an
         // exponential backoff
-        self.backoff.reset();
-        self.sleep = Some(Box::pin(tokio::time::sleep(self.backoff.initial_interval)));
+        self.connected = false;
+        self.redial.start();
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId, _: Self::ProtocolsHandler, _: DialError) {
+        if peer_id != &self.peer || self.connected || self.redial.is_redialling() {
+            return;
+        }
+
+        // we never reached the peer, e.g. because it was offline when we started
+        self.redial.start();
     }
 
     fn inject_event(&mut self, _: PeerId, _: ConnectionId, _: Void) {}
@@ -94,29 +258,132 @@ impl NetworkBehaviour for Behaviour {
         cx: &mut Context<'_>,
         _: &mut impl PollParameters,
     ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
-        let sleep = match self.sleep.as_mut() {
-            None => return Poll::Pending, // early exit if we shouldn't be re-dialling
-            Some(future) => future,
-        };
+        if futures::ready!(self.redial.poll_dial(cx)) {
+            return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                peer_id: self.peer,
+                condition: DialPeerCondition::Disconnected,
+                handler: Self::ProtocolsHandler::default(),
+            });
+        }
 
-        futures::ready!(sleep.poll_unpin(cx));
+        Poll::Ready(NetworkBehaviourAction::GenerateEvent(
+            OutEvent::AllAttemptsExhausted { peer: self.peer },
+        ))
+    }
+}
+
+struct Peer {
+    addresses: Vec<Multiaddr>,
+    redial: Redial,
+}
+
+/// A [`NetworkBehaviour`] that re-establishes the connection to any number of
+/// peers we still have to deliver messages to, each with its own exponential
+/// backoff and policy and at the addresses it told us it can be dialled at.
+pub struct MultiPeerBehaviour {
+    interval: Duration,
+    peers: HashMap<PeerId, Peer>,
+    connected: HashSet<PeerId>,
+}
+
+impl MultiPeerBehaviour {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            peers: HashMap::new(),
+            connected: HashSet::new(),
+        }
+    }
+
+    /// Dials the peer at the given addresses whenever we are not connected
+    /// to it, until [`MultiPeerBehaviour::stop_redial`] is called or the
+    /// policy gives up.
+    pub fn redial(&mut self, peer: PeerId, addresses: Vec<Multiaddr>, policy: Policy) {
+        let interval = self.interval;
+        let entry = self.peers.entry(peer).or_insert_with(|| Peer {
+            addresses: Vec::new(),
+            redial: Redial::new(interval, policy),
+        });
+        entry.addresses = addresses;
+        entry.redial.set_policy(policy);
+
+        if !self.connected.contains(&peer) && !entry.redial.is_redialling() {
+            entry.redial.start();
+        }
+    }
+
+    pub fn stop_redial(&mut self, peer: &PeerId) {
+        self.peers.remove(peer);
+    }
+}
+
+impl NetworkBehaviour for MultiPeerBehaviour {
+    type ProtocolsHandler = DummyProtocolsHandler;
+    type OutEvent = OutEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        DummyProtocolsHandler::default()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.peers
+            .get(peer_id)
+            .map(|peer| peer.addresses.clone())
+            .unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, peer_id: &PeerId) {
+        self.connected.insert(*peer_id);
+
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.redial.stop();
+        }
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId) {
+        self.connected.remove(peer_id);
+
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.redial.resume();
+        }
+    }
+
+    fn inject_event(&mut self, _: PeerId, _: ConnectionId, _: Void) {}
 
-        let next_dial_in = match self.backoff.next_backoff() {
-            Some(next_dial_in) => next_dial_in,
-            None => {
-                return Poll::Ready(NetworkBehaviourAction::GenerateEvent(
-                    OutEvent::AllAttemptsExhausted { peer: self.peer },
-                ));
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        _: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+        let mut exhausted = None;
+
+        for (peer_id, peer) in self.peers.iter_mut() {
+            match peer.redial.poll_dial(cx) {
+                Poll::Ready(true) => {
+                    return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                        peer_id: *peer_id,
+                        condition: DialPeerCondition::Disconnected,
+                        handler: Self::ProtocolsHandler::default(),
+                    });
+                }
+                Poll::Ready(false) => {
+                    exhausted = Some(*peer_id);
+                    break;
+                }
+                Poll::Pending => {}
             }
-        };
+        }
 
-        self.sleep = Some(Box::pin(tokio::time::sleep(next_dial_in)));
+        match exhausted {
+            Some(peer) => {
+                self.peers.remove(&peer);
 
-        Poll::Ready(NetworkBehaviourAction::DialPeer {
-            peer_id: self.peer,
-            condition: DialPeerCondition::Disconnected,
-            handler: Self::ProtocolsHandler::default(),
-        })
+                Poll::Ready(NetworkBehaviourAction::GenerateEvent(
+                    OutEvent::AllAttemptsExhausted { peer },
+                ))
+            }
+            None => Poll::Pending,
+        }
     }
 }
 
@@ -129,3 +396,114 @@ impl From<OutEvent> for cli::OutEvent {
         }
     }
 }
+
+impl From<OutEvent> for asb::OutEvent {
+    fn from(event: OutEvent) -> Self {
+        match event {
+            OutEvent::AllAttemptsExhausted { peer } => {
+                asb::OutEvent::AllRedialAttemptsExhausted { peer }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test::{new_swarm, SwarmExt};
+    use futures::StreamExt;
+    use libp2p::swarm::SwarmEvent;
+
+    #[tokio::test]
+    async fn given_keep_trying_policy_when_disconnected_then_reports_time_left_until_deadline() {
+        let peer = PeerId::random();
+        let mut behaviour = Behaviour::new(peer, Duration::from_secs(2));
+        behaviour.set_policy(Policy::KeepTrying {
+            deadline: Instant::now() + Duration::from_secs(60 * 60),
+        });
+
+        behaviour.inject_connected(&peer);
+        behaviour.inject_disconnected(&peer);
+
+        let time_left = behaviour.time_left().unwrap();
+        assert!(time_left > Duration::from_secs(59 * 60));
+        assert!(time_left <= Duration::from_secs(60 * 60));
+        assert!(behaviour.until_next_redial().is_some());
+    }
+
+    #[tokio::test]
+    async fn given_never_policy_when_disconnected_then_does_not_redial() {
+        let peer = PeerId::random();
+        let mut behaviour = Behaviour::new(peer, Duration::from_secs(2));
+
+        behaviour.inject_connected(&peer);
+        behaviour.inject_disconnected(&peer);
+        assert!(behaviour.until_next_redial().is_some());
+
+        behaviour.set_policy(Policy::Never);
+
+        assert!(behaviour.until_next_redial().is_none());
+        assert!(behaviour.time_left().is_none());
+    }
+
+    #[tokio::test]
+    async fn given_disconnected_peer_to_redial_then_dials_peer_at_given_addresses() {
+        let mut bob = new_swarm(|_, _| MultiPeerBehaviour::new(Duration::from_millis(100)));
+        let bob_address = bob.listen_on_random_memory_address().await;
+        let bob_peer_id = *bob.local_peer_id();
+
+        let mut alice = new_swarm(|_, _| MultiPeerBehaviour::new(Duration::from_millis(100)));
+        alice
+            .behaviour_mut()
+            .redial(bob_peer_id, vec![bob_address], Policy::default());
+
+        tokio::spawn(async move {
+            loop {
+                bob.next().await;
+            }
+        });
+        let alice_connected = tokio::spawn(async move {
+            loop {
+                if let SwarmEvent::ConnectionEstablished { peer_id, .. } =
+                    alice.select_next_some().await
+                {
+                    if peer_id == bob_peer_id {
+                        break;
+                    }
+                }
+            }
+        });
+
+        tokio::time::timeout(Duration::from_secs(10), alice_connected)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn given_peer_keeps_disconnecting_then_backoff_is_not_reset() {
+        let peer = PeerId::random();
+        let mut behaviour = MultiPeerBehaviour::new(Duration::from_secs(2));
+        behaviour.redial(peer, vec![], Policy::default());
+        let redial = &mut behaviour.peers.get_mut(&peer).unwrap().redial;
+        redial.backoff.current_interval = Duration::from_secs(60);
+
+        behaviour.inject_connected(&peer);
+        behaviour.inject_disconnected(&peer);
+
+        let until_next_redial = behaviour.peers[&peer].redial.until_next_redial().unwrap();
+        assert!(until_next_redial > Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn given_deadline_passed_then_gives_up_redialling() {
+        let peer = PeerId::random();
+        let mut behaviour = MultiPeerBehaviour::new(Duration::from_millis(10));
+        behaviour.redial(peer, vec![], Policy::GiveUpAt(Instant::now()));
+
+        let redial = &mut behaviour.peers.get_mut(&peer).unwrap().redial;
+        let dial_again = futures::future::poll_fn(|cx| redial.poll_dial(cx)).await;
+
+        assert!(!dial_again);
+    }
+}
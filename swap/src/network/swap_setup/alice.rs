@@ -17,7 +17,9 @@ use libp2p::swarm::{
 };
 use libp2p::{Multiaddr, PeerId};
 use std::collections::VecDeque;
+use std::fmt;
 use std::fmt::Debug;
+use std::sync::Arc;
 use std::task::Poll;
 use std::time::{Duration, Instant};
 use uuid::Uuid;
@@ -44,37 +46,85 @@ pub enum OutEvent {
 pub struct WalletSnapshot {
     balance: judecoin::Amount,
     lock_fee: judecoin::Amount,
+    /// The balance is below the floor configured for the JUDE inventory.
+    below_floor: bool,
 
-    // TODO: Consider using the same address for punish and redeem (they are mutually exclusive, so
-    // effectively the address will only be used once)
-    redeem_address: bitcoin::Address,
-    punish_address: bitcoin::Address,
+    payout_addresses: PayoutAddresses,
 
     redeem_fee: bitcoin::Amount,
     punish_fee: bitcoin::Amount,
 }
 
+/// The wallet the redeem and punish proceeds are paid to.
+///
+/// Addresses are only derived once Bob committed to a swap, otherwise every
+/// declined swap request would use up two addresses and could push later
+/// payouts past the gap limit of the wallet.
+#[derive(Clone)]
+pub enum PayoutAddresses {
+    HotWallet(Arc<bitcoin::Wallet>),
+    PayoutWallet(Arc<bitcoin::PayoutWallet>),
+}
+
+impl PayoutAddresses {
+    // TODO: Consider using the same address for punish and redeem (they are mutually exclusive, so
+    // effectively the address will only be used once)
+    async fn derive(&self) -> Result<(bitcoin::Address, bitcoin::Address)> {
+        Ok(match self {
+            PayoutAddresses::HotWallet(wallet) => {
+                (wallet.new_address().await?, wallet.new_address().await?)
+            }
+            PayoutAddresses::PayoutWallet(wallet) => {
+                (wallet.new_address().await?, wallet.new_address().await?)
+            }
+        })
+    }
+}
+
+impl fmt::Debug for PayoutAddresses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayoutAddresses::HotWallet(_) => f.write_str("HotWallet"),
+            PayoutAddresses::PayoutWallet(_) => f.write_str("PayoutWallet"),
+        }
+    }
+}
+
 impl WalletSnapshot {
+    /// Redeem and punish addresses are derived from the payout wallet if one
+    /// is configured, otherwise from the hot wallet, see [`PayoutAddresses`].
     pub async fn capture(
-        bitcoin_wallet: &bitcoin::Wallet,
+        bitcoin_wallet: &Arc<bitcoin::Wallet>,
+        payout_wallet: Option<&Arc<bitcoin::PayoutWallet>>,
         judecoin_wallet: &judecoin::Wallet,
         transfer_amount: bitcoin::Amount,
+        below_floor: bool,
     ) -> Result<Self> {
         let balance = judecoin_wallet.get_balance().await?;
-        let redeem_address = bitcoin_wallet.new_address().await?;
-        let punish_address = bitcoin_wallet.new_address().await?;
+        let payout_addresses = match payout_wallet {
+            Some(payout_wallet) => PayoutAddresses::PayoutWallet(payout_wallet.clone()),
+            None => PayoutAddresses::HotWallet(bitcoin_wallet.clone()),
+        };
         let redeem_fee = bitcoin_wallet
-            .estimate_fee(bitcoin::TxRedeem::weight(), transfer_amount)
+            .estimate_fee(
+                bitcoin::TxKind::Redeem,
+                bitcoin::TxRedeem::weight(),
+                transfer_amount,
+            )
             .await?;
         let punish_fee = bitcoin_wallet
-            .estimate_fee(bitcoin::TxPunish::weight(), transfer_amount)
+            .estimate_fee(
+                bitcoin::TxKind::Punish,
+                bitcoin::TxPunish::weight(),
+                transfer_amount,
+            )
             .await?;
 
         Ok(Self {
             balance,
             lock_fee: judecoin::JUDECOIN_FEE,
-            redeem_address,
-            punish_address,
+            below_floor,
+            payout_addresses,
             redeem_fee,
             punish_fee,
         })
@@ -323,7 +373,9 @@ where
                     .sell_quote(btc)
                     .map_err(Error::SellQuoteCalculationFailed)?;
 
-                if wallet_snapshot.balance < jude + wallet_snapshot.lock_fee {
+                if wallet_snapshot.below_floor
+                    || wallet_snapshot.balance < jude + wallet_snapshot.lock_fee
+                {
                     return Err(Error::BalanceTooLow {
                         balance: wallet_snapshot.balance,
                         buy: btc,
@@ -344,20 +396,25 @@ where
 
             let jude = result?;
 
+            let message0 = swap_setup::read_cbor_message::<Message0>(&mut substream)
+                .await
+                .context("Failed to read message0")?;
+
+            let (redeem_address, punish_address) = wallet_snapshot
+                .payout_addresses
+                .derive()
+                .await
+                .context("Failed to derive redeem and punish addresses")?;
             let state0 = State0::new(
                 request.btc,
                 jude,
                 env_config,
-                wallet_snapshot.redeem_address,
-                wallet_snapshot.punish_address,
+                redeem_address,
+                punish_address,
                 wallet_snapshot.redeem_fee,
                 wallet_snapshot.punish_fee,
                 &mut rand::thread_rng(),
             );
-
-            let message0 = swap_setup::read_cbor_message::<Message0>(&mut substream)
-                .await
-                .context("Failed to read message0")?;
             let (swap_id, state1) = state0
                 .receive(message0)
                 .context("Failed to transition state0 -> state1 using message0")?;
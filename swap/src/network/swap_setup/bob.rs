@@ -1,3 +1,4 @@
+use crate::judecoin::PICONERO_OFFSET;
 use crate::network::swap_setup::{
     protocol, read_cbor_message, write_cbor_message, BlockchainNetwork, SpotPriceError,
     SpotPriceRequest, SpotPriceResponse,
@@ -16,7 +17,11 @@ use libp2p::swarm::{
     SubstreamProtocol,
 };
 use libp2p::{Multiaddr, PeerId};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
@@ -123,7 +128,94 @@ pub struct NewSwap {
     pub btc: bitcoin::Amount,
     pub tx_refund_fee: bitcoin::Amount,
     pub tx_cancel_fee: bitcoin::Amount,
+    pub fee_ladder: Vec<bitcoin::FeeLevel>,
     pub bitcoin_refund_address: bitcoin::Address,
+    pub coin_control: bitcoin::CoinControl,
+    pub funded_tx_lock: Option<bitcoin::ExternallyFundedTxLock>,
+    pub price_protection: PriceProtection,
+}
+
+/// Limits on the seller's price, checked before any Bitcoin is locked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceProtection {
+    /// The most Bob is willing to pay for one JUDE.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub max_price: Option<bitcoin::Amount>,
+    /// The least amount of JUDE Bob is willing to receive for the swap.
+    pub min_jude: Option<judecoin::Amount>,
+    /// The price of the quote the swap was started with.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub quoted_price: Option<bitcoin::Amount>,
+    /// By how much the price may rise above the quoted price, e.g. `0.01`
+    /// for one percent. Without a tolerance price moves are not checked.
+    pub tolerance: Option<Decimal>,
+}
+
+impl PriceProtection {
+    /// Raises the least amount of JUDE accepted to what swapping for exactly
+    /// `jude` allows within the tolerance.
+    ///
+    /// Like price moves, the amount is not checked without a tolerance.
+    pub fn expect_jude(&mut self, jude: judecoin::Amount) {
+        let tolerance = match self.tolerance {
+            Some(tolerance) => tolerance,
+            None => return,
+        };
+
+        let min = (jude.as_piconero_decimal() / (Decimal::ONE + tolerance))
+            .floor()
+            .to_u64()
+            .map(judecoin::Amount::from_piconero)
+            .unwrap_or(judecoin::Amount::ZERO);
+
+        if self.min_jude.map_or(true, |current| current < min) {
+            self.min_jude = Some(min);
+        }
+    }
+
+    pub fn check(&self, btc: bitcoin::Amount, jude: judecoin::Amount) -> Result<(), Error> {
+        if let Some(min) = self.min_jude {
+            if jude < min {
+                return Err(Error::JudeBelowMinimum { jude, min });
+            }
+        }
+
+        let price = price_of_one_jude(btc, jude);
+
+        if let Some(max) = self.max_price {
+            if price > max {
+                return Err(Error::PriceAboveMaximum { price, max });
+            }
+        }
+
+        if let (Some(quoted), Some(tolerance)) = (self.quoted_price, self.tolerance) {
+            let limit = Decimal::from(quoted.as_sat()) * (Decimal::ONE + tolerance);
+
+            if Decimal::from(price.as_sat()) > limit {
+                return Err(Error::PriceMoved {
+                    price,
+                    quoted,
+                    tolerance,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The price implied by swapping `btc` for `jude`, rounded up to the next
+/// satoshi.
+fn price_of_one_jude(btc: bitcoin::Amount, jude: judecoin::Amount) -> bitcoin::Amount {
+    let piconero = u128::from(jude.as_piconero());
+
+    if piconero == 0 {
+        return bitcoin::Amount::from_sat(u64::MAX);
+    }
+
+    let sats = (u128::from(btc.as_sat()) * u128::from(PICONERO_OFFSET) + piconero - 1) / piconero;
+
+    bitcoin::Amount::from_sat(u64::try_from(sats).unwrap_or(u64::MAX))
 }
 
 #[derive(Debug)]
@@ -165,6 +257,7 @@ impl ProtocolsHandler for Handler {
             .await?;
 
             let jude = Result::from(read_cbor_message::<SpotPriceResponse>(&mut substream).await?)?;
+            info.price_protection.check(info.btc, jude)?;
 
             let state0 = State0::new(
                 info.swap_id,
@@ -177,11 +270,18 @@ impl ProtocolsHandler for Handler {
                 env_config.judecoin_finality_confirmations,
                 info.tx_refund_fee,
                 info.tx_cancel_fee,
+                info.fee_ladder,
+                info.coin_control,
             );
 
             write_cbor_message(&mut substream, state0.next_message()).await?;
             let message1 = read_cbor_message::<Message1>(&mut substream).await?;
-            let state1 = state0.receive(bitcoin_wallet.as_ref(), message1).await?;
+            let state1 = match info.funded_tx_lock {
+                Some(funded_tx_lock) => {
+                    state0.receive_externally_funded(message1, funded_tx_lock)?
+                }
+                None => state0.receive(bitcoin_wallet.as_ref(), message1).await?,
+            };
 
             write_cbor_message(&mut substream, state1.next_message()).await?;
             let message3 = read_cbor_message::<Message3>(&mut substream).await?;
@@ -281,6 +381,23 @@ pub enum Error {
         asb: BlockchainNetwork,
     },
 
+    #[error("Seller offered only {jude} but at least {min} are required")]
+    JudeBelowMinimum {
+        jude: judecoin::Amount,
+        min: judecoin::Amount,
+    },
+    #[error("Seller's price of {price} per JUDE is above the maximum of {max}")]
+    PriceAboveMaximum {
+        price: bitcoin::Amount,
+        max: bitcoin::Amount,
+    },
+    #[error("Seller's price of {price} per JUDE rose by more than {tolerance} over the quoted price of {quoted}")]
+    PriceMoved {
+        price: bitcoin::Amount,
+        quoted: bitcoin::Amount,
+        tolerance: Decimal,
+    },
+
     #[error("Failed to complete swap setup within {seconds}s")]
     Timeout { seconds: u64 },
 
@@ -308,3 +425,96 @@ impl From<SpotPriceError> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const BTC: bitcoin::Amount = bitcoin::Amount::from_sat(1_000_000);
+
+    fn jude(amount: &str) -> judecoin::Amount {
+        judecoin::Amount::parse_judecoin(amount).unwrap()
+    }
+
+    #[test]
+    fn given_no_limits_then_any_price_is_accepted() {
+        let result = PriceProtection::default().check(BTC, judecoin::Amount::ZERO);
+
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn given_too_little_jude_then_fails() {
+        let protection = PriceProtection {
+            min_jude: Some(jude("2")),
+            ..PriceProtection::default()
+        };
+
+        assert_eq!(protection.check(BTC, jude("2")), Ok(()));
+        assert_eq!(
+            protection.check(BTC, jude("1.5")),
+            Err(Error::JudeBelowMinimum {
+                jude: jude("1.5"),
+                min: jude("2"),
+            })
+        );
+    }
+
+    #[test]
+    fn given_price_above_maximum_then_fails() {
+        let protection = PriceProtection {
+            max_price: Some(bitcoin::Amount::from_sat(500_000)),
+            ..PriceProtection::default()
+        };
+
+        assert_eq!(protection.check(BTC, jude("2")), Ok(()));
+        assert_eq!(
+            protection.check(BTC, jude("1.5")),
+            Err(Error::PriceAboveMaximum {
+                price: bitcoin::Amount::from_sat(666_667),
+                max: bitcoin::Amount::from_sat(500_000),
+            })
+        );
+    }
+
+    #[test]
+    fn given_price_moved_beyond_tolerance_then_fails() {
+        let protection = PriceProtection {
+            quoted_price: Some(bitcoin::Amount::from_sat(490_000)),
+            tolerance: Some(dec!(0.05)),
+            ..PriceProtection::default()
+        };
+
+        assert_eq!(protection.check(BTC, jude("2")), Ok(()));
+        assert_eq!(
+            protection.check(BTC, jude("1.9")),
+            Err(Error::PriceMoved {
+                price: bitcoin::Amount::from_sat(526_316),
+                quoted: bitcoin::Amount::from_sat(490_000),
+                tolerance: dec!(0.05),
+            })
+        );
+    }
+
+    #[test]
+    fn given_exact_jude_then_floor_allows_for_the_tolerance() {
+        let mut protection = PriceProtection {
+            tolerance: Some(dec!(0.25)),
+            ..PriceProtection::default()
+        };
+
+        protection.expect_jude(jude("2"));
+
+        assert_eq!(protection.min_jude, Some(jude("1.6")));
+    }
+
+    #[test]
+    fn given_exact_jude_without_tolerance_then_amount_is_not_checked() {
+        let mut protection = PriceProtection::default();
+
+        protection.expect_jude(jude("2"));
+
+        assert_eq!(protection.min_jude, None);
+    }
+}
@@ -1,12 +1,16 @@
+use crate::asb::rendezous::RendezvousNode;
 use crate::asb::LatestRate;
 use crate::libp2p_ext::MultiAddrExt;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::seed::Seed;
-use crate::{asb, bitcoin, cli, env, tor};
+use crate::{asb, bitcoin, cli, env, rendezvous_server, tor};
 use anyhow::{Context, Result};
-use libp2p::swarm::{NetworkBehaviour, SwarmBuilder};
-use libp2p::{identity, Multiaddr, Swarm};
+use libp2p::core::multiaddr::Protocol;
+use libp2p::swarm::{AddressScore, NetworkBehaviour, SwarmBuilder};
+use libp2p::{identity, rendezvous, Multiaddr, Swarm};
 use std::fmt::Debug;
+use std::net::Ipv4Addr;
+use torut::onion::TorSecretKeyV3;
 
 #[allow(clippy::too_many_arguments)]
 pub fn asb<LR>(
@@ -16,19 +20,27 @@ pub fn asb<LR>(
     latest_rate: LR,
     resume_only: bool,
     env_config: env::Config,
-    rendezvous_params: Option<(Multiaddr, XmrBtcNamespace)>,
+    rendezvous_params: Option<(Vec<Multiaddr>, XmrBtcNamespace)>,
+    tor_socks5_port: Option<u16>,
 ) -> Result<Swarm<asb::Behaviour<LR>>>
 where
     LR: LatestRate + Send + 'static + Debug + Clone,
 {
     let identity = seed.derive_libp2p_identity();
 
-    let rendezvous_params = if let Some((address, namespace)) = rendezvous_params {
-        let peer_id = address
-            .extract_peer_id()
-            .context("Rendezvous node address must contain peer ID")?;
+    let rendezvous_params = if let Some((addresses, namespace)) = rendezvous_params {
+        let rendezvous_nodes = addresses
+            .into_iter()
+            .map(|address| {
+                let peer_id = address
+                    .extract_peer_id()
+                    .context("Rendezvous node address must contain peer ID")?;
 
-        Some((identity.clone(), peer_id, address, namespace))
+                Ok(RendezvousNode::new(address, peer_id, namespace, None))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Some((identity.clone(), rendezvous_nodes))
     } else {
         None
     };
@@ -39,10 +51,31 @@ where
         latest_rate,
         resume_only,
         env_config,
+        identity.public(),
         rendezvous_params,
     );
 
-    let transport = asb::transport::new(&identity)?;
+    let transport = asb::transport::new(&identity, tor_socks5_port)?;
+    let peer_id = identity.public().into();
+
+    let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
+        .executor(Box::new(|f| {
+            tokio::spawn(f);
+        }))
+        .build();
+
+    Ok(swarm)
+}
+
+pub fn rendezvous_server(
+    seed: &Seed,
+    config: rendezvous::server::Config,
+) -> Result<Swarm<rendezvous_server::Behaviour>> {
+    let identity = seed.derive_libp2p_identity();
+
+    let behaviour = rendezvous_server::Behaviour::new(config);
+
+    let transport = asb::transport::new(&identity, None)?;
     let peer_id = identity.public().into();
 
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
@@ -78,3 +111,47 @@ where
 
     Ok(swarm)
 }
+
+/// Publishes an onion service that forwards to a freshly opened local listener
+/// and announces its address as our external address.
+///
+/// This makes the CLI reachable for the ASB even if it sits behind a NAT. The
+/// onion service is removed once the returned client is dropped.
+///
+/// The local port is reserved up-front instead of waiting for the swarm to
+/// report its listen address, so no swarm events are consumed before the event
+/// loop gets to see them.
+pub async fn listen_on_onion_service<B>(
+    swarm: &mut Swarm<B>,
+    tor_client: tor::Client,
+    key: &TorSecretKeyV3,
+) -> Result<tor::AuthenticatedClient>
+where
+    B: NetworkBehaviour,
+{
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .context("Failed to find a free local port for the onion service")?
+        .port();
+    let local_address = Multiaddr::empty()
+        .with(Protocol::Ip4(Ipv4Addr::LOCALHOST))
+        .with(Protocol::Tcp(port));
+
+    swarm.listen_on(local_address.clone())?;
+
+    let ac = tor::register_services(vec![local_address], tor_client, key)
+        .await
+        .context("Failed to publish onion service")?;
+
+    let onion_address = format!(
+        "/onion3/{}:{}",
+        key.public()
+            .get_onion_address()
+            .get_address_without_dot_onion(),
+        port
+    )
+    .parse::<Multiaddr>()?;
+    let _ = swarm.add_external_address(onion_address, AddressScore::Infinite);
+
+    Ok(ac)
+}
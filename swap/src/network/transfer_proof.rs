@@ -1,5 +1,6 @@
 use crate::network::cbor_request_response::CborCodec;
 use crate::{asb, cli, judecoin};
+use anyhow::anyhow;
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{
     ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
@@ -57,7 +58,26 @@ impl From<(PeerId, Message)> for asb::OutEvent {
         }
     }
 }
-crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+impl From<OutEvent> for asb::OutEvent {
+    fn from(event: OutEvent) -> Self {
+        match event {
+            OutEvent::Message { message, peer } => Self::from((peer, message)),
+            // the transfer proof stays pending and is sent again once Bob reconnects
+            OutEvent::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => Self::TransferProofFailed {
+                peer,
+                id: request_id,
+                error: anyhow!("{} failed: {:?}", PROTOCOL, error),
+            },
+            OutEvent::InboundFailure { peer, .. } => Self::unexpected_request(peer),
+            OutEvent::ResponseSent { .. } => Self::Other,
+        }
+    }
+}
 
 impl From<(PeerId, Message)> for cli::OutEvent {
     fn from((peer, message): (PeerId, Message)) -> Self {
@@ -1,3 +1,4 @@
+use crate::network::{encrypted_signature, transfer_proof};
 use crate::protocol::alice::swap::is_complete as alice_is_complete;
 use crate::protocol::alice::AliceState;
 use crate::protocol::bob::swap::is_complete as bob_is_complete;
@@ -40,6 +41,10 @@ pub struct Message0 {
     tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    /// Escalated fee levels for which Bob asks Alice to additionally sign the
+    /// cancel and refund transaction.
+    #[serde(default)]
+    fee_ladder: Vec<bitcoin::FeeLevel>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -66,12 +71,43 @@ pub struct Message2 {
 pub struct Message3 {
     tx_cancel_sig: bitcoin::Signature,
     tx_refund_encsig: bitcoin::EncryptedSignature,
+    /// Signatures for the cancel transaction at each level of the fee ladder.
+    #[serde(default)]
+    tx_cancel_ladder_sigs: Vec<bitcoin::Signature>,
+    /// Encrypted signatures for the refund transaction at each level of the
+    /// fee ladder.
+    #[serde(default)]
+    tx_refund_ladder_encsigs: Vec<bitcoin::EncryptedSignature>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Message4 {
     tx_punish_sig: bitcoin::Signature,
     tx_cancel_sig: bitcoin::Signature,
+    /// Signatures for the cancel transaction at each level of the fee ladder.
+    #[serde(default)]
+    tx_cancel_ladder_sigs: Vec<bitcoin::Signature>,
+    /// Signatures for the punish transaction spending the cancel transaction
+    /// at each level of the fee ladder.
+    #[serde(default)]
+    tx_punish_ladder_sigs: Vec<bitcoin::Signature>,
+}
+
+/// A message to the counterparty that is kept in the database until the
+/// counterparty acknowledged it, so it survives a restart.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PendingMessage {
+    TransferProof(transfer_proof::Request),
+    EncryptedSignature(encrypted_signature::Request),
+}
+
+impl PendingMessage {
+    pub fn swap_id(&self) -> Uuid {
+        match self {
+            PendingMessage::TransferProof(request) => request.swap_id,
+            PendingMessage::EncryptedSignature(request) => request.swap_id,
+        }
+    }
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -88,6 +124,13 @@ impl State {
             State::Bob(state) => bob_is_complete(state),
         }
     }
+
+    pub fn bitcoin_transactions(&self) -> Vec<(bitcoin::Txid, bitcoin::Label)> {
+        match self {
+            State::Alice(state) => state.bitcoin_transactions(),
+            State::Bob(state) => state.bitcoin_transactions(),
+        }
+    }
 }
 
 impl From<AliceState> for State {
@@ -138,9 +181,36 @@ pub trait Database {
     async fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId>;
     async fn insert_judecoin_address(&self, swap_id: Uuid, address: judecoin::Address) -> Result<()>;
     async fn get_judecoin_address(&self, swap_id: Uuid) -> Result<judecoin::Address>;
+    /// Removes the peer id and judecoin address of a swap that never got a
+    /// state, e.g. because its swap setup failed.
+    async fn forget_swap(&self, swap_id: Uuid) -> Result<()>;
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()>;
     async fn get_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>>;
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()>;
     async fn get_state(&self, swap_id: Uuid) -> Result<State>;
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<State>>;
+    /// Every state of every swap, in the order they were inserted.
+    async fn all_states(&self) -> Result<Vec<(Uuid, State)>>;
     async fn all(&self) -> Result<Vec<(Uuid, State)>>;
+    /// Stores a message until [`Database::remove_pending_message`] is called
+    /// for its swap, replacing a message that is already pending for the swap.
+    async fn insert_pending_message(&self, peer_id: PeerId, message: PendingMessage) -> Result<()>;
+    async fn get_pending_messages(&self, peer_id: PeerId) -> Result<Vec<PendingMessage>>;
+    async fn remove_pending_message(&self, swap_id: Uuid) -> Result<()>;
+}
+
+/// Labels the Bitcoin transactions of every swap in the database.
+///
+/// All states a swap went through are considered because final states no
+/// longer carry the transactions that led to them.
+pub async fn bitcoin_labels(db: &(dyn Database + Send + Sync)) -> Result<bitcoin::Labels> {
+    let mut labels = bitcoin::Labels::default();
+
+    for (swap_id, state) in db.all_states().await? {
+        for (txid, label) in state.bitcoin_transactions() {
+            labels.insert(txid, label, swap_id);
+        }
+    }
+
+    Ok(labels)
 }
@@ -1,6 +1,7 @@
+use crate::bitcoin::wallet::ScriptStatus;
 use crate::bitcoin::{
-    current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxPunish, TxRedeem, TxRefund, Txid,
+    current_epoch, CancelTimelock, ExpiredTimelocks, FeeLevel, Label, PunishTimelock, Transaction,
+    TxCancel, TxPunish, TxRedeem, TxRefund, Txid,
 };
 use crate::env::Config;
 use crate::judecoin::wallet::{TransferRequest, WatchRequest};
@@ -107,6 +108,30 @@ impl fmt::Display for AliceState {
     }
 }
 
+impl AliceState {
+    /// The Bitcoin transactions this state knows about.
+    pub fn bitcoin_transactions(&self) -> Vec<(Txid, Label)> {
+        match self {
+            AliceState::Started { state3 }
+            | AliceState::BtcLockTransactionSeen { state3 }
+            | AliceState::BtcLocked { state3 }
+            | AliceState::XmrLockTransactionSent { state3, .. }
+            | AliceState::XmrLocked { state3, .. }
+            | AliceState::XmrLockTransferProofSent { state3, .. }
+            | AliceState::EncSigLearned { state3, .. }
+            | AliceState::BtcRedeemTransactionPublished { state3 }
+            | AliceState::BtcCancelled { state3, .. }
+            | AliceState::BtcRefunded { state3, .. }
+            | AliceState::BtcPunishable { state3, .. }
+            | AliceState::CancelTimelockExpired { state3, .. } => state3.bitcoin_transactions(),
+            AliceState::BtcRedeemed
+            | AliceState::XmrRefunded
+            | AliceState::BtcPunished
+            | AliceState::SafelyAborted => Vec::new(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     a: bitcoin::SecretKey,
@@ -182,6 +207,13 @@ impl State0 {
             bail!("Bob's dleq proof doesn't verify")
         }
 
+        FeeLevel::validate_ladder(
+            &msg.fee_ladder,
+            FeeLevel::new(msg.tx_cancel_fee, msg.tx_refund_fee),
+            self.btc,
+        )
+        .context("Bob's fee ladder is invalid")?;
+
         let v = self.v_a + msg.v_b;
 
         Ok((msg.swap_id, State1 {
@@ -206,6 +238,7 @@ impl State0 {
             tx_punish_fee: self.tx_punish_fee,
             tx_refund_fee: msg.tx_refund_fee,
             tx_cancel_fee: msg.tx_cancel_fee,
+            fee_ladder: msg.fee_ladder,
         }))
     }
 }
@@ -233,6 +266,7 @@ pub struct State1 {
     tx_punish_fee: bitcoin::Amount,
     tx_refund_fee: bitcoin::Amount,
     tx_cancel_fee: bitcoin::Amount,
+    fee_ladder: Vec<FeeLevel>,
 }
 
 impl State1 {
@@ -273,6 +307,7 @@ impl State1 {
             tx_punish_fee: self.tx_punish_fee,
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            fee_ladder: self.fee_ladder,
         })
     }
 }
@@ -297,6 +332,7 @@ pub struct State2 {
     tx_punish_fee: bitcoin::Amount,
     tx_refund_fee: bitcoin::Amount,
     tx_cancel_fee: bitcoin::Amount,
+    fee_ladder: Vec<FeeLevel>,
 }
 
 impl State2 {
@@ -319,9 +355,33 @@ impl State2 {
         let tx_refund_encsig = self.a.encsign(self.S_b_bitcoin, tx_refund.digest());
 
         let tx_cancel_sig = self.a.sign(tx_cancel.digest());
+
+        let (tx_cancel_ladder_sigs, tx_refund_ladder_encsigs): (Vec<_>, Vec<_>) = self
+            .fee_ladder
+            .iter()
+            .map(|fees| {
+                let tx_cancel = bitcoin::TxCancel::new(
+                    &self.tx_lock,
+                    self.cancel_timelock,
+                    self.a.public(),
+                    self.B,
+                    fees.tx_cancel_fee,
+                );
+                let tx_refund =
+                    bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, fees.tx_refund_fee);
+
+                (
+                    self.a.sign(tx_cancel.digest()),
+                    self.a.encsign(self.S_b_bitcoin, tx_refund.digest()),
+                )
+            })
+            .unzip();
+
         Message3 {
             tx_cancel_sig,
             tx_refund_encsig,
+            tx_cancel_ladder_sigs,
+            tx_refund_ladder_encsigs,
         }
     }
 
@@ -344,6 +404,49 @@ impl State2 {
         bitcoin::verify_sig(&self.B, &tx_punish.digest(), &msg.tx_punish_sig)
             .context("Failed to verify punish transaction")?;
 
+        if msg.tx_cancel_ladder_sigs.len() != self.fee_ladder.len()
+            || msg.tx_punish_ladder_sigs.len() != self.fee_ladder.len()
+        {
+            bail!(
+                "Expected signatures for {} fee levels but got {} cancel and {} punish signatures",
+                self.fee_ladder.len(),
+                msg.tx_cancel_ladder_sigs.len(),
+                msg.tx_punish_ladder_sigs.len()
+            )
+        }
+
+        let fee_ladder = self
+            .fee_ladder
+            .iter()
+            .zip(msg.tx_cancel_ladder_sigs)
+            .zip(msg.tx_punish_ladder_sigs)
+            .map(|((fees, tx_cancel_sig_bob), tx_punish_sig_bob)| {
+                let tx_cancel = bitcoin::TxCancel::new(
+                    &self.tx_lock,
+                    self.cancel_timelock,
+                    self.a.public(),
+                    self.B,
+                    fees.tx_cancel_fee,
+                );
+                bitcoin::verify_sig(&self.B, &tx_cancel.digest(), &tx_cancel_sig_bob)
+                    .context("Failed to verify cancel transaction of fee ladder")?;
+                let tx_punish = bitcoin::TxPunish::new(
+                    &tx_cancel,
+                    &self.punish_address,
+                    self.punish_timelock,
+                    self.tx_punish_fee,
+                );
+                bitcoin::verify_sig(&self.B, &tx_punish.digest(), &tx_punish_sig_bob)
+                    .context("Failed to verify punish transaction of fee ladder")?;
+
+                Ok(PresignedFeeLevel {
+                    fees: *fees,
+                    tx_cancel_sig_bob,
+                    tx_punish_sig_bob,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         Ok(State3 {
             a: self.a,
             B: self.B,
@@ -365,10 +468,20 @@ impl State2 {
             tx_punish_fee: self.tx_punish_fee,
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            fee_ladder,
         })
     }
 }
 
+/// Bob's signatures for the cancel and punish transaction at one level of the
+/// fee ladder.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PresignedFeeLevel {
+    fees: FeeLevel,
+    tx_cancel_sig_bob: bitcoin::Signature,
+    tx_punish_sig_bob: bitcoin::Signature,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct State3 {
     a: bitcoin::SecretKey,
@@ -396,6 +509,8 @@ pub struct State3 {
     tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    #[serde(default)]
+    fee_ladder: Vec<PresignedFeeLevel>,
 }
 
 impl State3 {
@@ -403,10 +518,17 @@ impl State3 {
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<ExpiredTimelocks> {
-        let tx_cancel = self.tx_cancel();
+        let tx_cancels = self
+            .fee_levels()
+            .iter()
+            .map(|level| self.tx_cancel_at(level))
+            .collect::<Vec<_>>();
 
         let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
-        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+        let tx_cancel_status = bitcoin_wallet
+            .status_of_first_seen(&tx_cancels)
+            .await?
+            .map_or(ScriptStatus::Unseen, |(_, status)| status);
 
         Ok(current_epoch(
             self.cancel_timelock,
@@ -447,6 +569,7 @@ impl State3 {
         }
     }
 
+    /// The cancel transaction at the fee negotiated at setup time.
     pub fn tx_cancel(&self) -> TxCancel {
         TxCancel::new(
             &self.tx_lock,
@@ -457,19 +580,110 @@ impl State3 {
         )
     }
 
-    pub fn tx_refund(&self) -> TxRefund {
-        bitcoin::TxRefund::new(&self.tx_cancel(), &self.refund_address, self.tx_refund_fee)
+    pub fn tx_cancel_at(&self, level: &PresignedFeeLevel) -> TxCancel {
+        TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.a.public(),
+            self.B,
+            level.fees.tx_cancel_fee,
+        )
+    }
+
+    pub fn tx_refund_at(&self, level: &PresignedFeeLevel) -> TxRefund {
+        bitcoin::TxRefund::new(
+            &self.tx_cancel_at(level),
+            &self.refund_address,
+            level.fees.tx_refund_fee,
+        )
     }
 
     pub fn tx_redeem(&self) -> TxRedeem {
         TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee)
     }
 
+    /// All fee levels at which the cancel transaction was pre-signed,
+    /// starting with the one negotiated at setup time.
+    pub fn fee_levels(&self) -> Vec<PresignedFeeLevel> {
+        let base = PresignedFeeLevel {
+            fees: FeeLevel::new(self.tx_cancel_fee, self.tx_refund_fee),
+            tx_cancel_sig_bob: self.tx_cancel_sig_bob.clone(),
+            tx_punish_sig_bob: self.tx_punish_sig_bob.clone(),
+        };
+
+        std::iter::once(base)
+            .chain(self.fee_ladder.iter().cloned())
+            .collect()
+    }
+
+    /// The lock, redeem and every pre-signed cancel, refund and punish
+    /// transaction of the swap.
+    pub fn bitcoin_transactions(&self) -> Vec<(Txid, Label)> {
+        let mut transactions = vec![
+            (self.tx_lock.txid(), Label::Lock),
+            (self.tx_redeem().txid(), Label::Redeem),
+        ];
+
+        for level in self.fee_levels() {
+            transactions.push((self.tx_cancel_at(&level).txid(), Label::Cancel));
+            transactions.push((self.tx_refund_at(&level).txid(), Label::Refund));
+            transactions.push((self.tx_punish(&level).txid(), Label::Punish));
+        }
+
+        transactions
+    }
+
+    /// The fee level of the cancel transaction that made it onto the network.
+    pub async fn published_fee_level(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<PresignedFeeLevel> {
+        let mut fee_levels = self.fee_levels();
+        let tx_cancels = fee_levels
+            .iter()
+            .map(|level| self.tx_cancel_at(level))
+            .collect::<Vec<_>>();
+
+        let (index, _) = bitcoin_wallet
+            .status_of_first_seen(&tx_cancels)
+            .await?
+            .context("Bitcoin cancel transaction has not been published")?;
+
+        Ok(fee_levels.swap_remove(index))
+    }
+
+    /// Like [`State3::published_fee_level`], but waits for the cancel
+    /// transaction to show up, e.g. because it was only just broadcast.
+    pub async fn wait_for_published_fee_level(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<PresignedFeeLevel> {
+        let mut fee_levels = self.fee_levels();
+        let tx_cancels = fee_levels
+            .iter()
+            .map(|level| self.tx_cancel_at(level))
+            .collect::<Vec<_>>();
+
+        let (index, _) = bitcoin_wallet
+            .wait_until_first_seen(&tx_cancels)
+            .await
+            .context("Bitcoin cancel transaction has not been published")?;
+
+        Ok(fee_levels.swap_remove(index))
+    }
+
     pub fn extract_judecoin_private_key(
         &self,
         published_refund_tx: bitcoin::Transaction,
     ) -> Result<judecoin::PrivateKey> {
-        self.tx_refund().extract_judecoin_private_key(
+        let tx_refund = self
+            .fee_levels()
+            .iter()
+            .map(|level| self.tx_refund_at(level))
+            .find(|tx_refund| tx_refund.txid() == published_refund_tx.txid())
+            .context("Published refund transaction does not match any fee level")?;
+
+        tx_refund.extract_judecoin_private_key(
             published_refund_tx,
             self.s_a,
             self.a.clone(),
@@ -481,19 +695,39 @@ impl State3 {
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<Transaction> {
-        let tx_cancel = self.tx_cancel();
-        let tx = bitcoin_wallet.get_raw_transaction(tx_cancel.txid()).await?;
+        let level = self.published_fee_level(bitcoin_wallet).await?;
+        let tx = bitcoin_wallet
+            .get_raw_transaction(self.tx_cancel_at(&level).txid())
+            .await?;
         Ok(tx)
     }
 
     pub async fn fetch_tx_refund(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Transaction> {
-        let tx_refund = self.tx_refund();
-        let tx = bitcoin_wallet.get_raw_transaction(tx_refund.txid()).await?;
+        let level = self.wait_for_published_fee_level(bitcoin_wallet).await?;
+        let tx = bitcoin_wallet
+            .get_raw_transaction(self.tx_refund_at(&level).txid())
+            .await?;
         Ok(tx)
     }
 
+    /// Publishes the cancel transaction at the cheapest pre-signed fee level
+    /// that satisfies the current fee estimate.
     pub async fn submit_tx_cancel(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
-        let transaction = self.signed_cancel_transaction()?;
+        let fee_levels = self.fee_levels();
+        let required_fee = bitcoin::estimate_cancel_fee(bitcoin_wallet, self.btc).await;
+        let fees = fee_levels
+            .iter()
+            .map(|level| level.fees)
+            .collect::<Vec<_>>();
+        let level = &fee_levels[bitcoin::select_fee_level(&fees, required_fee)];
+
+        tracing::debug!(
+            fee = %level.fees.tx_cancel_fee,
+            %required_fee,
+            "Selected fee level for cancel transaction"
+        );
+
+        let transaction = self.signed_cancel_transaction(level)?;
         let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
         Ok(tx_id)
     }
@@ -527,7 +761,8 @@ impl State3 {
     }
 
     pub async fn punish_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
-        let signed_tx_punish = self.signed_punish_transaction()?;
+        let level = self.wait_for_published_fee_level(bitcoin_wallet).await?;
+        let signed_tx_punish = self.signed_punish_transaction(&level)?;
 
         let (txid, subscription) = bitcoin_wallet.broadcast(signed_tx_punish, "punish").await?;
         subscription.wait_until_final().await?;
@@ -544,21 +779,27 @@ impl State3 {
             .context("Failed to complete Bitcoin redeem transaction")
     }
 
-    pub fn signed_cancel_transaction(&self) -> Result<bitcoin::Transaction> {
-        self.tx_cancel()
-            .complete_as_alice(self.a.clone(), self.B, self.tx_cancel_sig_bob.clone())
+    pub fn signed_cancel_transaction(
+        &self,
+        level: &PresignedFeeLevel,
+    ) -> Result<bitcoin::Transaction> {
+        self.tx_cancel_at(level)
+            .complete_as_alice(self.a.clone(), self.B, level.tx_cancel_sig_bob.clone())
             .context("Failed to complete Bitcoin cancel transaction")
     }
 
-    pub fn signed_punish_transaction(&self) -> Result<bitcoin::Transaction> {
-        self.tx_punish()
-            .complete(self.tx_punish_sig_bob.clone(), self.a.clone(), self.B)
+    pub fn signed_punish_transaction(
+        &self,
+        level: &PresignedFeeLevel,
+    ) -> Result<bitcoin::Transaction> {
+        self.tx_punish(level)
+            .complete(level.tx_punish_sig_bob.clone(), self.a.clone(), self.B)
             .context("Failed to complete Bitcoin punish transaction")
     }
 
-    fn tx_punish(&self) -> TxPunish {
+    fn tx_punish(&self, level: &PresignedFeeLevel) -> TxPunish {
         bitcoin::TxPunish::new(
-            &self.tx_cancel(),
+            &self.tx_cancel_at(level),
             &self.punish_address,
             self.punish_timelock,
             self.tx_punish_fee,
@@ -43,6 +43,11 @@ where
         swap.db
             .insert_latest_state(swap.swap_id, current_state.clone().into())
             .await?;
+
+        // a transfer proof that was not acknowledged is of no use to Bob anymore
+        if is_complete(&current_state) {
+            swap.db.remove_pending_message(swap.swap_id).await?;
+        }
     }
 
     Ok(current_state)
@@ -308,14 +313,20 @@ where
             transfer_proof,
             state3,
         } => {
-            let tx_refund_status = bitcoin_wallet.subscribe_to(state3.tx_refund()).await;
-            let tx_cancel_status = bitcoin_wallet.subscribe_to(state3.tx_cancel()).await;
+            let fee_level = state3.wait_for_published_fee_level(bitcoin_wallet).await?;
+            let tx_refund = state3.tx_refund_at(&fee_level);
+            let tx_refund_txid = tx_refund.txid();
+
+            let tx_refund_status = bitcoin_wallet.subscribe_to(tx_refund).await;
+            let tx_cancel_status = bitcoin_wallet
+                .subscribe_to(state3.tx_cancel_at(&fee_level))
+                .await;
 
             select! {
                 seen_refund = tx_refund_status.wait_until_seen() => {
                     seen_refund.context("Failed to monitor refund transaction")?;
 
-                    let published_refund_tx = bitcoin_wallet.get_raw_transaction(state3.tx_refund().txid()).await?;
+                    let published_refund_tx = bitcoin_wallet.get_raw_transaction(tx_refund_txid).await?;
                     let spend_key = state3.extract_judecoin_private_key(published_refund_tx)?;
 
                     AliceState::BtcRefunded {
@@ -375,9 +386,7 @@ where
 
                     tracing::info!("Falling back to refund");
 
-                    let published_refund_tx = bitcoin_wallet
-                        .get_raw_transaction(state3.tx_refund().txid())
-                        .await?;
+                    let published_refund_tx = state3.fetch_tx_refund(bitcoin_wallet).await?;
 
                     let spend_key = state3.extract_judecoin_private_key(published_refund_tx)?;
 
@@ -3,6 +3,8 @@ use std::sync::Arc;
 use anyhow::Result;
 use uuid::Uuid;
 
+use crate::cli::external_funding::ExternalFunding;
+use crate::network::swap_setup::bob::PriceProtection;
 use crate::protocol::Database;
 use crate::{bitcoin, cli, env, judecoin};
 
@@ -22,6 +24,7 @@ pub struct Swap {
     pub env_config: env::Config,
     pub id: Uuid,
     pub judecoin_receive_address: judecoin::Address,
+    pub external_funding: Option<ExternalFunding>,
 }
 
 impl Swap {
@@ -36,11 +39,17 @@ impl Swap {
         judecoin_receive_address: judecoin::Address,
         bitcoin_change_address: bitcoin::Address,
         btc_amount: bitcoin::Amount,
+        coin_control: bitcoin::CoinControl,
+        external_funding: Option<ExternalFunding>,
+        price_protection: PriceProtection,
     ) -> Self {
         Self {
             state: BobState::Started {
                 btc_amount,
                 change_address: bitcoin_change_address,
+                coin_control,
+                external_funding: external_funding.is_some(),
+                price_protection,
             },
             event_loop_handle,
             db,
@@ -49,6 +58,7 @@ impl Swap {
             env_config,
             id,
             judecoin_receive_address,
+            external_funding,
         }
     }
 
@@ -73,6 +83,14 @@ impl Swap {
             env_config,
             id,
             judecoin_receive_address,
+            external_funding: None,
         })
     }
+
+    /// Allows a resumed swap to fund and sign the lock transaction with an
+    /// external wallet, see [`BobState::requires_external_wallet`].
+    pub fn with_external_funding(mut self, external_funding: ExternalFunding) -> Self {
+        self.external_funding = Some(external_funding);
+        self
+    }
 }
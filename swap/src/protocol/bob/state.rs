@@ -1,12 +1,13 @@
-use crate::bitcoin::wallet::EstimateFeeRate;
+use crate::bitcoin::wallet::{EstimateFeeRate, ScriptStatus};
 use crate::bitcoin::{
-    self, current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxLock, Txid,
+    self, current_epoch, CancelTimelock, CoinControl, ExpiredTimelocks, FeeLevel, Label,
+    PunishTimelock, Transaction, TxCancel, TxLock, Txid,
 };
 use crate::judecoin;
 use crate::judecoin::wallet::WatchRequest;
 use crate::judecoin::{judecoin_private_key, TransferProof};
 use crate::judecoin_ext::ScalarExt;
+use crate::network::swap_setup::bob::PriceProtection;
 use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
 use anyhow::{anyhow, bail, Context, Result};
 use bdk::database::BatchDatabase;
@@ -26,6 +27,9 @@ pub enum BobState {
     Started {
         btc_amount: bitcoin::Amount,
         change_address: bitcoin::Address,
+        coin_control: CoinControl,
+        external_funding: bool,
+        price_protection: PriceProtection,
     },
     SwapSetupCompleted(State2),
     BtcLocked {
@@ -74,6 +78,42 @@ impl fmt::Display for BobState {
     }
 }
 
+impl BobState {
+    /// The Bitcoin transactions this state knows about.
+    pub fn bitcoin_transactions(&self) -> Vec<(Txid, Label)> {
+        match self {
+            BobState::Started { .. } | BobState::SafelyAborted => Vec::new(),
+            BobState::SwapSetupCompleted(state2) => vec![(state2.tx_lock_id(), Label::Lock)],
+            BobState::BtcLocked { state3, .. }
+            | BobState::XmrLockProofReceived { state: state3, .. } => {
+                vec![(state3.tx_lock_id(), Label::Lock)]
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                vec![(state4.tx_lock.txid(), Label::Lock)]
+            }
+            BobState::BtcRedeemed(state5) => vec![(state5.tx_lock_id(), Label::Lock)],
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => state6.bitcoin_transactions(),
+            BobState::XmrRedeemed { tx_lock_id } | BobState::BtcPunished { tx_lock_id } => {
+                vec![(*tx_lock_id, Label::Lock)]
+            }
+        }
+    }
+
+    /// Whether the next step needs the external wallet that funds the lock
+    /// transaction.
+    pub fn requires_external_wallet(&self) -> bool {
+        match self {
+            BobState::Started {
+                external_funding, ..
+            } => *external_funding,
+            BobState::SwapSetupCompleted(state2) => state2.is_externally_funded(),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     swap_id: Uuid,
@@ -91,6 +131,8 @@ pub struct State0 {
     min_judecoin_confirmations: u64,
     tx_refund_fee: bitcoin::Amount,
     tx_cancel_fee: bitcoin::Amount,
+    fee_ladder: Vec<FeeLevel>,
+    coin_control: CoinControl,
 }
 
 impl State0 {
@@ -106,6 +148,8 @@ impl State0 {
         min_judecoin_confirmations: u64,
         tx_refund_fee: bitcoin::Amount,
         tx_cancel_fee: bitcoin::Amount,
+        fee_ladder: Vec<FeeLevel>,
+        coin_control: CoinControl,
     ) -> Self {
         let b = bitcoin::SecretKey::new_random(rng);
 
@@ -132,6 +176,8 @@ impl State0 {
             min_judecoin_confirmations,
             tx_refund_fee,
             tx_cancel_fee,
+            fee_ladder,
+            coin_control,
         }
     }
 
@@ -146,6 +192,7 @@ impl State0 {
             refund_address: self.refund_address.clone(),
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            fee_ladder: self.fee_ladder.clone(),
         }
     }
 
@@ -158,6 +205,38 @@ impl State0 {
         C: EstimateFeeRate,
         D: BatchDatabase,
     {
+        self.verify(&msg)?;
+
+        let tx_lock = bitcoin::TxLock::new(
+            wallet,
+            self.btc,
+            msg.A,
+            self.b.public(),
+            self.refund_address.clone(),
+            &self.coin_control,
+        )
+        .await?;
+
+        Ok(self.into_state1(msg, tx_lock, false))
+    }
+
+    /// Like [`State0::receive`] but with a lock transaction that was funded
+    /// outside of our wallet.
+    pub fn receive_externally_funded(
+        self,
+        msg: Message1,
+        funded: bitcoin::ExternallyFundedTxLock,
+    ) -> Result<State1> {
+        self.verify(&msg)?;
+
+        let tx_lock =
+            bitcoin::TxLock::from_externally_funded(funded, msg.A, self.b.public(), self.btc)
+                .context("Externally funded lock transaction is invalid")?;
+
+        Ok(self.into_state1(msg, tx_lock, true))
+    }
+
+    fn verify(&self, msg: &Message1) -> Result<()> {
         let valid = CROSS_CURVE_PROOF_SYSTEM.verify(
             &msg.dleq_proof_s_a,
             (
@@ -173,17 +252,18 @@ impl State0 {
             bail!("Alice's dleq proof doesn't verify")
         }
 
-        let tx_lock = bitcoin::TxLock::new(
-            wallet,
-            self.btc,
-            msg.A,
-            self.b.public(),
-            self.refund_address.clone(),
-        )
-        .await?;
+        Ok(())
+    }
+
+    fn into_state1(
+        self,
+        msg: Message1,
+        tx_lock: bitcoin::TxLock,
+        external_funding: bool,
+    ) -> State1 {
         let v = msg.v_a + self.v_b;
 
-        Ok(State1 {
+        State1 {
             A: msg.A,
             b: self.b,
             s_b: self.s_b,
@@ -202,7 +282,9 @@ impl State0 {
             tx_refund_fee: self.tx_refund_fee,
             tx_punish_fee: msg.tx_punish_fee,
             tx_cancel_fee: self.tx_cancel_fee,
-        })
+            fee_ladder: self.fee_ladder,
+            external_funding,
+        }
     }
 }
 
@@ -226,6 +308,8 @@ pub struct State1 {
     tx_refund_fee: bitcoin::Amount,
     tx_punish_fee: bitcoin::Amount,
     tx_cancel_fee: bitcoin::Amount,
+    fee_ladder: Vec<FeeLevel>,
+    external_funding: bool,
 }
 
 impl State1 {
@@ -254,6 +338,9 @@ impl State1 {
             &msg.tx_refund_encsig,
         )?;
 
+        let fee_ladder =
+            self.verify_fee_ladder(msg.tx_cancel_ladder_sigs, msg.tx_refund_ladder_encsigs)?;
+
         Ok(State2 {
             A: self.A,
             b: self.b,
@@ -275,8 +362,78 @@ impl State1 {
             tx_refund_fee: self.tx_refund_fee,
             tx_punish_fee: self.tx_punish_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            fee_ladder,
+            external_funding: self.external_funding,
         })
     }
+
+    fn verify_fee_ladder(
+        &self,
+        tx_cancel_sigs: Vec<Signature>,
+        tx_refund_encsigs: Vec<bitcoin::EncryptedSignature>,
+    ) -> Result<Vec<PresignedFeeLevel>> {
+        if tx_cancel_sigs.is_empty() && tx_refund_encsigs.is_empty() {
+            if !self.fee_ladder.is_empty() {
+                tracing::warn!(
+                    "Alice did not sign the fee ladder, cancel and refund can only be published with a fee of {}",
+                    self.tx_cancel_fee
+                );
+            }
+
+            return Ok(Vec::new());
+        }
+
+        if tx_cancel_sigs.len() != self.fee_ladder.len()
+            || tx_refund_encsigs.len() != self.fee_ladder.len()
+        {
+            bail!(
+                "Expected signatures for {} fee levels but got {} cancel and {} refund signatures",
+                self.fee_ladder.len(),
+                tx_cancel_sigs.len(),
+                tx_refund_encsigs.len()
+            )
+        }
+
+        self.fee_ladder
+            .iter()
+            .zip(tx_cancel_sigs)
+            .zip(tx_refund_encsigs)
+            .map(|((fees, tx_cancel_sig_a), tx_refund_encsig)| {
+                let tx_cancel = TxCancel::new(
+                    &self.tx_lock,
+                    self.cancel_timelock,
+                    self.A,
+                    self.b.public(),
+                    fees.tx_cancel_fee,
+                );
+                let tx_refund =
+                    bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, fees.tx_refund_fee);
+
+                bitcoin::verify_sig(&self.A, &tx_cancel.digest(), &tx_cancel_sig_a)?;
+                bitcoin::verify_encsig(
+                    self.A,
+                    bitcoin::PublicKey::from(self.s_b.to_secpfun_scalar()),
+                    &tx_refund.digest(),
+                    &tx_refund_encsig,
+                )?;
+
+                Ok(PresignedFeeLevel {
+                    fees: *fees,
+                    tx_cancel_sig_a,
+                    tx_refund_encsig,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Alice's signatures for the cancel and refund transaction at one level of
+/// the fee ladder.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct PresignedFeeLevel {
+    fees: FeeLevel,
+    tx_cancel_sig_a: Signature,
+    tx_refund_encsig: bitcoin::EncryptedSignature,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -305,9 +462,22 @@ pub struct State2 {
     tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    #[serde(default)]
+    fee_ladder: Vec<PresignedFeeLevel>,
+    #[serde(default)]
+    external_funding: bool,
 }
 
 impl State2 {
+    pub fn tx_lock_id(&self) -> bitcoin::Txid {
+        self.tx_lock.txid()
+    }
+
+    /// Whether the lock transaction has to be signed by an external wallet.
+    pub fn is_externally_funded(&self) -> bool {
+        self.external_funding
+    }
+
     pub fn next_message(&self) -> Message4 {
         let tx_cancel = TxCancel::new(
             &self.tx_lock,
@@ -325,9 +495,36 @@ impl State2 {
         );
         let tx_punish_sig = self.b.sign(tx_punish.digest());
 
+        let (tx_cancel_ladder_sigs, tx_punish_ladder_sigs): (Vec<_>, Vec<_>) = self
+            .fee_ladder
+            .iter()
+            .map(|level| {
+                let tx_cancel = TxCancel::new(
+                    &self.tx_lock,
+                    self.cancel_timelock,
+                    self.A,
+                    self.b.public(),
+                    level.fees.tx_cancel_fee,
+                );
+                let tx_punish = bitcoin::TxPunish::new(
+                    &tx_cancel,
+                    &self.punish_address,
+                    self.punish_timelock,
+                    self.tx_punish_fee,
+                );
+
+                (
+                    self.b.sign(tx_cancel.digest()),
+                    self.b.sign(tx_punish.digest()),
+                )
+            })
+            .unzip();
+
         Message4 {
             tx_punish_sig,
             tx_cancel_sig,
+            tx_cancel_ladder_sigs,
+            tx_punish_ladder_sigs,
         }
     }
 
@@ -352,6 +549,7 @@ impl State2 {
                 tx_redeem_fee: self.tx_redeem_fee,
                 tx_refund_fee: self.tx_refund_fee,
                 tx_cancel_fee: self.tx_cancel_fee,
+                fee_ladder: self.fee_ladder,
             },
             self.tx_lock,
         ))
@@ -381,6 +579,8 @@ pub struct State3 {
     tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    #[serde(default)]
+    fee_ladder: Vec<PresignedFeeLevel>,
 }
 
 impl State3 {
@@ -416,6 +616,7 @@ impl State3 {
             tx_redeem_fee: self.tx_redeem_fee,
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            fee_ladder: self.fee_ladder,
         }
     }
 
@@ -432,6 +633,7 @@ impl State3 {
             tx_refund_encsig: self.tx_refund_encsig.clone(),
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            fee_ladder: self.fee_ladder.clone(),
         }
     }
 
@@ -443,16 +645,17 @@ impl State3 {
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<ExpiredTimelocks> {
-        let tx_cancel = TxCancel::new(
+        let tx_cancels = tx_cancels(
             &self.tx_lock,
             self.cancel_timelock,
             self.A,
             self.b.public(),
             self.tx_cancel_fee,
+            &self.fee_ladder,
         );
 
         let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
-        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+        let tx_cancel_status = tx_cancel_status(bitcoin_wallet, &tx_cancels).await?;
 
         Ok(current_epoch(
             self.cancel_timelock,
@@ -484,6 +687,8 @@ pub struct State4 {
     tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    #[serde(default)]
+    fee_ladder: Vec<PresignedFeeLevel>,
 }
 
 impl State4 {
@@ -524,16 +729,17 @@ impl State4 {
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<ExpiredTimelocks> {
-        let tx_cancel = TxCancel::new(
+        let tx_cancels = tx_cancels(
             &self.tx_lock,
             self.cancel_timelock,
             self.A,
             self.b.public(),
             self.tx_cancel_fee,
+            &self.fee_ladder,
         );
 
         let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
-        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+        let tx_cancel_status = tx_cancel_status(bitcoin_wallet, &tx_cancels).await?;
 
         Ok(current_epoch(
             self.cancel_timelock,
@@ -556,6 +762,7 @@ impl State4 {
             tx_refund_encsig: self.tx_refund_encsig,
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            fee_ladder: self.fee_ladder,
         }
     }
 }
@@ -598,6 +805,8 @@ pub struct State6 {
     pub tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub tx_cancel_fee: bitcoin::Amount,
+    #[serde(default)]
+    fee_ladder: Vec<PresignedFeeLevel>,
 }
 
 impl State6 {
@@ -605,16 +814,17 @@ impl State6 {
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<ExpiredTimelocks> {
-        let tx_cancel = TxCancel::new(
+        let tx_cancels = tx_cancels(
             &self.tx_lock,
             self.cancel_timelock,
             self.A,
             self.b.public(),
             self.tx_cancel_fee,
+            &self.fee_ladder,
         );
 
         let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
-        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+        let tx_cancel_status = tx_cancel_status(bitcoin_wallet, &tx_cancels).await?;
 
         Ok(current_epoch(
             self.cancel_timelock,
@@ -628,29 +838,36 @@ impl State6 {
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<Transaction> {
-        let tx_cancel = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        );
+        let level = self.published_fee_level(bitcoin_wallet).await?;
+        let tx_cancel = self.tx_cancel(&level);
 
         let tx = bitcoin_wallet.get_raw_transaction(tx_cancel.txid()).await?;
 
         Ok(tx)
     }
 
+    /// Publishes the cancel transaction at the cheapest pre-signed fee level
+    /// that satisfies the current fee estimate.
     pub async fn submit_tx_cancel(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
-        let transaction = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        )
-        .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
-        .context("Failed to complete Bitcoin cancel transaction")?;
+        let fee_levels = self.fee_levels();
+        let required_fee =
+            bitcoin::estimate_cancel_fee(bitcoin_wallet, self.tx_lock.lock_amount()).await;
+        let fees = fee_levels
+            .iter()
+            .map(|level| level.fees)
+            .collect::<Vec<_>>();
+        let level = &fee_levels[bitcoin::select_fee_level(&fees, required_fee)];
+
+        tracing::debug!(
+            fee = %level.fees.tx_cancel_fee,
+            %required_fee,
+            "Selected fee level for cancel transaction"
+        );
+
+        let transaction = self
+            .tx_cancel(level)
+            .complete_as_bob(self.A, self.b.clone(), level.tx_cancel_sig_a.clone())
+            .context("Failed to complete Bitcoin cancel transaction")?;
 
         let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
 
@@ -658,36 +875,141 @@ impl State6 {
     }
 
     pub async fn publish_refund_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
-        let signed_tx_refund = self.signed_refund_transaction()?;
+        let level = self.wait_for_published_fee_level(bitcoin_wallet).await?;
+        let signed_tx_refund = self.signed_refund_transaction(&level)?;
         let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
         subscription.wait_until_final().await?;
 
         Ok(())
     }
 
-    pub fn signed_refund_transaction(&self) -> Result<Transaction> {
-        let tx_cancel = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        );
+    /// Signs the refund transaction spending the cancel transaction of the
+    /// given fee level.
+    pub fn signed_refund_transaction(&self, level: &PresignedFeeLevel) -> Result<Transaction> {
+        let tx_cancel = self.tx_cancel(level);
         let tx_refund =
-            bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, self.tx_refund_fee);
+            bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, level.fees.tx_refund_fee);
 
         let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
 
         let sig_b = self.b.sign(tx_refund.digest());
-        let sig_a =
-            adaptor.decrypt_signature(&self.s_b.to_secpfun_scalar(), self.tx_refund_encsig.clone());
+        let sig_a = adaptor.decrypt_signature(
+            &self.s_b.to_secpfun_scalar(),
+            level.tx_refund_encsig.clone(),
+        );
 
         let signed_tx_refund =
             tx_refund.add_signatures((self.A, sig_a), (self.b.public(), sig_b))?;
         Ok(signed_tx_refund)
     }
 
+    /// All fee levels at which the cancel and refund transaction were
+    /// pre-signed, starting with the one negotiated at setup time.
+    pub fn fee_levels(&self) -> Vec<PresignedFeeLevel> {
+        let base = PresignedFeeLevel {
+            fees: FeeLevel::new(self.tx_cancel_fee, self.tx_refund_fee),
+            tx_cancel_sig_a: self.tx_cancel_sig_a.clone(),
+            tx_refund_encsig: self.tx_refund_encsig.clone(),
+        };
+
+        std::iter::once(base)
+            .chain(self.fee_ladder.iter().cloned())
+            .collect()
+    }
+
+    /// The lock and every pre-signed cancel and refund transaction of the
+    /// swap.
+    pub fn bitcoin_transactions(&self) -> Vec<(Txid, Label)> {
+        let mut transactions = vec![(self.tx_lock.txid(), Label::Lock)];
+
+        for level in self.fee_levels() {
+            let tx_cancel = self.tx_cancel(&level);
+            let tx_refund =
+                bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, level.fees.tx_refund_fee);
+
+            transactions.push((tx_cancel.txid(), Label::Cancel));
+            transactions.push((tx_refund.txid(), Label::Refund));
+        }
+
+        transactions
+    }
+
+    async fn published_fee_level(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<PresignedFeeLevel> {
+        let mut fee_levels = self.fee_levels();
+        let tx_cancels = fee_levels
+            .iter()
+            .map(|level| self.tx_cancel(level))
+            .collect::<Vec<_>>();
+
+        let (index, _) = bitcoin_wallet
+            .status_of_first_seen(&tx_cancels)
+            .await?
+            .context("Bitcoin cancel transaction has not been published")?;
+
+        Ok(fee_levels.swap_remove(index))
+    }
+
+    /// Like [`State6::published_fee_level`], but waits for the cancel
+    /// transaction to show up, e.g. because it was only just broadcast.
+    async fn wait_for_published_fee_level(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<PresignedFeeLevel> {
+        let mut fee_levels = self.fee_levels();
+        let tx_cancels = fee_levels
+            .iter()
+            .map(|level| self.tx_cancel(level))
+            .collect::<Vec<_>>();
+
+        let (index, _) = bitcoin_wallet
+            .wait_until_first_seen(&tx_cancels)
+            .await
+            .context("Bitcoin cancel transaction has not been published")?;
+
+        Ok(fee_levels.swap_remove(index))
+    }
+
+    fn tx_cancel(&self, level: &PresignedFeeLevel) -> TxCancel {
+        TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            level.fees.tx_cancel_fee,
+        )
+    }
+
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
         self.tx_lock.txid()
     }
 }
+
+/// Builds the cancel transaction for the base fee and every level of the fee
+/// ladder.
+fn tx_cancels(
+    tx_lock: &TxLock,
+    cancel_timelock: CancelTimelock,
+    A: bitcoin::PublicKey,
+    B: bitcoin::PublicKey,
+    tx_cancel_fee: bitcoin::Amount,
+    fee_ladder: &[PresignedFeeLevel],
+) -> Vec<TxCancel> {
+    std::iter::once(tx_cancel_fee)
+        .chain(fee_ladder.iter().map(|level| level.fees.tx_cancel_fee))
+        .map(|fee| TxCancel::new(tx_lock, cancel_timelock, A, B, fee))
+        .collect()
+}
+
+/// The status of whichever of the cancel transactions has been published.
+async fn tx_cancel_status(
+    bitcoin_wallet: &bitcoin::Wallet,
+    tx_cancels: &[TxCancel],
+) -> Result<ScriptStatus> {
+    Ok(bitcoin_wallet
+        .status_of_first_seen(tx_cancels)
+        .await?
+        .map_or(ScriptStatus::Unseen, |(_, status)| status))
+}
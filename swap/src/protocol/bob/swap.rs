@@ -1,11 +1,16 @@
-use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
+use crate::bitcoin::wallet::ScriptStatus;
+use crate::bitcoin::{ExpiredTimelocks, FeeLevel, TxCancel, TxKind, TxRefund};
+use crate::cli::external_funding::ExternalFunding;
 use crate::cli::EventLoopHandle;
+use crate::network::redial;
 use crate::network::swap_setup::bob::NewSwap;
 use crate::protocol::bob;
 use crate::protocol::bob::state::*;
-use crate::{bitcoin, judecoin};
+use crate::protocol::{Database, PendingMessage};
+use crate::{bitcoin, env, judecoin};
 use anyhow::{bail, Context, Result};
 use tokio::select;
+use tokio::time::Instant;
 use uuid::Uuid;
 
 pub fn is_complete(state: &BobState) -> bool {
@@ -30,31 +35,109 @@ pub async fn run_until(
     let mut current_state = swap.state;
 
     while !is_target_state(&current_state) {
+        // failing to look up the lock transaction must not abort the swap
+        let redial_policy = redial_policy(
+            &current_state,
+            swap.bitcoin_wallet.as_ref(),
+            swap.env_config,
+        )
+        .await
+        .unwrap_or_else(|error| {
+            tracing::warn!(
+                "Failed to determine how long to reconnect to Alice, using the default: {:#}",
+                error
+            );
+            redial::Policy::default()
+        });
+        swap.event_loop_handle.set_redial_policy(redial_policy);
+
         current_state = next_state(
             swap.id,
             current_state.clone(),
             &mut swap.event_loop_handle,
+            swap.db.as_ref(),
             swap.bitcoin_wallet.as_ref(),
             swap.judecoin_wallet.as_ref(),
             swap.judecoin_receive_address,
+            swap.external_funding.as_ref(),
         )
         .await?;
 
         swap.db
             .insert_latest_state(swap.id, current_state.clone().into())
             .await?;
+
+        // an encrypted signature that was not acknowledged is of no use to Alice anymore
+        if is_complete(&current_state) {
+            swap.db.remove_pending_message(swap.id).await?;
+        }
     }
 
     Ok(current_state)
 }
 
+/// Decides for how long to try reconnecting to Alice in the given state.
+///
+/// Once the Bitcoin is locked we depend on Alice until she has our encrypted
+/// signature, so we keep trying for as long as the cancel timelock allows.
+/// Afterwards the swap continues without her.
+async fn redial_policy(
+    state: &BobState,
+    bitcoin_wallet: &bitcoin::Wallet,
+    env_config: env::Config,
+) -> Result<redial::Policy> {
+    let (tx_lock, cancel_timelock) = match state {
+        BobState::Started { .. } | BobState::SwapSetupCompleted(_) => {
+            return Ok(redial::Policy::default())
+        }
+        BobState::BtcLocked { state3, .. } => (&state3.tx_lock, state3.cancel_timelock),
+        BobState::XmrLockProofReceived { state, .. } => (&state.tx_lock, state.cancel_timelock),
+        BobState::XmrLocked(state4) => (&state4.tx_lock, state4.cancel_timelock),
+        _ => return Ok(redial::Policy::Never),
+    };
+
+    let blocks_left = match bitcoin_wallet.status_of_script(tx_lock).await? {
+        ScriptStatus::Confirmed(confirmed) => {
+            u32::from(cancel_timelock).saturating_sub(confirmed.confirmations())
+        }
+        _ => u32::from(cancel_timelock),
+    };
+
+    Ok(redial::Policy::KeepTrying {
+        deadline: Instant::now() + env_config.bitcoin_avg_block_time * blocks_left,
+    })
+}
+
+/// A transfer proof the event loop of another swap received and stored for
+/// this swap.
+async fn stored_transfer_proof(
+    db: &(dyn Database + Send + Sync),
+    swap_id: Uuid,
+) -> Result<Option<judecoin::TransferProof>> {
+    let peer_id = db.get_peer_id(swap_id).await?;
+    let transfer_proof = db
+        .get_pending_messages(peer_id)
+        .await?
+        .into_iter()
+        .find_map(|message| match message {
+            PendingMessage::TransferProof(request) if request.swap_id == swap_id => {
+                Some(request.tx_lock_proof)
+            }
+            _ => None,
+        });
+
+    Ok(transfer_proof)
+}
+
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
     event_loop_handle: &mut EventLoopHandle,
+    db: &(dyn Database + Send + Sync),
     bitcoin_wallet: &bitcoin::Wallet,
     judecoin_wallet: &judecoin::Wallet,
     judecoin_receive_address: judecoin::Address,
+    external_funding: Option<&ExternalFunding>,
 ) -> Result<BobState> {
     tracing::debug!(%state, "Advancing state");
 
@@ -62,13 +145,30 @@ async fn next_state(
         BobState::Started {
             btc_amount,
             change_address,
+            coin_control,
+            external_funding: fund_externally,
+            price_protection,
         } => {
+            // The external wallet funds the lock transaction before the swap setup because
+            // the swap setup has to complete within a short timeout.
+            let funded_tx_lock = if fund_externally {
+                let external_funding = external_funding.context(
+                    "Swap has to be funded from an external wallet but none is available",
+                )?;
+
+                Some(fund_lock_externally(bitcoin_wallet, external_funding, btc_amount).await?)
+            } else {
+                None
+            };
+
             let tx_refund_fee = bitcoin_wallet
-                .estimate_fee(TxRefund::weight(), btc_amount)
+                .estimate_fee(TxKind::Refund, TxRefund::weight(), btc_amount)
                 .await?;
             let tx_cancel_fee = bitcoin_wallet
-                .estimate_fee(TxCancel::weight(), btc_amount)
+                .estimate_fee(TxKind::Cancel, TxCancel::weight(), btc_amount)
                 .await?;
+            let fee_ladder =
+                FeeLevel::ladder(FeeLevel::new(tx_cancel_fee, tx_refund_fee), btc_amount);
 
             let state2 = event_loop_handle
                 .setup_swap(NewSwap {
@@ -76,7 +176,11 @@ async fn next_state(
                     btc: btc_amount,
                     tx_refund_fee,
                     tx_cancel_fee,
+                    fee_ladder,
                     bitcoin_refund_address: change_address,
+                    coin_control,
+                    funded_tx_lock,
+                    price_protection,
                 })
                 .await?;
 
@@ -97,11 +201,25 @@ async fn next_state(
             let judecoin_wallet_restore_blockheight = judecoin_wallet.block_height().await?;
 
             // Alice and Bob have exchanged info
+            let externally_funded = state2.is_externally_funded();
             let (state3, tx_lock) = state2.lock_btc().await?;
-            let signed_tx = bitcoin_wallet
-                .sign_and_finalize(tx_lock.clone().into())
-                .await
-                .context("Failed to sign Bitcoin lock transaction")?;
+            // Alice's signatures for the cancel and refund transactions were verified during
+            // the swap setup, only now it is safe to sign the lock transaction.
+            let signed_tx = if externally_funded {
+                let external_funding = external_funding.context(
+                    "Lock transaction has to be signed by an external wallet but none is available",
+                )?;
+                let signed = external_funding.sign(tx_lock.clone().into()).await?;
+
+                tx_lock
+                    .extract_signed(signed)
+                    .context("Externally signed lock transaction is invalid")?
+            } else {
+                bitcoin_wallet
+                    .sign_and_finalize(tx_lock.clone().into())
+                    .await
+                    .context("Failed to sign Bitcoin lock transaction")?
+            };
             let (..) = bitcoin_wallet.broadcast(signed_tx, "lock").await?;
 
             BobState::BtcLocked {
@@ -118,6 +236,16 @@ async fn next_state(
             let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
 
             if let ExpiredTimelocks::None = state3.current_epoch(bitcoin_wallet).await? {
+                if let Some(transfer_proof) = stored_transfer_proof(db, swap_id).await? {
+                    tracing::info!(txid = %transfer_proof.tx_hash(), "Alice locked judecoin while the swap was not running");
+
+                    return Ok(BobState::XmrLockProofReceived {
+                        state: state3,
+                        lock_transfer_proof: transfer_proof,
+                        judecoin_wallet_restore_blockheight,
+                    });
+                }
+
                 let transfer_proof_watcher = event_loop_handle.recv_transfer_proof();
                 let cancel_timelock_expires =
                     tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock);
@@ -289,3 +417,28 @@ async fn next_state(
         BobState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
     })
 }
+
+/// Has the external wallet fund a lock transaction of `btc` and checks the
+/// fee it chose.
+///
+/// The funded transaction pays to a placeholder address of our wallet until
+/// the shared output is known.
+async fn fund_lock_externally(
+    bitcoin_wallet: &bitcoin::Wallet,
+    external_funding: &ExternalFunding,
+    btc: bitcoin::Amount,
+) -> Result<bitcoin::ExternallyFundedTxLock> {
+    let placeholder = bitcoin_wallet.new_address().await?;
+    let template = bitcoin::ExternallyFundedTxLock::template(btc, &placeholder)?;
+
+    let funded = external_funding.fund(template).await?;
+    let funded = bitcoin::ExternallyFundedTxLock::new(funded, &placeholder, btc)
+        .context("Externally funded lock transaction is invalid")?;
+
+    let estimated_fee = bitcoin_wallet
+        .estimate_fee(TxKind::Lock, funded.estimated_weight(), btc)
+        .await?;
+    funded.ensure_fee_close_to(estimated_fee)?;
+
+    Ok(funded)
+}
@@ -0,0 +1,43 @@
+pub mod command;
+pub mod config;
+pub mod tracing;
+
+use libp2p::ping::{Ping, PingEvent};
+use libp2p::rendezvous;
+
+#[derive(Debug)]
+pub enum OutEvent {
+    Rendezvous(rendezvous::server::Event),
+    Ping(PingEvent),
+}
+
+impl From<rendezvous::server::Event> for OutEvent {
+    fn from(event: rendezvous::server::Event) -> Self {
+        OutEvent::Rendezvous(event)
+    }
+}
+
+impl From<PingEvent> for OutEvent {
+    fn from(event: PingEvent) -> Self {
+        OutEvent::Ping(event)
+    }
+}
+
+/// A `NetworkBehaviour` that lets sellers register and takers discover them.
+#[derive(libp2p::NetworkBehaviour)]
+#[behaviour(event_process = false)]
+#[behaviour(out_event = "OutEvent")]
+pub struct Behaviour {
+    pub rendezvous: rendezvous::server::Behaviour,
+    // Support `Ping` as a workaround until https://github.com/libp2p/rust-libp2p/issues/2109 is fixed.
+    pub ping: Ping,
+}
+
+impl Behaviour {
+    pub fn new(config: rendezvous::server::Config) -> Self {
+        Self {
+            rendezvous: rendezvous::server::Behaviour::new(config),
+            ping: Ping::default(),
+        }
+    }
+}
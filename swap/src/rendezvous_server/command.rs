@@ -0,0 +1,84 @@
+use crate::rendezvous_server::config::default_config_path;
+use anyhow::Result;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arguments {
+    pub json: bool,
+    pub disable_timestamp: bool,
+    pub config_path: PathBuf,
+}
+
+pub fn parse_args<I, T>(raw_args: I) -> Result<Arguments>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = RawArguments::clap().get_matches_from_safe(raw_args)?;
+    let args = RawArguments::from_clap(&matches);
+
+    let config_path = match args.config {
+        Some(config_path) => config_path,
+        None => default_config_path()?,
+    };
+
+    Ok(Arguments {
+        json: args.json,
+        disable_timestamp: args.disable_timestamp,
+        config_path,
+    })
+}
+
+#[derive(structopt::StructOpt, Debug)]
+#[structopt(
+    name = "rendezvous-server",
+    about = "Rendezvous point for discovering JUDE/BTC swap sellers",
+    author,
+    version = env!("VERGEN_GIT_SEMVER_LIGHTWEIGHT")
+)]
+struct RawArguments {
+    #[structopt(
+        short,
+        long = "json",
+        help = "Changes the log messages to json vs plain-text. If you run the rendezvous server as a service, it is recommended to set this to true to simplify log analyses."
+    )]
+    json: bool,
+
+    #[structopt(
+        short,
+        long = "disable-timestamp",
+        help = "Disable timestamping of log messages"
+    )]
+    disable_timestamp: bool,
+
+    #[structopt(
+        long = "config",
+        help = "Provide a custom path to the configuration file. The configuration file must be a toml file.",
+        parse(from_os_str)
+    )]
+    config: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_config_then_config_path_is_used() {
+        let args = parse_args(vec![
+            "rendezvous-server",
+            "--json",
+            "--config",
+            "/tmp/rendezvous.toml",
+        ])
+        .unwrap();
+
+        assert_eq!(args, Arguments {
+            json: true,
+            disable_timestamp: false,
+            config_path: PathBuf::from("/tmp/rendezvous.toml"),
+        });
+    }
+}
@@ -0,0 +1,185 @@
+use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
+use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
+use anyhow::{Context, Result};
+use config::ConfigError;
+use libp2p::core::Multiaddr;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const DEFAULT_LISTEN_ADDRESS: &str = "/ip4/0.0.0.0/tcp/8888";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub data: Data,
+    pub network: Network,
+    #[serde(default)]
+    pub tor: Option<TorConf>,
+    #[serde(default)]
+    pub registrations: Registrations,
+}
+
+impl Config {
+    pub fn read<D>(config_file: D) -> Result<Self, ConfigError>
+    where
+        D: AsRef<OsStr>,
+    {
+        let config_file = Path::new(&config_file);
+
+        let mut config = config::Config::new();
+        config.merge(config::File::from(config_file))?;
+        config.try_into()
+    }
+
+    pub fn default_with_data_dir(dir: PathBuf) -> Result<Self> {
+        Ok(Self {
+            data: Data { dir },
+            network: Network {
+                listen: vec![Multiaddr::from_str(DEFAULT_LISTEN_ADDRESS)?],
+                external_addresses: vec![],
+            },
+            tor: None,
+            registrations: Registrations::default(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Data {
+    pub dir: PathBuf,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Network {
+    pub listen: Vec<Multiaddr>,
+    #[serde(default)]
+    pub external_addresses: Vec<Multiaddr>,
+}
+
+/// Publishes the listen addresses as an onion service if set.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TorConf {
+    pub control_port: u16,
+    pub socks5_port: u16,
+}
+
+impl Default for TorConf {
+    fn default() -> Self {
+        Self {
+            control_port: DEFAULT_CONTROL_PORT,
+            socks5_port: DEFAULT_SOCKS5_PORT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Registrations {
+    /// Shortest TTL in seconds a seller may register with.
+    #[serde(default)]
+    pub min_ttl_secs: Option<u64>,
+    /// Longest TTL in seconds a seller may register with.
+    #[serde(default)]
+    pub max_ttl_secs: Option<u64>,
+}
+
+impl Registrations {
+    pub fn server_config(&self) -> libp2p::rendezvous::server::Config {
+        let mut config = libp2p::rendezvous::server::Config::default();
+
+        if let Some(min_ttl) = self.min_ttl_secs {
+            config = config.with_min_ttl(min_ttl);
+        }
+        if let Some(max_ttl) = self.max_ttl_secs {
+            config = config.with_max_ttl(max_ttl);
+        }
+
+        config
+    }
+}
+
+pub fn default_config_path() -> Result<PathBuf> {
+    system_config_dir()
+        .map(|dir| dir.join("rendezvous-server").join("config.toml"))
+        .context("Could not generate default config file path")
+}
+
+pub fn default_data_dir() -> Result<PathBuf> {
+    system_data_dir()
+        .map(|dir| dir.join("rendezvous-server"))
+        .context("Could not generate default data dir path")
+}
+
+/// Reads the config file, writing a default one first if it does not exist.
+pub fn read_or_create_config(config_path: PathBuf) -> Result<Config> {
+    if !config_path.exists() {
+        let config = Config::default_with_data_dir(default_data_dir()?)?;
+        let toml = toml::to_string(&config)?;
+
+        ensure_directory_exists(config_path.as_path())?;
+        fs::write(&config_path, toml)?;
+
+        tracing::info!(
+            path = %config_path.display(),
+            "Created default config file",
+        );
+    }
+
+    tracing::info!(
+        path = %config_path.display(),
+        "Reading config file",
+    );
+
+    Config::read(&config_path)
+        .with_context(|| format!("Failed to read config file at {}", config_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn config_roundtrip() {
+        let temp_dir = tempdir().unwrap().path().to_path_buf();
+        let config_path = temp_dir.join("config.toml");
+
+        let expected = Config {
+            tor: Some(TorConf::default()),
+            registrations: Registrations {
+                min_ttl_secs: None,
+                max_ttl_secs: Some(7200),
+            },
+            ..Config::default_with_data_dir(temp_dir.join("data")).unwrap()
+        };
+
+        ensure_directory_exists(&config_path).unwrap();
+        fs::write(&config_path, toml::to_string(&expected).unwrap()).unwrap();
+        let actual = read_or_create_config(config_path).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn registrations_default_when_not_set() {
+        let config: Config = toml::from_str(
+            r#"
+            [data]
+            dir = "/tmp/rendezvous-server"
+
+            [network]
+            listen = ["/ip4/0.0.0.0/tcp/8888"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.tor, None);
+        assert_eq!(config.registrations, Registrations::default());
+    }
+}
@@ -3,6 +3,7 @@ use ::bitcoin::secp256k1::constants::SECRET_KEY_SIZE;
 use ::bitcoin::secp256k1::{self, SecretKey};
 use anyhow::{Context, Result};
 use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bip39::Mnemonic;
 use bitcoin::hashes::{sha256, Hash, HashEngine};
 use libp2p::identity;
 use pem::{encode, Pem};
@@ -56,6 +57,39 @@ impl Seed {
         esk.to_bytes().into()
     }
 
+    /// Encodes the seed as a 24 word BIP39 mnemonic for paper backups.
+    ///
+    /// The words encode the seed bytes directly, they are not stretched into
+    /// a BIP39 seed, so restoring yields exactly the same keys.
+    pub fn to_mnemonic(&self) -> Mnemonic {
+        Mnemonic::from_entropy(&self.0).expect("32 bytes are valid BIP39 entropy")
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Result<Self, Error> {
+        let entropy = Mnemonic::parse_normalized(mnemonic.trim())?.to_entropy();
+
+        if entropy.len() != SEED_LENGTH {
+            return Err(Error::IncorrectLength(entropy.len()));
+        }
+
+        let mut bytes = [0; SEED_LENGTH];
+        bytes.copy_from_slice(&entropy);
+
+        Ok(Self(bytes))
+    }
+
+    /// Writes the seed to the data directory, refusing to overwrite an
+    /// existing seed.
+    pub fn restore(&self, data_dir: &Path) -> Result<(), Error> {
+        let file_path = data_dir.join("seed.pem");
+
+        if file_path.exists() {
+            return Err(Error::SeedExists(file_path));
+        }
+
+        self.write_to(file_path)
+    }
+
     pub fn from_file_or_generate(data_dir: &Path) -> Result<Self, Error> {
         let file_path_buf = data_dir.join("seed.pem");
         let file_path = Path::new(&file_path_buf);
@@ -168,6 +202,10 @@ pub enum Error {
     Rand(#[from] rand::Error),
     #[error("no default path")]
     NoDefaultPath,
+    #[error("Mnemonic: {0}")]
+    Mnemonic(#[from] bip39::Error),
+    #[error("a seed already exists at {0}, refusing to overwrite it")]
+    SeedExists(PathBuf),
 }
 
 #[cfg(test)]
@@ -240,6 +278,44 @@ mbKANv2qKGmNVg1qtquj6Hx1pFPelpqOfE2JaJJAMEg1FlFhNRNlFlE=
         }
     }
 
+    #[test]
+    fn seed_to_mnemonic_matches_bip39_test_vector() {
+        let seed = Seed::from([0u8; SEED_LENGTH]);
+
+        let mnemonic = seed.to_mnemonic().to_string();
+
+        assert_eq!(mnemonic, format!("{} art", ["abandon"; 23].join(" ")));
+    }
+
+    #[test]
+    fn round_trip_through_mnemonic() {
+        let seed = Seed::random().unwrap();
+
+        let restored = Seed::from_mnemonic(&seed.to_mnemonic().to_string()).unwrap();
+
+        assert_eq!(seed, restored);
+    }
+
+    #[test]
+    fn mnemonic_with_wrong_checksum_is_rejected() {
+        let mnemonic = ["abandon"; 24].join(" ");
+
+        assert!(matches!(
+            Seed::from_mnemonic(&mnemonic),
+            Err(Error::Mnemonic(_))
+        ));
+    }
+
+    #[test]
+    fn restore_does_not_overwrite_existing_seed() {
+        let data_dir = tempfile::tempdir().unwrap();
+        Seed::from_file_or_generate(data_dir.path()).unwrap();
+
+        let result = Seed::random().unwrap().restore(data_dir.path());
+
+        assert!(matches!(result, Err(Error::SeedExists(_))));
+    }
+
     #[test]
     fn round_trip_through_file_write_read() {
         let tmpfile = temp_dir().join("seed.pem");
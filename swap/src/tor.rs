@@ -1,6 +1,8 @@
 use anyhow::{bail, Context, Result};
+use libp2p::core::multiaddr::Protocol;
+use libp2p::core::Multiaddr;
 use std::future::Future;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
 use tokio::net::TcpStream;
 use torut::control::{AsyncEvent, AuthenticatedConn, ConnError, UnauthenticatedConn};
 use torut::onion::TorSecretKeyV3;
@@ -126,3 +128,44 @@ impl AuthenticatedClient {
             .context("Failed to add onion service")
     }
 }
+
+/// Registers a hidden service for each network.
+/// Note: Once ac goes out of scope, the services will be de-registered.
+pub async fn register_services(
+    networks: Vec<Multiaddr>,
+    tor_client: Client,
+    key: &TorSecretKeyV3,
+) -> Result<AuthenticatedClient> {
+    let mut ac = tor_client.into_authenticated_client().await?;
+
+    let hidden_services_details = networks
+        .iter()
+        .flat_map(|network| {
+            network.iter().map(|protocol| match protocol {
+                Protocol::Tcp(port) => Some((
+                    port,
+                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), port),
+                )),
+                _ => {
+                    // We only care for Tcp for now.
+                    None
+                }
+            })
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+    ac.add_services(&hidden_services_details, key).await?;
+
+    let onion_address = key
+        .public()
+        .get_onion_address()
+        .get_address_without_dot_onion();
+
+    hidden_services_details.iter().for_each(|(port, _)| {
+        let onion_address = format!("/onion3/{}:{}", onion_address, port);
+        tracing::info!(%onion_address, "Successfully created hidden service");
+    });
+
+    Ok(ac)
+}
@@ -15,14 +15,17 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use swap::asb::FixedRate;
-use swap::bitcoin::{CancelTimelock, PunishTimelock, TxCancel, TxPunish, TxRedeem, TxRefund};
+use swap::bitcoin::{
+    CancelTimelock, FeeStrategy, PunishTimelock, TxCancel, TxKind, TxPunish, TxRedeem, TxRefund,
+};
 use swap::database::SqliteDatabase;
 use swap::env::{Config, GetConfig};
 use swap::fs::ensure_directory_exists;
+use swap::network::swap_setup::bob::PriceProtection;
 use swap::network::swarm;
 use swap::protocol::alice::{AliceState, Swap};
 use swap::protocol::bob::BobState;
-use swap::protocol::{alice, bob};
+use swap::protocol::{alice, bob, Database};
 use swap::seed::Seed;
 use swap::{asb, bitcoin, cli, env, judecoin};
 use tempfile::{tempdir, NamedTempFile};
@@ -244,6 +247,7 @@ async fn start_alice(
         resume_only,
         env_config,
         None,
+        None,
     )
     .unwrap();
     swarm.listen_on(listen_address).unwrap();
@@ -252,11 +256,13 @@ async fn start_alice(
         swarm,
         env_config,
         bitcoin_wallet,
+        None,
         judecoin_wallet,
         db,
         FixedRate::default(),
         min_buy,
         max_buy,
+        None,
     )
     .unwrap();
 
@@ -308,7 +314,7 @@ async fn init_test_wallets(
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
         env_config,
-        1,
+        FeeStrategy::electrum(1),
     )
     .await
     .expect("could not init btc wallet");
@@ -408,8 +414,6 @@ struct BobParams {
 
 impl BobParams {
     pub async fn new_swap_from_db(&self, swap_id: Uuid) -> Result<(bob::Swap, cli::EventLoop)> {
-        let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-
         if let Some(parent_dir) = self.db_path.parent() {
             ensure_directory_exists(parent_dir)?;
         }
@@ -418,6 +422,8 @@ impl BobParams {
         }
         let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
 
+        let (event_loop, handle) = self.new_eventloop(swap_id, db.clone()).await?;
+
         let swap = bob::Swap::from_db(
             db,
             swap_id,
@@ -438,8 +444,6 @@ impl BobParams {
     ) -> Result<(bob::Swap, cli::EventLoop)> {
         let swap_id = Uuid::new_v4();
 
-        let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-
         if let Some(parent_dir) = self.db_path.parent() {
             ensure_directory_exists(parent_dir)?;
         }
@@ -448,6 +452,8 @@ impl BobParams {
         }
         let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
 
+        let (event_loop, handle) = self.new_eventloop(swap_id, db.clone()).await?;
+
         let swap = bob::Swap::new(
             db,
             swap_id,
@@ -458,6 +464,9 @@ impl BobParams {
             self.judecoin_wallet.get_main_address(),
             self.bitcoin_wallet.new_address().await?,
             btc_amount,
+            bitcoin::CoinControl::default(),
+            None,
+            PriceProtection::default(),
         );
 
         Ok((swap, event_loop))
@@ -466,6 +475,7 @@ impl BobParams {
     pub async fn new_eventloop(
         &self,
         swap_id: Uuid,
+        db: Arc<dyn Database + Send + Sync>,
     ) -> Result<(cli::EventLoop, cli::EventLoopHandle)> {
         let tor_socks5_port = get_port()
             .expect("We don't care about Tor in the tests so we get a free port to disable it.");
@@ -474,6 +484,7 @@ impl BobParams {
             self.alice_peer_id,
             self.env_config,
             self.bitcoin_wallet.clone(),
+            self.seed.derive_libp2p_identity().public(),
         );
         let mut swarm = swarm::cli(
             self.seed.derive_libp2p_identity(),
@@ -485,7 +496,7 @@ impl BobParams {
             .behaviour_mut()
             .add_address(self.alice_peer_id, self.alice_address.clone());
 
-        cli::EventLoop::new(swap_id, swarm, self.alice_peer_id, self.env_config)
+        cli::EventLoop::new(swap_id, swarm, self.alice_peer_id, db, self.env_config)
     }
 }
 
@@ -680,12 +691,12 @@ impl TestContext {
 
         let cancel_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxCancel::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Cancel, TxCancel::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         let refund_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxRefund::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Refund, TxRefund::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
 
@@ -728,7 +739,7 @@ impl TestContext {
     async fn alice_redeemed_btc_balance(&self) -> bitcoin::Amount {
         let fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxRedeem::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Redeem, TxRedeem::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         self.alice_starting_balances.btc + self.btc_amount - fee
@@ -771,12 +782,12 @@ impl TestContext {
     async fn alice_punished_btc_balance(&self) -> bitcoin::Amount {
         let cancel_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxCancel::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Cancel, TxCancel::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         let punish_fee = self
             .alice_bitcoin_wallet
-            .estimate_fee(TxPunish::weight(), self.btc_amount)
+            .estimate_fee(TxKind::Punish, TxPunish::weight(), self.btc_amount)
             .await
             .expect("To estimate fee correctly");
         self.alice_starting_balances.btc + self.btc_amount - cancel_fee - punish_fee